@@ -0,0 +1,57 @@
+// Compares stepping the whole grid with only stepping the active regions,
+// on a big grid where a small soup has mostly settled.
+//
+//   cargo bench --bench active_regions
+
+use std::time::{Duration, Instant};
+
+use game_of_life::{config::load_config, headless::Headless};
+
+const SIZE: usize = 2048;
+const SOUP_SIZE: usize = 256;
+/// generations run before timing, for the soup to settle down
+const SETTLE: usize = 2000;
+const GENERATIONS: usize = 2000;
+
+/// Time taken to run `GENERATIONS` generations, and the grid afterwards
+fn run(active_regions: bool) -> anyhow::Result<(Duration, Vec<u32>)> {
+    let mut config = load_config()?;
+    config.resize_grid(SIZE, SIZE);
+    config.fill_region = Some((SOUP_SIZE, SOUP_SIZE));
+    config.seed = Some(1);
+    config.active_regions = active_regions;
+    let mut headless = Headless::new(config)?;
+    headless
+        .game_data
+        .randomise_grid_state(&headless.config, &headless.queue);
+    headless.step(SETTLE);
+    // reading the grid back waits for the GPU to finish
+    headless.read_grid_state()?;
+
+    let start = Instant::now();
+    headless.step(GENERATIONS);
+    let state = headless.read_grid_state()?;
+    Ok((start.elapsed(), state))
+}
+
+fn main() -> anyhow::Result<()> {
+    let (whole, whole_state) = run(false)?;
+    let (active, active_state) = run(true)?;
+    anyhow::ensure!(
+        whole_state == active_state,
+        "active regions ended up with a different grid"
+    );
+
+    let per_generation = |time: Duration| time / GENERATIONS as u32;
+    println!(
+        "{SIZE}x{SIZE} grid, {SOUP_SIZE}x{SOUP_SIZE} soup, generations {SETTLE} to {}",
+        SETTLE + GENERATIONS
+    );
+    println!("whole grid:     {:?} a generation", per_generation(whole));
+    println!("active regions: {:?} a generation", per_generation(active));
+    println!(
+        "speed-up:       {:.1}x",
+        whole.as_secs_f64() / active.as_secs_f64()
+    );
+    Ok(())
+}
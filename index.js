@@ -4,6 +4,19 @@ import init, {
   randomiseState,
   updateFps,
   resetState,
+  census,
+  saveSnapshot,
+  loadSnapshot,
+  downloadSnapshot,
+  loadSnapshotFile,
+  exportPng,
+  toggleRecording,
+  importImage,
+  loadPattern,
+  savePattern,
+  loadRule,
+  setPaintState,
+  shareLink,
 } from "./pkg/game_of_life.js";
 
 async function run() {
@@ -24,6 +37,58 @@ async function run() {
   window.randomiseState = randomiseState;
   window.updateFps = updateFps;
   window.resetState = resetState;
+  window.census = census;
+  window.saveSnapshot = saveSnapshot;
+  window.loadSnapshot = loadSnapshot;
+  window.downloadSnapshot = downloadSnapshot;
+  window.exportPng = exportPng;
+  window.toggleRecording = toggleRecording;
+  window.savePattern = savePattern;
+  window.setPaintState = setPaintState;
+  window.shareLink = shareLink;
+  window.openSnapshotFile = async (input) => {
+    const file = input.files[0];
+    if (file) {
+      loadSnapshotFile(new Uint8Array(await file.arrayBuffer()));
+    }
+    // allow the same file to be picked again
+    input.value = "";
+  };
+  window.openPatternFile = async (input) => {
+    const file = input.files[0];
+    if (file) {
+      loadPattern(await file.text());
+    }
+    input.value = "";
+  };
+  window.openRuleFile = async (input) => {
+    const file = input.files[0];
+    if (file) {
+      loadRule(await file.text(), file.name);
+    }
+    input.value = "";
+  };
+  window.openImageFile = async (input) => {
+    const file = input.files[0];
+    if (file) {
+      importImage(new Uint8Array(await file.arrayBuffer()));
+    }
+    input.value = "";
+  };
+
+  // dropping a picture on the canvas draws it onto the grid
+  const canvas = document.getElementById("canvas");
+  canvas.addEventListener("dragover", (event) => {
+    event.preventDefault();
+    event.dataTransfer.dropEffect = "copy";
+  });
+  canvas.addEventListener("drop", async (event) => {
+    event.preventDefault();
+    const file = event.dataTransfer.files[0];
+    if (file && file.type.startsWith("image/")) {
+      importImage(new Uint8Array(await file.arrayBuffer()));
+    }
+  });
 }
 
 run();
@@ -0,0 +1,304 @@
+// Active regions: only stepping the parts of the grid that are changing.
+//
+// The grid is split into the same 16x16 squares as the compute workgroups.
+// Stepping a square marks it if any of its cells changed, and the next
+// generation only steps the marked squares and the squares around them. The
+// list of squares to step is built on the GPU and dispatched indirectly, so
+// nothing is read back.
+//
+// A square that isn't stepped holds the same cells in both state buffers,
+// since it didn't change the last time it was stepped, so the next state is
+// already right. Anything else that writes to the grid, like painting or
+// loading a pattern, breaks that and has to `wake` every square for a
+// generation.
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    config::{AppConfig, SimulationMode, Topology},
+    rule::RuleKind,
+};
+
+/// side of a square, the same as the compute workgroups
+const SQUARE_SIZE: usize = 16;
+/// workgroup size of the pass that picks the squares
+const SELECT_WORKGROUP_SIZE: u32 = 64;
+
+/// Arguments of `dispatch_workgroups_indirect`. `x` is counted up as squares
+/// are picked, starting from `START`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DispatchArgs {
+    x: u32,
+    y: u32,
+    z: u32,
+}
+
+impl DispatchArgs {
+    const START: Self = Self { x: 0, y: 1, z: 1 };
+}
+
+pub struct ActiveRegion {
+    select_pipeline: wgpu::ComputePipeline,
+    step_pipeline: wgpu::ComputePipeline,
+    /// 1 for each square that changed, one for the last generation and one
+    /// being marked by this one
+    changed_buffers: [wgpu::Buffer; 2],
+    dispatch_buffer: wgpu::Buffer,
+    /// copied over `dispatch_buffer` before the squares are picked
+    start_buffer: wgpu::Buffer,
+    /// the first reads from changed buffer 0, to go with game state buffer A
+    select_bind_groups: [wgpu::BindGroup; 2],
+    step_bind_groups: [wgpu::BindGroup; 2],
+    squares: usize,
+}
+
+impl ActiveRegion {
+    /// Rules that only look at the cells next to them on a bounded grid or
+    /// torus, as long as the squares fit in one indirect dispatch. B0 rules
+    /// are left out as quiet squares don't stay quiet.
+    pub fn is_active(config: &AppConfig, device: &wgpu::Device) -> bool {
+        config.active_regions
+            && config.mode == SimulationMode::Discrete
+            && config.topology != Topology::Unbounded
+            && matches!(
+                config.rule.kind,
+                RuleKind::Totalistic { .. }
+                    | RuleKind::Isotropic { .. }
+                    | RuleKind::Map { .. }
+                    | RuleKind::Hexagonal { .. }
+            )
+            && !config.rule.births_from_nothing()
+            && Self::square_count(config)
+                <= device.limits().max_compute_workgroups_per_dimension as usize
+    }
+
+    fn square_count(config: &AppConfig) -> usize {
+        config.cols.div_ceil(SQUARE_SIZE) * config.rows.div_ceil(SQUARE_SIZE)
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        config: &AppConfig,
+        compute_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+        game_state_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let squares = Self::square_count(config);
+
+        // every square starts off awake
+        let changed = vec![1_u32; squares.max(1)];
+        let changed_buffer = |label| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(&changed),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            })
+        };
+        let changed_buffers = [
+            changed_buffer("Changed Squares A"),
+            changed_buffer("Changed Squares B"),
+        ];
+        let list_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Active Square List Buffer"),
+            size: (squares.max(1) * size_of::<u32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let dispatch_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Active Square Dispatch Buffer"),
+            contents: bytemuck::bytes_of(&DispatchArgs::START),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_DST,
+        });
+        let start_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Active Square Dispatch Start Buffer"),
+            contents: bytemuck::bytes_of(&DispatchArgs::START),
+            usage: wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let buffer_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        // the dispatch buffer can't be bound while it's dispatching the
+        // step, so that gets a layout without it
+        let select_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Select Squares Bind Group Layout"),
+            entries: &[
+                buffer_entry(0, true),
+                buffer_entry(1, false),
+                buffer_entry(2, false),
+                buffer_entry(3, false),
+            ],
+        });
+        let step_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Step Squares Bind Group Layout"),
+            entries: &[buffer_entry(0, true), buffer_entry(1, false)],
+        });
+        let select_bind_group = |label, changed: &wgpu::Buffer, next_changed: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &select_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: changed.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: next_changed.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: list_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: dispatch_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        let step_bind_group = |label, next_changed: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &step_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: list_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: next_changed.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        let select_bind_groups = [
+            select_bind_group(
+                "Select Squares Bind Group A",
+                &changed_buffers[0],
+                &changed_buffers[1],
+            ),
+            select_bind_group(
+                "Select Squares Bind Group B",
+                &changed_buffers[1],
+                &changed_buffers[0],
+            ),
+        ];
+        let step_bind_groups = [
+            step_bind_group("Step Squares Bind Group A", &changed_buffers[1]),
+            step_bind_group("Step Squares Bind Group B", &changed_buffers[0]),
+        ];
+
+        let select_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Select Squares Pipeline Layout"),
+                bind_group_layouts: &[compute_uniform_bind_group_layout, &select_layout],
+                push_constant_ranges: &[],
+            });
+        let select_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Active region shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/active.wgsl").into()),
+        });
+        let select_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Select Squares Pipeline"),
+            layout: Some(&select_pipeline_layout),
+            cache: None,
+            module: &select_shader,
+            entry_point: Some("select_squares"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        });
+
+        let step_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Step Squares Pipeline Layout"),
+            bind_group_layouts: &[
+                compute_uniform_bind_group_layout,
+                game_state_bind_group_layout,
+                &step_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let step_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/compute.wgsl").into()),
+        });
+        let step_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Step Squares Pipeline"),
+            layout: Some(&step_pipeline_layout),
+            cache: None,
+            module: &step_shader,
+            entry_point: Some(if config.rule.is_hexagonal() {
+                "main_hex_active"
+            } else {
+                "main_active"
+            }),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        });
+
+        Self {
+            select_pipeline,
+            step_pipeline,
+            changed_buffers,
+            dispatch_buffer,
+            start_buffer,
+            select_bind_groups,
+            step_bind_groups,
+            squares,
+        }
+    }
+
+    /// Steps every square next generation, after something other than the
+    /// rule has written to the grid
+    pub fn wake(&self, queue: &wgpu::Queue) {
+        let changed = vec![1_u32; self.squares.max(1)];
+        for buffer in &self.changed_buffers {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&changed));
+        }
+    }
+
+    /// Picks the squares near the ones that changed last generation and
+    /// steps them. `is_a_current` picks which changed squares were marked
+    /// last generation the same way it does for the grid.
+    pub fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        compute_uniform_bind_group: &wgpu::BindGroup,
+        game_state_bind_group: &wgpu::BindGroup,
+        is_a_current: bool,
+    ) {
+        let index = !is_a_current as usize;
+        encoder.copy_buffer_to_buffer(
+            &self.start_buffer,
+            0,
+            &self.dispatch_buffer,
+            0,
+            size_of::<DispatchArgs>() as u64,
+        );
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Active Region Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.select_pipeline);
+        compute_pass.set_bind_group(0, compute_uniform_bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.select_bind_groups[index], &[]);
+        compute_pass.dispatch_workgroups(
+            (self.squares as u32).div_ceil(SELECT_WORKGROUP_SIZE),
+            1,
+            1,
+        );
+
+        compute_pass.set_pipeline(&self.step_pipeline);
+        compute_pass.set_bind_group(1, game_state_bind_group, &[]);
+        compute_pass.set_bind_group(2, &self.step_bind_groups[index], &[]);
+        compute_pass.dispatch_workgroups_indirect(&self.dispatch_buffer, 0);
+    }
+}
@@ -0,0 +1,684 @@
+// Object census of a settled grid.
+//
+// The live cells are grouped into clusters, each cluster is put into a
+// canonical form (up to translation, rotation and reflection) and looked up
+// in a table of known still lifes, oscillators and spaceships. Oscillators
+// and spaceships are stored once per phase, so an object is recognised
+// whichever phase the grid was frozen in.
+//
+// The objects are Life's, so under another rule only the ones that go through
+// the same phases as they do in Life are known, and everything else is
+// unidentified.
+
+use crate::{
+    config::Topology,
+    rule::{Rule, RuleKind},
+};
+use anyhow::ensure;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+pub type Cell = (i32, i32);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ObjectKind {
+    StillLife,
+    Oscillator,
+    Spaceship,
+}
+
+/// A named pattern in rows of `o` (alive) and `.` (dead) separated by `/`.
+struct KnownObject {
+    name: &'static str,
+    kind: ObjectKind,
+    pattern: &'static str,
+}
+
+const KNOWN_OBJECTS: &[KnownObject] = &[
+    // still lifes
+    KnownObject {
+        name: "block",
+        kind: ObjectKind::StillLife,
+        pattern: "oo/oo",
+    },
+    KnownObject {
+        name: "beehive",
+        kind: ObjectKind::StillLife,
+        pattern: ".oo./o..o/.oo.",
+    },
+    KnownObject {
+        name: "loaf",
+        kind: ObjectKind::StillLife,
+        pattern: ".oo./o..o/.o.o/..o.",
+    },
+    KnownObject {
+        name: "boat",
+        kind: ObjectKind::StillLife,
+        pattern: "oo./o.o/.o.",
+    },
+    KnownObject {
+        name: "ship",
+        kind: ObjectKind::StillLife,
+        pattern: "oo./o.o/.oo",
+    },
+    KnownObject {
+        name: "tub",
+        kind: ObjectKind::StillLife,
+        pattern: ".o./o.o/.o.",
+    },
+    KnownObject {
+        name: "pond",
+        kind: ObjectKind::StillLife,
+        pattern: ".oo./o..o/o..o/.oo.",
+    },
+    KnownObject {
+        name: "long boat",
+        kind: ObjectKind::StillLife,
+        pattern: "oo../o.o./.o.o/..o.",
+    },
+    KnownObject {
+        name: "long ship",
+        kind: ObjectKind::StillLife,
+        pattern: "oo../o.o./.o.o/..oo",
+    },
+    KnownObject {
+        name: "barge",
+        kind: ObjectKind::StillLife,
+        pattern: ".o../o.o./.o.o/..o.",
+    },
+    KnownObject {
+        name: "long barge",
+        kind: ObjectKind::StillLife,
+        pattern: ".o.../o.o../.o.o./..o.o/...o.",
+    },
+    KnownObject {
+        name: "mango",
+        kind: ObjectKind::StillLife,
+        pattern: ".oo../o..o./.o..o/..oo.",
+    },
+    KnownObject {
+        name: "eater 1",
+        kind: ObjectKind::StillLife,
+        pattern: "oo../o.o./..o./..oo",
+    },
+    KnownObject {
+        name: "snake",
+        kind: ObjectKind::StillLife,
+        pattern: "oo.o/o.oo",
+    },
+    KnownObject {
+        name: "aircraft carrier",
+        kind: ObjectKind::StillLife,
+        pattern: "oo../o..o/..oo",
+    },
+    KnownObject {
+        name: "hat",
+        kind: ObjectKind::StillLife,
+        pattern: "..o../.o.o./.o.o./oo.oo",
+    },
+    // oscillators
+    KnownObject {
+        name: "blinker",
+        kind: ObjectKind::Oscillator,
+        pattern: "ooo",
+    },
+    KnownObject {
+        name: "toad",
+        kind: ObjectKind::Oscillator,
+        pattern: ".ooo/ooo.",
+    },
+    KnownObject {
+        name: "beacon",
+        kind: ObjectKind::Oscillator,
+        pattern: "oo../oo../..oo/..oo",
+    },
+    KnownObject {
+        name: "pulsar",
+        kind: ObjectKind::Oscillator,
+        pattern: "..ooo...ooo../............./o....o.o....o/o....o.o....o/o....o.o....o/..ooo...ooo../............./..ooo...ooo../o....o.o....o/o....o.o....o/o....o.o....o/............./..ooo...ooo..",
+    },
+    KnownObject {
+        name: "pentadecathlon",
+        kind: ObjectKind::Oscillator,
+        pattern: "..o....o../oo.oooo.oo/..o....o..",
+    },
+    // spaceships
+    KnownObject {
+        name: "glider",
+        kind: ObjectKind::Spaceship,
+        pattern: ".o./..o/ooo",
+    },
+    KnownObject {
+        name: "lightweight spaceship",
+        kind: ObjectKind::Spaceship,
+        pattern: ".o..o/o..../o...o/oooo.",
+    },
+    KnownObject {
+        name: "middleweight spaceship",
+        kind: ObjectKind::Spaceship,
+        pattern: "...o../.o...o/o...../o....o/ooooo.",
+    },
+    KnownObject {
+        name: "heavyweight spaceship",
+        kind: ObjectKind::Spaceship,
+        pattern: "...oo../.o....o/o....../o.....o/oooooo.",
+    },
+];
+
+// long enough to see every phase of the pentadecathlon
+const PHASE_GENERATIONS: usize = 30;
+/// most grid hashes kept while waiting for one to come round again
+const MAX_SEEN: usize = 256;
+
+/// Canonical form of every phase of every object known under a rule
+struct KnownPhases(HashMap<Vec<Cell>, &'static KnownObject>);
+
+impl KnownPhases {
+    /// The known objects that go through the same phases under `rule` as in
+    /// Life. Only two state rules that treat every rotation and reflection
+    /// the same are stepped, as canonical forms don't keep the orientation,
+    /// and not B0 ones, where nothing is ever on its own.
+    fn for_rule(rule: &Rule) -> Self {
+        let table = match rule.kind {
+            RuleKind::Totalistic { .. } | RuleKind::Isotropic { .. }
+                if rule.states == 2 && !rule.births_from_nothing() =>
+            {
+                rule.lookup_table()
+            }
+            _ => None,
+        };
+        let Some(table) = table else {
+            return Self(HashMap::new());
+        };
+        let life = Rule::default().lookup_table().unwrap();
+
+        let mut phases = HashMap::new();
+        for object in KNOWN_OBJECTS {
+            let mut cells = parse_pattern(object.pattern);
+            let mut object_phases = Vec::with_capacity(PHASE_GENERATIONS);
+            for _ in 0..PHASE_GENERATIONS {
+                let next = step(&cells, &table);
+                if canonical_form(&next) != canonical_form(&step(&cells, &life)) {
+                    object_phases.clear();
+                    break;
+                }
+                object_phases.push(canonical_form(&cells));
+                cells = next;
+            }
+            for phase in object_phases {
+                phases.entry(phase).or_insert(object);
+            }
+        }
+        Self(phases)
+    }
+
+    fn identify(&self, cells: &[Cell]) -> Option<Object> {
+        self.0
+            .get(&canonical_form(cells))
+            .map(|object| Object::Known {
+                name: object.name,
+                kind: object.kind,
+            })
+    }
+}
+
+/// What a single cluster of live cells was identified as.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Object {
+    Known {
+        name: &'static str,
+        kind: ObjectKind,
+    },
+    Unidentified {
+        population: usize,
+    },
+}
+
+impl Object {
+    pub fn name(&self) -> String {
+        match self {
+            Object::Known { name, .. } => name.to_string(),
+            Object::Unidentified { population } => format!("unidentified ({population} cells)"),
+        }
+    }
+}
+
+/// Object counts of a grid, most common first.
+#[derive(Debug, Default)]
+pub struct Census {
+    pub entries: Vec<(Object, usize)>,
+}
+
+impl Census {
+    /// Checks a census can be taken under `rule`. Hexagonal and one
+    /// dimensional grids can't be split into objects this way.
+    pub fn check_rule(rule: &Rule) -> anyhow::Result<()> {
+        ensure!(
+            !rule.is_hexagonal() && !rule.is_one_dimensional(),
+            "the census only works on square grids, so it can't be taken under {rule}"
+        );
+        Ok(())
+    }
+
+    /// Takes a census of the live cells of a `rows`x`cols` grid state
+    /// stepped by `rule`, see [`Census::check_rule`].
+    pub fn of_grid(
+        state: &[u32],
+        rows: usize,
+        cols: usize,
+        topology: Topology,
+        rule: &Rule,
+    ) -> anyhow::Result<Self> {
+        Self::check_rule(rule)?;
+        let cells = live_cells(state, rows, cols);
+        let wrap = (topology == Topology::Torus).then_some((cols as i32, rows as i32));
+        let mut counts: HashMap<Object, usize> = HashMap::new();
+        for object in identify_objects(&cells, wrap, &KnownPhases::for_rule(rule)) {
+            *counts.entry(object).or_default() += 1;
+        }
+
+        let mut entries: Vec<(Object, usize)> = counts.into_iter().collect();
+        entries.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        Ok(Self { entries })
+    }
+
+    pub fn total_objects(&self) -> usize {
+        self.entries.iter().map(|(_, count)| count).sum()
+    }
+}
+
+impl fmt::Display for Census {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .entries
+            .iter()
+            .map(|(object, _)| object.name().len())
+            .max()
+            .unwrap_or(0)
+            .max("object".len());
+        writeln!(f, "{:<width$}  count", "object")?;
+        writeln!(f, "{:-<width$}  -----", "")?;
+        for (object, count) in &self.entries {
+            writeln!(f, "{:<width$}  {count:>5}", object.name())?;
+        }
+        write!(f, "{} objects", self.total_objects())
+    }
+}
+
+/// Spots a grid settling into a cycle from the hashes of consecutive
+/// generations. Once a hash comes round again the grid repeats from then on,
+/// with a period of the generations in between.
+#[derive(Debug, Default)]
+pub struct Settling {
+    /// generation and hash of the grid at each generation since it last changed
+    seen: Vec<(u64, u64)>,
+    /// hashes of the cycle the grid settled into, while it stays there
+    settled: Vec<u64>,
+}
+
+impl Settling {
+    /// Adds the hash of the grid at `generation`, returning the period the
+    /// first time the grid is found repeating. A generation that doesn't
+    /// follow the last one starts again, as the period can't be told
+    /// across a gap.
+    pub fn check(&mut self, generation: u64, hash: u64) -> Option<u64> {
+        if !self.settled.is_empty() {
+            if self.settled.contains(&hash) {
+                return None;
+            }
+            self.settled.clear();
+            self.seen.clear();
+        }
+        if self
+            .seen
+            .last()
+            .is_some_and(|&(last, _)| generation != last + 1)
+        {
+            self.seen.clear();
+        }
+        match self.seen.iter().position(|&(_, seen)| seen == hash) {
+            Some(first) => {
+                let period = generation - self.seen[first].0;
+                self.settled = self.seen[first..].iter().map(|&(_, seen)| seen).collect();
+                self.seen.clear();
+                Some(period)
+            }
+            None => {
+                if self.seen.len() == MAX_SEEN {
+                    self.seen.remove(0);
+                }
+                self.seen.push((generation, hash));
+                None
+            }
+        }
+    }
+}
+
+pub fn live_cells(state: &[u32], rows: usize, cols: usize) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    for y in 0..rows {
+        for x in 0..cols {
+            if state[x + cols * y] == 1 {
+                cells.push((x as i32, y as i32));
+            }
+        }
+    }
+    cells
+}
+
+/// Splits live cells into objects and identifies each of them.
+///
+/// Cells are first grouped with anything within two cells of them, which
+/// keeps objects such as the toad or pulsar together in phases where they are
+/// not 8-connected. If such a group isn't recognised, it's split again into
+/// 8-connected pieces which are identified one at a time.
+///
+/// `wrap` is the `(cols, rows)` of a torus, if the grid wraps around.
+fn identify_objects(cells: &[Cell], wrap: Option<(i32, i32)>, known: &KnownPhases) -> Vec<Object> {
+    let mut objects = Vec::new();
+    for cluster in clusters(cells, 2, wrap) {
+        if let Some(object) = known.identify(&cluster) {
+            objects.push(object);
+            continue;
+        }
+        // the cluster has already been unwrapped so its pieces don't need to be
+        for piece in clusters(&cluster, 1, None) {
+            objects.push(known.identify(&piece).unwrap_or(Object::Unidentified {
+                population: piece.len(),
+            }));
+        }
+    }
+    objects
+}
+
+/// Groups cells which are within `reach` of each other (Chebyshev distance).
+///
+/// On a torus the neighbours of edge cells are looked up on the opposite edge,
+/// and the returned clusters keep unwrapped coordinates so an object that
+/// straddles an edge comes back in one piece.
+pub fn clusters(cells: &[Cell], reach: i32, wrap: Option<(i32, i32)>) -> Vec<Vec<Cell>> {
+    let wrapped = |(x, y): Cell| match wrap {
+        Some((cols, rows)) => (x.rem_euclid(cols), y.rem_euclid(rows)),
+        None => (x, y),
+    };
+    let mut unvisited: HashSet<Cell> = cells.iter().copied().collect();
+    let mut result = Vec::new();
+
+    // iterate over the input rather than the set so the output is deterministic
+    for &start in cells {
+        if !unvisited.remove(&start) {
+            continue;
+        }
+        let mut cluster = vec![start];
+        let mut queue = VecDeque::from([start]);
+        while let Some((x, y)) = queue.pop_front() {
+            for dy in -reach..=reach {
+                for dx in -reach..=reach {
+                    let neighbour = (x + dx, y + dy);
+                    if unvisited.remove(&wrapped(neighbour)) {
+                        cluster.push(neighbour);
+                        queue.push_back(neighbour);
+                    }
+                }
+            }
+        }
+        result.push(cluster);
+    }
+    result
+}
+
+/// The lexicographically smallest of the 8 rotations/reflections of `cells`,
+/// translated so its bounding box starts at the origin.
+pub fn canonical_form(cells: &[Cell]) -> Vec<Cell> {
+    const TRANSFORMS: [fn(Cell) -> Cell; 8] = [
+        |(x, y)| (x, y),
+        |(x, y)| (-y, x),
+        |(x, y)| (-x, -y),
+        |(x, y)| (y, -x),
+        |(x, y)| (-x, y),
+        |(x, y)| (x, -y),
+        |(x, y)| (y, x),
+        |(x, y)| (-y, -x),
+    ];
+
+    TRANSFORMS
+        .iter()
+        .map(|transform| {
+            let mut transformed: Vec<Cell> = cells.iter().map(|&c| transform(c)).collect();
+            let min_x = transformed.iter().map(|c| c.0).min().unwrap_or(0);
+            let min_y = transformed.iter().map(|c| c.1).min().unwrap_or(0);
+            transformed
+                .iter_mut()
+                .for_each(|c| *c = (c.0 - min_x, c.1 - min_y));
+            transformed.sort_unstable();
+            transformed
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Cell> {
+    pattern
+        .split('/')
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.chars()
+                .enumerate()
+                .filter(|&(_, c)| c == 'o')
+                .map(move |(x, _)| (x as i32, y as i32))
+        })
+        .collect()
+}
+
+/// One generation on an unbounded plane of a two state rule's
+/// `Rule::lookup_table`, which mustn't have B0.
+fn step(cells: &[Cell], table: &[u32]) -> Vec<Cell> {
+    let alive: HashSet<Cell> = cells.iter().copied().collect();
+    let candidates: HashSet<Cell> = cells
+        .iter()
+        .flat_map(|&(x, y)| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dx| (x + dx, y + dy))))
+        .collect();
+    let mut next: Vec<Cell> = candidates
+        .into_iter()
+        .filter(|&(x, y)| {
+            // the most significant bit is the top left, as in `Rule::lookup_table`
+            let mut index = 0;
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    index = index << 1 | alive.contains(&(x + dx, y + dy)) as usize;
+                }
+            }
+            table[index] != 0
+        })
+        .collect();
+    next.sort_unstable();
+    next
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = ".o./..o/ooo";
+
+    fn census(state: &[u32], rows: usize, cols: usize, topology: Topology, rule: &str) -> Census {
+        Census::of_grid(state, rows, cols, topology, &rule.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn canonical_form_ignores_position_and_orientation() {
+        let glider = parse_pattern(GLIDER);
+        let canonical = canonical_form(&glider);
+        assert_eq!(canonical.iter().map(|c| c.0).min(), Some(0));
+        assert_eq!(canonical.iter().map(|c| c.1).min(), Some(0));
+        assert_eq!(canonical.len(), glider.len());
+        let moved: Vec<Cell> = glider.iter().map(|&(x, y)| (x + 17, y - 40)).collect();
+        let turned: Vec<Cell> = glider.iter().map(|&(x, y)| (y, -x)).collect();
+        let mirrored: Vec<Cell> = glider.iter().map(|&(x, y)| (-x, y)).collect();
+        for cells in [moved, turned, mirrored] {
+            assert_eq!(canonical_form(&cells), canonical);
+        }
+        // the order the cells come in doesn't matter either
+        let reversed: Vec<Cell> = glider.iter().rev().copied().collect();
+        assert_eq!(canonical_form(&reversed), canonical);
+    }
+
+    #[test]
+    fn canonical_form_tells_shapes_apart() {
+        let boat = canonical_form(&parse_pattern("oo./o.o/.o."));
+        let ship = canonical_form(&parse_pattern("oo./o.o/.oo"));
+        let glider = canonical_form(&parse_pattern(GLIDER));
+        assert_ne!(boat, ship);
+        assert_ne!(boat, glider);
+        assert!(canonical_form(&[]).is_empty());
+    }
+
+    #[test]
+    fn every_phase_is_recognised() {
+        let known = KnownPhases::for_rule(&Rule::default());
+        let life = Rule::default().lookup_table().unwrap();
+        let mut glider = parse_pattern(GLIDER);
+        for _ in 0..8 {
+            assert_eq!(
+                known.identify(&glider),
+                Some(Object::Known {
+                    name: "glider",
+                    kind: ObjectKind::Spaceship,
+                })
+            );
+            glider = step(&glider, &life);
+        }
+    }
+
+    #[test]
+    fn census_counts_objects() {
+        let (rows, cols) = (10, 10);
+        let mut state = vec![0; rows * cols];
+        // two blocks and a blinker
+        for (x, y) in [
+            (1, 1),
+            (2, 1),
+            (1, 2),
+            (2, 2),
+            (6, 1),
+            (7, 1),
+            (6, 2),
+            (7, 2),
+        ] {
+            state[x + cols * y] = 1;
+        }
+        for x in 3..6 {
+            state[x + cols * 7] = 1;
+        }
+        let census = census(&state, rows, cols, Topology::Bounded, "B3/S23");
+        assert_eq!(census.total_objects(), 3);
+        assert_eq!(census.entries[0].0.name(), "block");
+        assert_eq!(census.entries[0].1, 2);
+        assert_eq!(census.entries[1].0.name(), "blinker");
+    }
+
+    #[test]
+    fn objects_across_the_edge_of_a_torus_are_whole() {
+        let (rows, cols) = (8, 8);
+        let mut state = vec![0; rows * cols];
+        for (x, y) in [(7, 3), (0, 3), (7, 4), (0, 4)] {
+            state[x + cols * y] = 1;
+        }
+        let torus = census(&state, rows, cols, Topology::Torus, "B3/S23");
+        assert_eq!(torus.total_objects(), 1);
+        assert_eq!(torus.entries[0].0.name(), "block");
+        let bounded = census(&state, rows, cols, Topology::Bounded, "B3/S23");
+        assert_eq!(bounded.total_objects(), 2);
+    }
+
+    /// A block and a glider, apart on a 12x12 grid
+    fn block_and_glider() -> Vec<u32> {
+        let cols = 12;
+        let mut state = vec![0; cols * cols];
+        let glider = parse_pattern(GLIDER)
+            .into_iter()
+            .map(|(x, y)| (x + 7, y + 7));
+        for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)].into_iter().chain(glider) {
+            state[x as usize + cols * y as usize] = 1;
+        }
+        state
+    }
+
+    #[test]
+    fn objects_are_known_under_rules_they_behave_the_same_in() {
+        // HighLife's extra birth on 6 doesn't change blocks or gliders
+        let census = census(&block_and_glider(), 12, 12, Topology::Bounded, "B36/S23");
+        let names: Vec<String> = census.entries.iter().map(|(o, _)| o.name()).collect();
+        assert_eq!(names, ["block", "glider"]);
+    }
+
+    #[test]
+    fn objects_are_unidentified_under_rules_they_behave_differently_in() {
+        // in Seeds every live cell dies, and Generations rules and B0 aren't
+        // stepped at all
+        for rule in ["B2/S", "B3/S23/C3", "B03/S23"] {
+            let census = census(&block_and_glider(), 12, 12, Topology::Bounded, rule);
+            assert_eq!(census.total_objects(), 2, "{rule}");
+            assert!(
+                census
+                    .entries
+                    .iter()
+                    .all(|(object, _)| matches!(object, Object::Unidentified { .. })),
+                "{rule}: {census}"
+            );
+        }
+    }
+
+    #[test]
+    fn hexagonal_and_one_dimensional_grids_are_refused() {
+        for rule in ["B2/S34H", "W30"] {
+            let rule: Rule = rule.parse().unwrap();
+            assert!(
+                Census::of_grid(&block_and_glider(), 12, 12, Topology::Bounded, &rule).is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn still_lifes_settle_with_period_1() {
+        let mut settling = Settling::default();
+        assert_eq!(settling.check(10, 1), None);
+        assert_eq!(settling.check(11, 2), None);
+        assert_eq!(settling.check(12, 2), Some(1));
+        // and aren't reported again while they stay
+        assert_eq!(settling.check(13, 2), None);
+        assert_eq!(settling.check(14, 2), None);
+    }
+
+    #[test]
+    fn oscillators_settle_with_their_period() {
+        let mut settling = Settling::default();
+        let hashes = [7, 1, 2, 3, 1, 2, 3, 1];
+        let periods: Vec<Option<u64>> = (0..)
+            .zip(hashes)
+            .map(|(generation, hash)| settling.check(generation, hash))
+            .collect();
+        assert_eq!(periods, [None, None, None, None, Some(3), None, None, None]);
+        // changing again starts over
+        assert_eq!(settling.check(8, 9), None);
+        assert_eq!(settling.check(9, 9), Some(1));
+    }
+
+    #[test]
+    fn gaps_and_resets_start_over() {
+        let mut settling = Settling::default();
+        settling.check(0, 1);
+        settling.check(1, 2);
+        // a skipped generation would make the period look like 2
+        assert_eq!(settling.check(3, 1), None);
+        assert_eq!(settling.check(4, 2), None);
+        assert_eq!(settling.check(5, 1), Some(2));
+
+        let mut settling = Settling::default();
+        settling.check(5, 1);
+        settling.check(6, 2);
+        // going back means the grid was reset or loaded
+        assert_eq!(settling.check(0, 1), None);
+        assert_eq!(settling.check(1, 1), Some(1));
+    }
+}
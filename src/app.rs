@@ -1,9 +1,23 @@
 use crate::{
-    config::{AppConfig, load_config},
+    analysis::Census,
+    config::{AppConfig, ConfigError, ConfigSource, Topology, load_config},
+    export::{self, FrameRenderer},
     game_data::GameData,
     graphics::{self, GraphicsContext},
+    hashlife::HashLife,
+    hud::Hud,
+    image_import,
+    keybindings::Action,
     paint::MousePainter,
+    readback::Readback,
+    recording::Recording,
     render_data::RenderData,
+    rle::Pattern,
+    rule::Rule,
+    rule_table::RuleTable,
+    share::SharedState,
+    snapshot::Snapshot,
+    turmite::Turmites,
 };
 
 use std::cmp;
@@ -12,12 +26,13 @@ use winit::{
     application::ApplicationHandler,
     event::{ElementState, KeyEvent, MouseButton, Touch, TouchPhase, WindowEvent},
     event_loop::{ActiveEventLoop, EventLoop},
-    keyboard::{KeyCode, PhysicalKey},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
     window::Window,
 };
 
 #[cfg(not(target_arch = "wasm32"))]
 use {
+    crate::config_watcher::ConfigWatcher,
     std::{
         iter::repeat_n,
         time::{Duration, Instant},
@@ -27,6 +42,12 @@ use {
 
 #[cfg(target_arch = "wasm32")]
 use {
+    crate::{
+        config::SimulationMode,
+        share::MAX_URL_LEN,
+        web_api::{self, Listeners, Reply, Status},
+        web_files,
+    },
     std::sync::Mutex,
     wasm_bindgen::prelude::*,
     web_sys,
@@ -34,6 +55,9 @@ use {
     winit::event_loop::EventLoopProxy,
 };
 
+/// most kinds of object listed in the HUD's census, so it fits on screen
+const MAX_CENSUS_LINES: usize = 16;
+
 pub enum AppEvents {
     NewGraphicsContext(GraphicsContext),
     PlayPause,
@@ -44,6 +68,82 @@ pub enum AppEvents {
     UpdateRows(usize),
     UpdateCols(usize),
     UpdatePlayPauseUI,
+    Census,
+    CensusReady(Vec<u32>),
+    /// save to a file on desktop and localStorage on the web
+    SaveSnapshot,
+    /// load from a file on desktop and localStorage on the web
+    LoadSnapshot,
+    DownloadSnapshot,
+    LoadSnapshotBytes(Vec<u8>),
+    /// render the current generation to a PNG file, downloaded on the web
+    ExportPng,
+    /// start recording an animation, or stop and save the current one
+    ToggleRecording,
+    /// pixels of a recorded frame, once they've been read back on the web
+    RecordedFrame(Readback<Vec<u8>>),
+    /// PNG or JPEG file to draw onto the grid
+    ImportImage(Vec<u8>),
+    /// RLE pattern to place in the middle of the grid
+    LoadPattern(String),
+    /// save the grid as an RLE pattern, downloaded on the web
+    SavePattern,
+    /// text and file name of a `.rule` or `.table` file to switch to
+    LoadRule(String, String),
+    /// state the mouse paints cells in
+    SetPaintState(u32),
+    /// the grid, once it's been read back on the web, to carry on in HashLife
+    StartHashLife(Vec<u32>),
+    /// switch rule, starting again on an empty grid
+    SetRule(Rule),
+    /// switch topology, starting again on an empty grid
+    SetTopology(Topology),
+    /// a byte for each cell, see `web_api::cells_to_bytes`
+    #[cfg(target_arch = "wasm32")]
+    GetCells(Reply),
+    #[cfg(target_arch = "wasm32")]
+    SetCells(Vec<u8>),
+    #[cfg(target_arch = "wasm32")]
+    GetStatus(Reply),
+    /// the grid as RLE text
+    #[cfg(target_arch = "wasm32")]
+    ExportPattern(Reply),
+    /// called with each new generation
+    #[cfg(target_arch = "wasm32")]
+    OnGeneration(js_sys::Function),
+    /// called with the generation and period once the grid repeats
+    #[cfg(target_arch = "wasm32")]
+    OnStable(js_sys::Function),
+    #[cfg(target_arch = "wasm32")]
+    ClearListeners,
+    /// the generation and hash of the grid, to see if it has settled
+    #[cfg(target_arch = "wasm32")]
+    CheckStable(u64, Readback<u64>),
+    /// put the grid in the page's URL hash
+    #[cfg(target_arch = "wasm32")]
+    ShareLink,
+    #[cfg(target_arch = "wasm32")]
+    ShareLinkReady(Vec<u32>),
+}
+
+/// Options for starting the app
+#[derive(Default)]
+pub struct Startup {
+    /// used instead of reading `appconfig.toml`
+    pub config: Option<AppConfig>,
+    /// file the config came from, watched for changes on desktop
+    pub config_source: Option<ConfigSource>,
+    /// restored as soon as the window is ready
+    pub snapshot: Option<Snapshot>,
+    /// PNG or JPEG file drawn onto the grid once the window is ready
+    pub image: Option<Vec<u8>>,
+    /// placed in the middle of the grid once the window is ready
+    pub pattern: Option<Pattern>,
+    /// grid, rule and speed from a shared link, restored once the window is
+    /// ready
+    pub shared: Option<SharedState>,
+    /// run with HashLife from the start
+    pub hashlife: Option<HashLife>,
 }
 
 // This thread local will allow us to send events from our JS functions to control
@@ -51,6 +151,19 @@ pub enum AppEvents {
 #[cfg(target_arch = "wasm32")]
 thread_local! {pub static EVENT_LOOP_PROXY: Mutex<Option<EventLoopProxy<AppEvents>>> = Mutex::new(None);}
 
+/// Sends an event to the app from outside the event loop, e.g. from a GPU
+/// readback callback.
+#[cfg(target_arch = "wasm32")]
+pub fn send_app_event(event: AppEvents) {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock()
+            && let Some(proxy) = &*guard
+        {
+            let _ = proxy.send_event(event);
+        }
+    })
+}
+
 pub struct App {
     // EventLoopProxy allows for Async code which is needed on the web so the
     //page does not hang when waiting on resources.
@@ -63,15 +176,31 @@ pub struct App {
     next_paint_frame: Instant,
     mouse: Option<MousePainter>,
     config: AppConfig,
+    modifiers: ModifiersState,
+    /// what to load once the grid has been set up
+    startup: Startup,
+    recording: Option<Recording>,
+    /// steps the pattern instead of the GPU while it's set, see hashlife.rs
+    hashlife: Option<HashLife>,
+    /// status and key bindings drawn over the grid on desktop
+    hud: Option<Hud>,
+    /// the last census and the generation it was taken of, for the HUD
+    census: Option<(u64, Census)>,
+    /// reloads the config file when it's saved
+    #[cfg(not(target_arch = "wasm32"))]
+    config_watcher: Option<ConfigWatcher>,
+    /// callbacks from the page embedding the app
+    #[cfg(target_arch = "wasm32")]
+    listeners: Listeners,
 }
 
 impl App {
     pub fn new(
+        config: AppConfig,
         #[cfg(target_arch = "wasm32")] event_loop: &EventLoop<AppEvents>,
     ) -> anyhow::Result<Self> {
         #[cfg(target_arch = "wasm32")]
         let proxy = Some(event_loop.create_proxy());
-        let config = load_config();
 
         let next_frame = Instant::now() + config.frame_duration;
         let next_paint_frame = Instant::now() + config.paint_frame_duration;
@@ -86,6 +215,16 @@ impl App {
             next_paint_frame,
             config,
             mouse: None,
+            modifiers: ModifiersState::empty(),
+            startup: Startup::default(),
+            recording: None,
+            hashlife: None,
+            hud: None,
+            census: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            config_watcher: None,
+            #[cfg(target_arch = "wasm32")]
+            listeners: Listeners::default(),
         })
     }
     #[cfg(target_arch = "wasm32")]
@@ -122,7 +261,8 @@ impl App {
     }
     fn update_fps(&mut self, new_fps: usize) {
         // set limits on fps to be within 0 and 60. clip the values at those limits
-        self.config.frame_duration = Duration::from_millis((1000 / new_fps.clamp(1, 60)) as u64);
+        self.config.fps = new_fps.clamp(1, 60);
+        self.config.frame_duration = Duration::from_millis((1000 / self.config.fps) as u64);
         self.next_frame = Instant::now() + self.config.frame_duration;
     }
 
@@ -156,12 +296,314 @@ impl App {
         if !self.config.is_paused {
             return;
         }
+        self.advance();
+    }
+
+    /// Runs the next generation, or the next jump in HashLife, then records
+    /// it and tells the page about it if there was one
+    fn advance(&mut self) {
+        // every generation is read back while the page waits for the grid
+        // to settle, so none are skipped
+        #[cfg(target_arch = "wasm32")]
+        if self.listeners.is_checking() {
+            return;
+        }
+        let stepped = if self.hashlife.is_some() {
+            self.step_hashlife();
+            true
+        } else if let (Some(gc), Some(game_data)) =
+            (&mut self.graphics_context, &mut self.game_data)
+        {
+            let stepped = gc.update(game_data, &self.config);
+            gc.request_redraw();
+            stepped
+        } else {
+            false
+        };
+        if stepped {
+            self.record_frame();
+            #[cfg(target_arch = "wasm32")]
+            self.notify_listeners();
+        }
+    }
+
+    /// Tells the page about the new generation, and reads the grid back to
+    /// see if it has settled if the page wants to know
+    #[cfg(target_arch = "wasm32")]
+    fn notify_listeners(&mut self) {
+        let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) else {
+            return;
+        };
+        let generation = game_data.generation;
+        self.listeners.generation(generation);
+        if self.listeners.start_check() {
+            game_data.request_grid_state(&gc.device, &gc.queue, move |state| {
+                let hash = state.map(|state| web_api::grid_hash(&state));
+                send_app_event(AppEvents::CheckStable(generation, hash));
+            });
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn get_cells(&self, reply: Reply) {
+        let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) else {
+            return reply.reject("the grid isn't ready yet");
+        };
+        let mode = self.config.mode;
+        game_data.request_grid_state(&gc.device, &gc.queue, move |state| match state {
+            Ok(state) => {
+                let bytes = web_api::cells_to_bytes(&state, mode);
+                reply.resolve(&js_sys::Uint8Array::from(bytes.as_slice()).into());
+            }
+            Err(e) => reply.reject(&format!("unable to read the grid back: {e}")),
+        });
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn set_cells(&mut self, bytes: &[u8]) {
+        if bytes.len() != self.config.num_elements() {
+            log::error!(
+                "Expected {} cells for a {}x{} grid, not {}",
+                self.config.num_elements(),
+                self.config.rows,
+                self.config.cols,
+                bytes.len()
+            );
+            return;
+        }
+        self.stop_hashlife();
         if let (Some(gc), Some(game_data)) = (&mut self.graphics_context, &mut self.game_data) {
-            gc.update(game_data, &self.config);
+            let state = web_api::bytes_to_cells(bytes, self.config.mode);
+            game_data.update_grid_state(&state, &gc.queue);
             gc.request_redraw();
         }
     }
+
+    #[cfg(target_arch = "wasm32")]
+    fn get_status(&self, reply: Reply) {
+        let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) else {
+            return reply.reject("the grid isn't ready yet");
+        };
+        let mut status = Status {
+            generation: game_data.generation,
+            population: 0,
+            rows: self.config.rows,
+            cols: self.config.cols,
+            rule: self.config.rule.to_string(),
+            topology: self.config.topology,
+            paused: self.config.is_paused,
+            fps: 1.0 / self.config.frame_duration.as_secs_f64(),
+        };
+        // HashLife's universe goes on past the grid
+        if let Some(hashlife) = &self.hashlife {
+            status.population = hashlife.population();
+            return reply.resolve(&status.to_js());
+        }
+        game_data.request_grid_state(&gc.device, &gc.queue, move |state| match state {
+            Ok(state) => {
+                status.population = state.iter().filter(|&&cell| cell != 0).count() as u64;
+                reply.resolve(&status.to_js());
+            }
+            Err(e) => reply.reject(&format!("unable to read the grid back: {e}")),
+        });
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn export_pattern(&self, reply: Reply) {
+        let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) else {
+            return reply.reject("the grid isn't ready yet");
+        };
+        let (rows, cols, rule) = (self.config.rows, self.config.cols, self.config.rule.clone());
+        game_data.request_grid_state(&gc.device, &gc.queue, move |state| match state {
+            Ok(state) => {
+                let rle = Pattern::from_grid(&state, rows, cols, &rule).to_rle();
+                reply.resolve(&JsValue::from_str(&rle));
+            }
+            Err(e) => reply.reject(&format!("unable to read the grid back: {e}")),
+        });
+    }
+
+    /// Switches rule or topology, starting again from an empty grid
+    fn switch_grid(&mut self, rule: Option<Rule>, topology: Option<Topology>) {
+        self.stop_hashlife();
+        if let Some(rule) = rule {
+            log::info!("Switching to rule {rule}");
+            self.config.set_rule(rule);
+        }
+        if let Some(topology) = topology {
+            log::info!("Switching to a {topology:?} grid");
+            self.config.topology = topology;
+        }
+        self.rebuild_game_and_render_data();
+        if let Some(gc) = &mut self.graphics_context {
+            gc.request_redraw();
+        }
+    }
+
+    fn step_hashlife(&mut self) {
+        let (Some(hashlife), Some(gc), Some(game_data)) = (
+            &mut self.hashlife,
+            &mut self.graphics_context,
+            &mut self.game_data,
+        ) else {
+            return;
+        };
+        if *hashlife.rule() != self.config.rule {
+            log::info!("Stopped HashLife, the rule has changed");
+            self.hashlife = None;
+            return;
+        }
+        hashlife.step();
+        let state = hashlife.to_grid(self.config.rows, self.config.cols);
+        game_data.show_grid_state(&state, hashlife.generation(), &gc.queue);
+        gc.request_redraw();
+    }
+
+    /// Switches between stepping on the GPU and jumping with HashLife
+    fn toggle_hashlife(&mut self) {
+        if self.hashlife.take().is_some() {
+            log::info!("Stopped HashLife");
+            return;
+        }
+        if !HashLife::supports(&self.config.rule) {
+            log::warn!("HashLife can't run {}", self.config.rule);
+            return;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) {
+            match game_data.read_grid_state(&gc.device, &gc.queue) {
+                Ok(state) => self.start_hashlife(&state),
+                Err(e) => log::error!("Unable to read grid state: {e}"),
+            }
+        }
+        // the readback finishes asynchronously on the web
+        #[cfg(target_arch = "wasm32")]
+        if let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) {
+            game_data.request_grid_state(&gc.device, &gc.queue, |state| match state {
+                Ok(state) => send_app_event(AppEvents::StartHashLife(state)),
+                Err(e) => log::error!("Unable to read grid state: {e}"),
+            });
+        }
+    }
+
+    /// Carries on from `state` in HashLife
+    fn start_hashlife(&mut self, state: &[u32]) {
+        let Some(game_data) = &self.game_data else {
+            return;
+        };
+        match HashLife::from_grid(state, self.config.rows, self.config.cols, &self.config.rule) {
+            Ok(mut hashlife) => {
+                hashlife.set_generation(game_data.generation);
+                log::info!(
+                    "Started HashLife, jumping 2^{} generations a step",
+                    hashlife.step_log2()
+                );
+                self.hashlife = Some(hashlife);
+            }
+            Err(e) => log::error!("Unable to start HashLife: {e}"),
+        }
+    }
+
+    /// Stops HashLife before the grid is replaced
+    fn stop_hashlife(&mut self) {
+        if self.hashlife.take().is_some() {
+            log::info!("Stopped HashLife");
+        }
+    }
+
+    /// Doubles or halves how far each HashLife step jumps
+    fn change_hashlife_step(&mut self, further: bool) {
+        if let Some(hashlife) = &mut self.hashlife {
+            let step_log2 = hashlife.step_log2();
+            hashlife.set_step_log2(if further {
+                step_log2 + 1
+            } else {
+                step_log2.saturating_sub(1)
+            });
+            log::info!(
+                "HashLife jumps 2^{} generations a step",
+                hashlife.step_log2()
+            );
+        }
+    }
+
+    /// Runs a macrocell pattern in HashLife, showing the middle of it
+    fn load_macrocell(&mut self, hashlife: HashLife) {
+        if *hashlife.rule() != self.config.rule {
+            log::info!("Switching to rule {}", hashlife.rule());
+            self.config.set_rule(hashlife.rule().clone());
+            self.rebuild_game_and_render_data();
+        }
+        if let (Some(gc), Some(game_data)) = (&mut self.graphics_context, &mut self.game_data) {
+            let state = hashlife.to_grid(self.config.rows, self.config.cols);
+            game_data.update_grid_state(&state, &gc.queue);
+            game_data.generation = hashlife.generation();
+            gc.request_redraw();
+        }
+        log::info!(
+            "Loaded a macrocell pattern of {} cells",
+            hashlife.population()
+        );
+        self.hashlife = Some(hashlife);
+    }
+
+    /// The grid as a macrocell file, or the whole of HashLife's universe
+    /// while it's running
+    fn macrocell(&self, state: &[u32], generation: u64) -> anyhow::Result<String> {
+        if let Some(hashlife) = &self.hashlife {
+            return Ok(hashlife.to_macrocell());
+        }
+        let mut hashlife =
+            HashLife::from_grid(state, self.config.rows, self.config.cols, &self.config.rule)?;
+        hashlife.set_generation(generation);
+        Ok(hashlife.to_macrocell())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_macrocell(&self) {
+        use crate::hashlife::DEFAULT_MACROCELL_PATH;
+
+        if let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) {
+            let result = game_data
+                .read_grid_state(&gc.device, &gc.queue)
+                .and_then(|state| self.macrocell(&state, game_data.generation))
+                .and_then(|text| Ok(std::fs::write(DEFAULT_MACROCELL_PATH, text)?));
+            match result {
+                Ok(()) => println!("Saved pattern to {DEFAULT_MACROCELL_PATH}"),
+                Err(e) => log::error!("Unable to save pattern: {e}"),
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save_macrocell(&self) {
+        use crate::hashlife::DEFAULT_MACROCELL_PATH;
+
+        let save = |text: anyhow::Result<String>| {
+            let result = text
+                .and_then(|text| save_file(DEFAULT_MACROCELL_PATH, text.as_bytes(), "text/plain"));
+            if let Err(e) = result {
+                log::error!("Unable to save pattern: {e}");
+            }
+        };
+        if let Some(hashlife) = &self.hashlife {
+            save(Ok(hashlife.to_macrocell()));
+        } else if let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) {
+            let (rows, cols, rule) = (self.config.rows, self.config.cols, self.config.rule.clone());
+            let generation = game_data.generation;
+            game_data.request_grid_state(&gc.device, &gc.queue, move |state| {
+                save(state.map_err(anyhow::Error::from).and_then(|state| {
+                    HashLife::from_grid(&state, rows, cols, &rule).map(|mut hashlife| {
+                        hashlife.set_generation(generation);
+                        hashlife.to_macrocell()
+                    })
+                }));
+            });
+        }
+    }
     fn reset_state(&mut self) {
+        self.stop_hashlife();
+        self.census = None;
         if let (Some(game_data), Some(graphics_context), Some(render_data)) = (
             &mut self.game_data,
             &mut self.graphics_context,
@@ -169,11 +611,7 @@ impl App {
         ) {
             game_data.reset_grid_state(&self.config, &graphics_context.queue);
             game_data.is_a_current = true;
-            match graphics_context.render(
-                render_data,
-                game_data.get_current_render_bind_group(),
-                &self.config,
-            ) {
+            match graphics_context.render(render_data, game_data, &self.config, self.hud.as_ref()) {
                 Ok(_) => {}
                 Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
                     let (width, height) = graphics_context.get_size().unwrap();
@@ -187,18 +625,27 @@ impl App {
     }
 
     fn randomise_state(&mut self) {
+        self.stop_hashlife();
+        self.census = None;
         if let (Some(game_data), Some(graphics_context), Some(render_data)) = (
             &mut self.game_data,
             &mut self.graphics_context,
             &mut self.render_data,
         ) {
-            game_data.randomise_grid_state(&self.config, &graphics_context.queue);
-            game_data.is_a_current = true;
-            match graphics_context.render(
-                render_data,
-                game_data.get_current_render_bind_group(),
-                &self.config,
-            ) {
+            let selection = self.mouse.as_ref().and_then(|mouse| mouse.selection);
+            let seed = match selection {
+                Some(selection) => {
+                    game_data.randomise_selection(selection, &self.config, &graphics_context.queue)
+                }
+                None => {
+                    let seed =
+                        game_data.randomise_grid_state(&self.config, &graphics_context.queue);
+                    game_data.is_a_current = true;
+                    seed
+                }
+            };
+            show_seed(seed);
+            match graphics_context.render(render_data, game_data, &self.config, self.hud.as_ref()) {
                 Ok(_) => {}
                 Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
                     let (width, height) = graphics_context.get_size().unwrap();
@@ -210,32 +657,298 @@ impl App {
             }
         }
     }
+    #[cfg(not(target_arch = "wasm32"))]
+    fn census(&mut self) {
+        if let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) {
+            match game_data.read_grid_state(&gc.device, &gc.queue) {
+                Ok(state) => self.show_census(&state),
+                Err(e) => log::error!("Unable to read grid state: {e}"),
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn census(&mut self) {
+        // the readback finishes asynchronously on the web so the result comes
+        // back to us as a CensusReady event
+        if let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) {
+            game_data.request_grid_state(&gc.device, &gc.queue, |state| match state {
+                Ok(state) => send_app_event(AppEvents::CensusReady(state)),
+                Err(e) => log::error!("Unable to read the grid back for the census: {e}"),
+            });
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn show_census(&mut self, state: &[u32]) {
+        let census = match Census::of_grid(
+            state,
+            self.config.rows,
+            self.config.cols,
+            self.config.topology,
+            &self.config.rule,
+        ) {
+            Ok(census) => census,
+            Err(e) => {
+                log::error!("Unable to take a census: {e}");
+                return;
+            }
+        };
+        println!("{census}");
+        let generation = self
+            .game_data
+            .as_ref()
+            .map_or(0, |game_data| game_data.generation);
+        self.census = Some((generation, census));
+        if let Some(hud) = &mut self.hud {
+            hud.visible = true;
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn show_census(&self, state: &[u32]) {
+        use wasm_bindgen::UnwrapThrowExt;
+
+        let census = match Census::of_grid(
+            state,
+            self.config.rows,
+            self.config.cols,
+            self.config.topology,
+            &self.config.rule,
+        ) {
+            Ok(census) => census,
+            Err(e) => {
+                log::error!("Unable to take a census: {e}");
+                return;
+            }
+        };
+        let rows: String = census
+            .entries
+            .iter()
+            .map(|(object, count)| format!("<tr><td>{}</td><td>{count}</td></tr>", object.name()))
+            .collect();
+
+        let window = web_sys::window().unwrap_throw();
+        let document = window.document().unwrap_throw();
+        let table = document.get_element_by_id("censusTable").unwrap_throw();
+        table.set_inner_html(&format!(
+            "<tr><th>Object</th><th>Count</th></tr>{rows}<tr><td>Total</td><td>{}</td></tr>",
+            census.total_objects()
+        ));
+    }
+
     fn handle_key(&mut self, event_loop: &ActiveEventLoop, code: KeyCode, is_pressed: bool) {
-        match (code, is_pressed) {
-            (KeyCode::Escape, true) => event_loop.exit(),
-            (KeyCode::Space, true) => self.play_pause(),
-            (KeyCode::ArrowRight, true) => self.step_forward(),
-            (KeyCode::KeyR, true) => self.reset_state(),
-            (_, _) => (),
+        if !is_pressed {
+            return;
+        }
+        let Some(action) = self.config.keybindings.action(code, self.modifiers) else {
+            if let Some(state) = paint_state_key(code) {
+                self.config.set_paint_state(state);
+            }
+            return;
+        };
+        match action {
+            Action::Quit => event_loop.exit(),
+            Action::PlayPause => self.play_pause(),
+            Action::Step => self.step_forward(),
+            Action::Reset => self.reset_state(),
+            Action::Randomise => self.randomise_state(),
+            Action::Census => self.census(),
+            Action::ExportPng => self.export_png(),
+            Action::ToggleRecording => self.toggle_recording(),
+            Action::ToggleHashlife => self.toggle_hashlife(),
+            Action::FasterHashlife => self.change_hashlife_step(true),
+            Action::SlowerHashlife => self.change_hashlife_step(false),
+            Action::SaveSnapshot => self.save_snapshot(),
+            Action::LoadSnapshot => {
+                self.load_snapshot();
+                #[cfg(not(target_arch = "wasm32"))]
+                self.reset_cursor(event_loop);
+            }
+            Action::SavePattern => self.save_pattern(),
+            Action::SaveMacrocell => self.save_macrocell(),
+            Action::ToggleHud => {
+                if let Some(hud) = &mut self.hud {
+                    hud.visible = !hud.visible;
+                }
+            }
+            Action::Help => match &mut self.hud {
+                Some(hud) => {
+                    hud.show_help = !hud.show_help;
+                    hud.visible |= hud.show_help;
+                }
+                None => log::info!("Key bindings:\n{}", self.config.keybindings),
+            },
         }
     }
 
     fn setup_game_and_render_data(&mut self) {
         if let Some(graphics_context) = &self.graphics_context {
-            let device = &graphics_context.device;
             // use the graphics context to get the window dims which we will use to calculate the num
             // of rows and columns of cells we want to start with
             self.config
                 .update_cell_configuration(&graphics_context.window);
+            // the web page has its own controls
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                self.hud = Some(Hud::new(
+                    &graphics_context.device,
+                    graphics_context.surface_config.format,
+                ));
+            }
+            self.rebuild_game_and_render_data();
+            println!("got the gc set up and all");
+
+            // Initialize the play/pause button UI state
+            self.update_play_pause_ui();
+
+            let startup = std::mem::take(&mut self.startup);
+            if let Some(snapshot) = startup.snapshot {
+                self.restore_snapshot(snapshot);
+            }
+            if let Some(shared) = startup.shared {
+                self.restore_shared(shared);
+            }
+            if let Some(image) = startup.image {
+                self.import_image(&image);
+            }
+            if let Some(pattern) = startup.pattern {
+                self.load_pattern(pattern);
+            }
+            if let Some(hashlife) = startup.hashlife {
+                self.load_macrocell(hashlife);
+            }
+        }
+    }
+
+    /// Picks up changes to the config file, redrawing the grid as it is with
+    /// the new settings
+    #[cfg(not(target_arch = "wasm32"))]
+    fn reload_config(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(reload) = self
+            .config_watcher
+            .as_mut()
+            .and_then(|watcher| watcher.poll(&mut self.config))
+        else {
+            return;
+        };
+        if !reload.applied.is_empty() {
+            log::info!("Reloaded {}", reload.applied.join(", "));
+        }
+        if !reload.need_restart.is_empty() {
+            log::warn!("Restart to use the new {}", reload.need_restart.join(", "));
+        }
+        // the frame times are picked up when the next frames are scheduled
+        if reload.redraw_cells
+            && let Some(graphics_context) = &self.graphics_context
+        {
+            let device = &graphics_context.device;
+            self.render_data = Some(
+                RenderData::new(
+                    device,
+                    graphics_context.surface_config.format,
+                    &GameData::get_render_bind_group_layout(device),
+                    &self.config,
+                )
+                .unwrap(),
+            );
+        }
+        if reload.new_cursor {
+            self.reset_cursor(event_loop);
+        }
+    }
+
+    /// Writes the current status, and the key bindings if they're wanted,
+    /// into the HUD
+    fn update_hud(&mut self) {
+        let (Some(hud), Some(gc), Some(game_data)) =
+            (&mut self.hud, &self.graphics_context, &self.game_data)
+        else {
+            return;
+        };
+        if !hud.visible {
+            return;
+        }
+        let population = match &self.hashlife {
+            Some(hashlife) => Some(hashlife.population()),
+            None => hud.population(&gc.device, &gc.queue, game_data),
+        };
+        let rate = hud.generation_rate(game_data.generation);
+
+        let mut lines = vec![
+            format!("Generation {}", game_data.generation),
+            match population {
+                Some(population) => format!("Population {population}"),
+                None => "Population ...".to_string(),
+            },
+            format!(
+                "{:.0} gen/s of {:.0}",
+                rate,
+                1.0 / self.config.frame_duration.as_secs_f64()
+            ),
+            format!("Rule {}", self.config.rule),
+            if self.config.is_paused {
+                "Paused".to_string()
+            } else {
+                "Running".to_string()
+            },
+        ];
+        if let Some(hashlife) = &self.hashlife {
+            lines.push(format!("HashLife 2^{} a step", hashlife.step_log2()));
+        }
+        if let Some(selection) = self.mouse.as_ref().and_then(|mouse| mouse.selection) {
+            lines.push(format!(
+                "Selected {}x{} at ({}, {})",
+                selection.cols, selection.rows, selection.x, selection.y
+            ));
+        }
+        if let Some((generation, census)) = &self.census {
+            lines.push(String::new());
+            lines.push(format!("Census of generation {generation}"));
+            // the header, then a line for each kind of object
+            let shown = census.entries.len().min(MAX_CENSUS_LINES);
+            lines.extend(census.to_string().lines().take(2 + shown).map(String::from));
+            if census.entries.len() > shown {
+                lines.push(format!("and {} more", census.entries.len() - shown));
+            }
+            lines.push(format!("{} objects", census.total_objects()));
+        }
+        if hud.show_help {
+            lines.push(String::new());
+            lines.extend(
+                self.config
+                    .keybindings
+                    .to_string()
+                    .lines()
+                    .map(String::from),
+            );
+        } else if let Some(key) = self.config.keybindings.key(Action::Help) {
+            lines.push(format!("{key} for keys"));
+        }
+
+        let (width, height) = gc.get_window_size();
+        let scale = (gc.window.scale_factor() * 2.0).round().max(1.0) as f32;
+        hud.set_lines(&gc.queue, &lines, (width, height), scale);
+    }
+
+    /// (Re)creates everything sized by the grid from the current config
+    fn rebuild_game_and_render_data(&mut self) {
+        self.census = None;
+        // frames of a different size can't go in the same animation
+        if let Some(recording) = &mut self.recording {
+            recording.stop();
+            self.finish_recording_if_done();
+        }
+        if let Some(graphics_context) = &self.graphics_context {
+            let device = &graphics_context.device;
             self.game_data = Some(GameData::new(device, &self.config));
             // now that the graphics context is setup we can setup the render_pipeline if it's not there already
             // setup the render stuff now that the window and surface configurations are made
 
-            let surface_config = &graphics_context.surface_config;
             self.render_data = Some(
                 RenderData::new(
                     device,
-                    surface_config,
+                    graphics_context.surface_config.format,
                     &GameData::get_render_bind_group_layout(device),
                     &self.config,
                 )
@@ -249,12 +962,397 @@ impl App {
                 &self.config,
                 graphics_context.window.clone(),
             ));
-            println!("got the gc set up and all");
+        }
+    }
 
-            // Initialize the play/pause button UI state
-            self.update_play_pause_ui();
+    fn restore_snapshot(&mut self, snapshot: Snapshot) {
+        self.stop_hashlife();
+        snapshot.apply_to_config(&mut self.config);
+        self.rebuild_game_and_render_data();
+        if let (Some(gc), Some(game_data)) = (&mut self.graphics_context, &mut self.game_data) {
+            game_data.update_grid_state(&snapshot.cells, &gc.queue);
+            game_data.generation = snapshot.generation;
+            gc.request_redraw();
+            log::info!(
+                "Restored a {}x{} snapshot at generation {}",
+                snapshot.rows,
+                snapshot.cols,
+                snapshot.generation
+            );
+        }
+    }
+
+    fn restore_shared(&mut self, shared: SharedState) {
+        self.stop_hashlife();
+        shared.apply_to_config(&mut self.config);
+        self.rebuild_game_and_render_data();
+        if let (Some(gc), Some(game_data)) = (&mut self.graphics_context, &mut self.game_data) {
+            if let Some(cells) = &shared.cells {
+                game_data.update_grid_state(cells, &gc.queue);
+            }
+            gc.request_redraw();
+            log::info!(
+                "Opened a {}x{} grid running {} from a link",
+                shared.rows,
+                shared.cols,
+                shared.rule
+            );
+        }
+        #[cfg(target_arch = "wasm32")]
+        show_fps(self.config.fps);
+    }
+
+    /// Reads the grid back to put it in the URL, which happens once it
+    /// arrives as a ShareLinkReady event
+    #[cfg(target_arch = "wasm32")]
+    fn share_link(&self) {
+        if let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) {
+            game_data.request_grid_state(&gc.device, &gc.queue, |state| match state {
+                Ok(state) => send_app_event(AppEvents::ShareLinkReady(state)),
+                Err(e) => show_share_message(&format!("Unable to read the grid back: {e}")),
+            });
+        }
+    }
+
+    /// Puts the grid in the URL hash, leaving the cells out if they'd make
+    /// the link too long to pass around
+    #[cfg(target_arch = "wasm32")]
+    fn share_link_ready(&self, state: Vec<u32>) {
+        // snapshots and patterns only keep whole number states too
+        let is_discrete = self.config.mode == SimulationMode::Discrete;
+        let is_unbounded = self.config.topology == Topology::Unbounded;
+        let mut shared = SharedState::new(
+            &self.config,
+            (is_discrete && !is_unbounded).then_some(state),
+        );
+        let link = shared.to_hash().and_then(|hash| {
+            let page = web_files::location_without_hash()
+                .map_err(|e| anyhow::anyhow!("unable to read the page's URL: {e:?}"))?;
+            let len = page.len() + 1 + hash.len();
+            if len <= MAX_URL_LEN || shared.cells.is_none() {
+                return Ok((hash, len));
+            }
+            shared.cells = None;
+            Ok((shared.to_hash()?, len))
+        });
+        let message = match link {
+            Ok((hash, len)) => match web_files::replace_location_hash(&hash) {
+                Ok(()) if !is_discrete => "Continuous grids can't go in a link, so it only has \
+                     the rule, grid size, topology and speed"
+                    .to_string(),
+                Ok(()) if is_unbounded => "Unbounded universes spread past the grid, so the \
+                     link only has the rule, grid size, topology and speed. Patterns can be \
+                     shared on a bounded grid or a torus"
+                    .to_string(),
+                Ok(()) if len > MAX_URL_LEN => format!(
+                    "The pattern would make the link {len} characters long, more than the \
+                     {MAX_URL_LEN} links can safely be, so it only has the rule, grid size, \
+                     topology and speed. Download a snapshot to share the pattern"
+                ),
+                Ok(()) => "Link updated, copy it from the address bar".to_string(),
+                Err(e) => format!("Unable to update the link: {e:?}"),
+            },
+            Err(e) => format!("Unable to make a link: {e:#}"),
+        };
+        show_share_message(&message);
+    }
+
+    fn import_image(&mut self, bytes: &[u8]) {
+        self.stop_hashlife();
+        if let (Some(gc), Some(game_data)) = (&mut self.graphics_context, &mut self.game_data) {
+            match image_import::image_to_state(bytes, &self.config) {
+                Ok(state) => {
+                    game_data.update_grid_state(&state, &gc.queue);
+                    gc.request_redraw();
+                }
+                Err(e) => log::error!("Unable to import image: {e}"),
+            }
+        }
+    }
+
+    /// Switches to the pattern's rule, if it has one, and places the pattern
+    /// in the middle of a cleared grid
+    fn load_pattern(&mut self, pattern: Pattern) {
+        self.stop_hashlife();
+        if let Some(rule) = &pattern.rule
+            && *rule != self.config.rule
+        {
+            log::info!("Switching to rule {rule}");
+            self.config.set_rule(rule.clone());
+            self.rebuild_game_and_render_data();
+        }
+        if let (Some(gc), Some(game_data)) = (&mut self.graphics_context, &mut self.game_data) {
+            match pattern.to_grid(self.config.rows, self.config.cols) {
+                Ok(state) => {
+                    game_data.update_grid_state(&state, &gc.queue);
+                    gc.request_redraw();
+                }
+                Err(e) => log::error!("Unable to load pattern: {e}"),
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_pattern(&self) {
+        use crate::rle::DEFAULT_PATTERN_PATH;
+
+        if let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) {
+            let result = game_data
+                .read_grid_state(&gc.device, &gc.queue)
+                .and_then(|state| {
+                    let pattern = Pattern::from_grid(
+                        &state,
+                        self.config.rows,
+                        self.config.cols,
+                        &self.config.rule,
+                    );
+                    Ok(std::fs::write(DEFAULT_PATTERN_PATH, pattern.to_rle())?)
+                });
+            match result {
+                Ok(()) => println!("Saved pattern to {DEFAULT_PATTERN_PATH}"),
+                Err(e) => log::error!("Unable to save pattern: {e}"),
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save_pattern(&self) {
+        if let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) {
+            let (rows, cols, rule) = (self.config.rows, self.config.cols, self.config.rule.clone());
+            game_data.request_grid_state(&gc.device, &gc.queue, move |state| {
+                let result = state.map_err(anyhow::Error::from).and_then(|state| {
+                    let rle = Pattern::from_grid(&state, rows, cols, &rule).to_rle();
+                    save_file(
+                        crate::rle::DEFAULT_PATTERN_PATH,
+                        rle.as_bytes(),
+                        "text/plain",
+                    )
+                });
+                if let Err(e) = result {
+                    log::error!("Unable to save pattern: {e}");
+                }
+            });
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_snapshot(&self) {
+        use crate::snapshot::DEFAULT_SNAPSHOT_PATH;
+
+        if let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) {
+            let result = game_data
+                .read_grid_state(&gc.device, &gc.queue)
+                .and_then(|cells| {
+                    Snapshot::new(&self.config, game_data.generation, cells)
+                        .save(DEFAULT_SNAPSHOT_PATH)
+                });
+            match result {
+                Ok(()) => println!("Saved snapshot to {DEFAULT_SNAPSHOT_PATH}"),
+                Err(e) => log::error!("Unable to save snapshot: {e}"),
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_snapshot(&mut self) {
+        match Snapshot::load(crate::snapshot::DEFAULT_SNAPSHOT_PATH) {
+            Ok(snapshot) => self.restore_snapshot(snapshot),
+            Err(e) => log::error!("Unable to load snapshot: {e}"),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save_snapshot(&self) {
+        self.with_snapshot(|snapshot| snapshot.save_to_local_storage());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn download_snapshot(&self) {
+        self.with_snapshot(|snapshot| snapshot.download());
+    }
+
+    /// Reads the grid back and hands a snapshot of it to `save` once the
+    /// readback has finished
+    #[cfg(target_arch = "wasm32")]
+    fn with_snapshot(&self, save: impl FnOnce(&Snapshot) -> anyhow::Result<()> + 'static) {
+        if let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) {
+            let mut snapshot = Snapshot::new(&self.config, game_data.generation, Vec::new());
+            game_data.request_grid_state(&gc.device, &gc.queue, move |cells| {
+                let result = cells.map_err(anyhow::Error::from).and_then(|cells| {
+                    snapshot.cells = cells;
+                    save(&snapshot)
+                });
+                if let Err(e) = result {
+                    log::error!("Unable to save snapshot: {e}");
+                }
+            });
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn load_snapshot(&mut self) {
+        match Snapshot::load_from_local_storage() {
+            Ok(snapshot) => self.restore_snapshot(snapshot),
+            Err(e) => log::error!("Unable to load snapshot: {e}"),
+        }
+    }
+
+    fn frame_renderer(&self) -> Option<FrameRenderer> {
+        let gc = self.graphics_context.as_ref()?;
+        let size = export::export_size(&self.config, gc.device.limits().max_texture_dimension_2d);
+        FrameRenderer::new(&gc.device, &self.config, size)
+            .inspect_err(|e| log::error!("Unable to export image: {e}"))
+            .ok()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_png(&self) {
+        if let (Some(gc), Some(game_data), Some(renderer)) = (
+            &self.graphics_context,
+            &self.game_data,
+            self.frame_renderer(),
+        ) {
+            let path = export::frame_file_name(game_data.generation);
+            let result = renderer
+                .render_blocking(&gc.device, &gc.queue, game_data, &self.config)
+                .and_then(|pixels| export::encode_png(renderer.width, renderer.height, &pixels))
+                .and_then(|png| Ok(std::fs::write(&path, png)?));
+            match result {
+                Ok(()) => println!("Exported {path}"),
+                Err(e) => log::error!("Unable to export image: {e}"),
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn export_png(&self) {
+        if let (Some(gc), Some(game_data), Some(renderer)) = (
+            &self.graphics_context,
+            &self.game_data,
+            self.frame_renderer(),
+        ) {
+            let file_name = export::frame_file_name(game_data.generation);
+            let (width, height) = (renderer.width, renderer.height);
+            renderer.render(
+                &gc.device,
+                &gc.queue,
+                game_data,
+                &self.config,
+                move |pixels| {
+                    let result = pixels
+                        .map_err(anyhow::Error::from)
+                        .and_then(|pixels| export::encode_png(width, height, &pixels));
+                    let result = result.and_then(|png| {
+                        crate::web_files::download_bytes(&file_name, &png, "image/png")
+                            .map_err(|e| anyhow::anyhow!("{e:?}"))
+                    });
+                    if let Err(e) = result {
+                        log::error!("Unable to export image: {e}");
+                    }
+                },
+            );
+        }
+    }
+
+    fn toggle_recording(&mut self) {
+        match &mut self.recording {
+            Some(recording) => {
+                recording.stop();
+                self.finish_recording_if_done();
+            }
+            None => {
+                let (Some(gc), Some(game_data)) = (&self.graphics_context, &self.game_data) else {
+                    return;
+                };
+                match Recording::new(
+                    &gc.device,
+                    &self.config,
+                    game_data,
+                    self.config.recording.format,
+                ) {
+                    Ok(recording) => {
+                        log::info!("Recording started");
+                        self.recording = Some(recording);
+                        self.capture_recording_frame();
+                    }
+                    Err(e) => log::error!("Unable to start recording: {e}"),
+                }
+            }
         }
+        self.update_recording_ui();
     }
+
+    /// Records the generation that was just stepped to, if it's on the stride
+    fn record_frame(&mut self) {
+        if let (Some(recording), Some(game_data)) = (&self.recording, &self.game_data)
+            && recording.wants_generation(game_data.generation)
+        {
+            self.capture_recording_frame();
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn capture_recording_frame(&mut self) {
+        if let (Some(gc), Some(game_data), Some(recording)) =
+            (&self.graphics_context, &self.game_data, &mut self.recording)
+            && let Err(e) = recording.capture_frame(&gc.device, &gc.queue, game_data, &self.config)
+        {
+            log::error!("Unable to record frame: {e}");
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn capture_recording_frame(&mut self) {
+        // frames come back as RecordedFrame events once they've been read back
+        if let (Some(gc), Some(game_data), Some(recording)) =
+            (&self.graphics_context, &self.game_data, &mut self.recording)
+        {
+            recording.request_frame(&gc.device, &gc.queue, game_data, &self.config, |pixels| {
+                send_app_event(AppEvents::RecordedFrame(pixels))
+            });
+        }
+    }
+
+    /// Encodes and saves the recording once it's stopped and has all its frames
+    fn finish_recording_if_done(&mut self) {
+        if !self.recording.as_ref().is_some_and(Recording::is_finished) {
+            return;
+        }
+        if let Some(recording) = self.recording.take() {
+            let generation = self.game_data.as_ref().map_or(0, |g| g.generation);
+            let file_name = recording.file_name(generation);
+            let result = recording
+                .encode()
+                .and_then(|bytes| save_file(&file_name, &bytes, recording.format.mime_type()));
+            match result {
+                Ok(()) => log::info!("Saved {} frames to {file_name}", recording.frame_count()),
+                Err(e) => log::error!("Unable to save recording: {e}"),
+            }
+        }
+        self.update_recording_ui();
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn update_recording_ui(&self) {
+        use wasm_bindgen::UnwrapThrowExt;
+
+        let window = web_sys::window().unwrap_throw();
+        let document = window.document().unwrap_throw();
+        let button = document.get_element_by_id("toggleRecording").unwrap_throw();
+        if self.recording.is_some() {
+            button.set_attribute("class", "recording").unwrap_throw();
+            button.set_text_content(Some("■ Stop Recording"));
+        } else {
+            button.remove_attribute("class").unwrap_throw();
+            button.set_text_content(Some("● Record"));
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn update_recording_ui(&self) {
+        // No-op for desktop version
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     fn reset_cursor(&mut self, event_loop: &ActiveEventLoop) {
         if let Some(graphics_context) = &self.graphics_context {
@@ -287,10 +1385,60 @@ impl App {
     }
 }
 
+/// The digit keys pick which state the mouse paints
+fn paint_state_key(code: KeyCode) -> Option<u32> {
+    let digits = [
+        KeyCode::Digit0,
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+    digits
+        .iter()
+        .position(|&digit| digit == code)
+        .map(|state| state as u32)
+}
+
+/// Shows the seed of the last randomised soup so a run can be shared
+#[cfg(not(target_arch = "wasm32"))]
+fn show_seed(seed: u64) {
+    println!("Randomised with seed {seed}");
+}
+
+#[cfg(target_arch = "wasm32")]
+fn show_seed(seed: u64) {
+    use wasm_bindgen::UnwrapThrowExt;
+
+    log::info!("Randomised with seed {seed}");
+    let window = web_sys::window().unwrap_throw();
+    let document = window.document().unwrap_throw();
+    let seed_span = document.get_element_by_id("seedValue").unwrap_throw();
+    seed_span.set_text_content(Some(&seed.to_string()));
+}
+
+/// Writes a file to the working directory
+#[cfg(not(target_arch = "wasm32"))]
+fn save_file(file_name: &str, bytes: &[u8], _mime_type: &str) -> anyhow::Result<()> {
+    Ok(std::fs::write(file_name, bytes)?)
+}
+
+/// Offers a file to the user as a download
+#[cfg(target_arch = "wasm32")]
+fn save_file(file_name: &str, bytes: &[u8], mime_type: &str) -> anyhow::Result<()> {
+    crate::web_files::download_bytes(file_name, bytes, mime_type)
+        .map_err(|e| anyhow::anyhow!("unable to download {file_name}: {e:?}"))
+}
+
 impl ApplicationHandler<AppEvents> for App {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
         print!("resumed");
-        let window = graphics::get_window(event_loop);
+        let window = graphics::get_window(event_loop, &self.config);
         // Now getting the window in wgpu is an asynchronous task because we're asking the GPU to get
         // it for us, then we will use it
         // This differs on web and desktop so we need two variants of this.
@@ -336,6 +1484,83 @@ impl ApplicationHandler<AppEvents> for App {
             AppEvents::RandomiseState => self.randomise_state(),
             AppEvents::ResetState => self.reset_state(),
             AppEvents::UpdatePlayPauseUI => self.update_play_pause_ui(),
+            AppEvents::Census => self.census(),
+            AppEvents::CensusReady(state) => self.show_census(&state),
+            AppEvents::SaveSnapshot => self.save_snapshot(),
+            AppEvents::LoadSnapshot => self.load_snapshot(),
+            #[cfg(target_arch = "wasm32")]
+            AppEvents::DownloadSnapshot => self.download_snapshot(),
+            AppEvents::LoadSnapshotBytes(bytes) => match Snapshot::from_bytes(&bytes) {
+                Ok(snapshot) => self.restore_snapshot(snapshot),
+                Err(e) => log::error!("Unable to load snapshot: {e}"),
+            },
+            AppEvents::ExportPng => self.export_png(),
+            AppEvents::ToggleRecording => self.toggle_recording(),
+            AppEvents::RecordedFrame(pixels) => {
+                if let Some(recording) = &mut self.recording {
+                    match pixels {
+                        Ok(pixels) => recording.add_frame(&pixels),
+                        Err(e) => {
+                            log::error!("Unable to record frame: {e}");
+                            recording.drop_frame();
+                        }
+                    }
+                }
+                self.finish_recording_if_done();
+            }
+            AppEvents::ImportImage(bytes) => self.import_image(&bytes),
+            AppEvents::LoadPattern(text) if text.starts_with("[M2]") => {
+                match HashLife::parse_macrocell(&text) {
+                    Ok(hashlife) => self.load_macrocell(hashlife),
+                    Err(e) => log::error!("Unable to load pattern: {e}"),
+                }
+            }
+            AppEvents::LoadPattern(rle) => match Pattern::parse(&rle) {
+                Ok(pattern) => self.load_pattern(pattern),
+                Err(e) => log::error!("Unable to load pattern: {e}"),
+            },
+            AppEvents::SavePattern => self.save_pattern(),
+            AppEvents::LoadRule(text, name) => match RuleTable::load(&text, &name) {
+                Ok(table) => {
+                    log::info!("Switching to rule {}", table.name);
+                    self.config.set_rule(table.into());
+                    self.rebuild_game_and_render_data();
+                    if let Some(gc) = &mut self.graphics_context {
+                        gc.request_redraw();
+                    }
+                }
+                Err(e) => log::error!("Unable to load rule: {e}"),
+            },
+            AppEvents::SetPaintState(state) => self.config.set_paint_state(state),
+            AppEvents::StartHashLife(state) => self.start_hashlife(&state),
+            AppEvents::SetRule(rule) => self.switch_grid(Some(rule), None),
+            AppEvents::SetTopology(topology) => self.switch_grid(None, Some(topology)),
+            #[cfg(target_arch = "wasm32")]
+            AppEvents::GetCells(reply) => self.get_cells(reply),
+            #[cfg(target_arch = "wasm32")]
+            AppEvents::SetCells(bytes) => self.set_cells(&bytes),
+            #[cfg(target_arch = "wasm32")]
+            AppEvents::GetStatus(reply) => self.get_status(reply),
+            #[cfg(target_arch = "wasm32")]
+            AppEvents::ExportPattern(reply) => self.export_pattern(reply),
+            #[cfg(target_arch = "wasm32")]
+            AppEvents::OnGeneration(callback) => self.listeners.on_generation(callback),
+            #[cfg(target_arch = "wasm32")]
+            AppEvents::OnStable(callback) => self.listeners.on_stable(callback),
+            #[cfg(target_arch = "wasm32")]
+            AppEvents::ClearListeners => self.listeners.clear(),
+            #[cfg(target_arch = "wasm32")]
+            AppEvents::CheckStable(generation, hash) => match hash {
+                Ok(hash) => self.listeners.check(generation, hash),
+                Err(e) => {
+                    log::error!("Unable to read the grid back: {e}");
+                    self.listeners.check_failed();
+                }
+            },
+            #[cfg(target_arch = "wasm32")]
+            AppEvents::ShareLink => self.share_link(),
+            #[cfg(target_arch = "wasm32")]
+            AppEvents::ShareLinkReady(state) => self.share_link_ready(state),
             _ => todo!(),
         }
     }
@@ -369,11 +1594,9 @@ impl ApplicationHandler<AppEvents> for App {
             WindowEvent::RedrawRequested => {
                 match graphics_context.render(
                     self.render_data.as_ref().unwrap(),
-                    self.game_data
-                        .as_ref()
-                        .unwrap()
-                        .get_current_render_bind_group(),
+                    self.game_data.as_ref().unwrap(),
                     &self.config,
+                    self.hud.as_ref(),
                 ) {
                     Ok(_) => {}
                     Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
@@ -395,6 +1618,9 @@ impl ApplicationHandler<AppEvents> for App {
                     },
                 ..
             } => self.handle_key(event_loop, code, state.is_pressed()),
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
             WindowEvent::CursorEntered { device_id: _ } => {
                 mouse.in_grid = true;
             }
@@ -408,6 +1634,21 @@ impl ApplicationHandler<AppEvents> for App {
             } => {
                 mouse.is_pressed = false;
             }
+            // shift-click places a turmite instead of painting
+            WindowEvent::MouseInput {
+                device_id: _,
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+            } if self.modifiers.shift_key() && Turmites::is_active(&self.config) => {
+                if let (Some(game_data), Some((x, y))) =
+                    (&mut self.game_data, mouse.cell_under_cursor(&self.config))
+                {
+                    game_data
+                        .turmites
+                        .place(x as u32, y as u32, &graphics_context.queue);
+                    graphics_context.request_redraw();
+                }
+            }
             WindowEvent::MouseInput {
                 device_id: _,
                 state: ElementState::Pressed,
@@ -416,6 +1657,19 @@ impl ApplicationHandler<AppEvents> for App {
                 mouse.is_pressed = true;
                 mouse.add_to_buffer(&self.config);
             }
+            // right-drag selects the cells that are randomised
+            WindowEvent::MouseInput {
+                device_id: _,
+                state,
+                button: MouseButton::Right,
+            } => {
+                if state.is_pressed() {
+                    mouse.start_selection(&self.config);
+                } else {
+                    mouse.end_selection(&self.config);
+                }
+            }
+            #[allow(clippy::collapsible_match)]
             WindowEvent::CursorMoved {
                 device_id: _,
                 position: phys_pos,
@@ -428,9 +1682,13 @@ impl ApplicationHandler<AppEvents> for App {
                     if mouse.is_pressed {
                         mouse.add_to_buffer(&self.config);
                     }
+                    if mouse.is_selecting() {
+                        mouse.extend_selection(&self.config);
+                    }
                 }
             }
             // Touch events
+            #[allow(clippy::collapsible_match)]
             WindowEvent::Touch(Touch {
                 phase: TouchPhase::Started,
                 location,
@@ -479,6 +1737,8 @@ impl ApplicationHandler<AppEvents> for App {
         }
     }
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.reload_config(event_loop);
         let now = Instant::now();
         // paint logic here?
         if now >= self.next_paint_frame {
@@ -488,22 +1748,25 @@ impl ApplicationHandler<AppEvents> for App {
                 &mut self.mouse,
                 &mut self.game_data,
             ) {
+                if mouse.has_paint {
+                    game_data.wake(&gc.queue);
+                }
                 _ = gc.paint(
                     mouse,
                     &game_data.compute_uniform_bind_group,
                     game_data.get_current_compute_bind_group(),
                     &self.config,
                 );
+            }
+            self.update_hud();
+            if let Some(gc) = &mut self.graphics_context {
                 gc.request_redraw();
             }
             self.next_paint_frame = now + self.config.paint_frame_duration;
         }
         if now >= self.next_frame && !self.config.is_paused {
             // if we're ready for next frame and not paused then we update and send redraw command
-            if let (Some(gc), Some(game_data)) = (&mut self.graphics_context, &mut self.game_data) {
-                gc.update(game_data, &self.config);
-                gc.request_redraw();
-            }
+            self.advance();
             self.next_frame = now + self.config.frame_duration;
         }
 
@@ -512,7 +1775,58 @@ impl ApplicationHandler<AppEvents> for App {
     }
 }
 
+/// Lists what's wrong with the config in the console
+#[cfg(not(target_arch = "wasm32"))]
+fn show_config_error(e: &ConfigError) {
+    log::error!("{e}");
+}
+
+/// Lists what's wrong with the config above the controls, since the app
+/// can't start without one
+#[cfg(target_arch = "wasm32")]
+fn show_config_error(e: &ConfigError) {
+    log::error!("{e}");
+    let message = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id("configErrors"));
+    if let Some(message) = message {
+        message.set_text_content(Some(&e.to_string()));
+        _ = message.remove_attribute("hidden");
+    }
+}
+
+/// Shows how sharing a link went under the share button
+#[cfg(target_arch = "wasm32")]
+fn show_share_message(message: &str) {
+    log::info!("{message}");
+    let element = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id("shareMessage"));
+    if let Some(element) = element {
+        element.set_text_content(Some(message));
+        _ = element.remove_attribute("hidden");
+    }
+}
+
+/// Moves the speed slider to `fps`
+#[cfg(target_arch = "wasm32")]
+fn show_fps(fps: usize) {
+    let document = web_sys::window().and_then(|window| window.document());
+    if let Some(document) = document {
+        if let Some(slider) = document.get_element_by_id("fpsSlider") {
+            _ = slider.set_attribute("value", &fps.to_string());
+        }
+        if let Some(value) = document.get_element_by_id("fpsValue") {
+            value.set_text_content(Some(&fps.to_string()));
+        }
+    }
+}
+
 pub fn run() -> anyhow::Result<()> {
+    run_with(Startup::default())
+}
+
+pub fn run_with(startup: Startup) -> anyhow::Result<()> {
     #[cfg(not(target_arch = "wasm32"))]
     {
         env_logger::init();
@@ -525,9 +1839,20 @@ pub fn run() -> anyhow::Result<()> {
     // re-emphasising that the 'event' is our state. we're calling a change to our state the event in the loop
     let event_loop = EventLoop::<AppEvents>::with_user_event().build()?;
 
+    let mut startup = startup;
+    let config = match startup.config.take() {
+        Some(config) => config,
+        None => load_config().inspect_err(show_config_error)?,
+    };
+
     #[cfg(not(target_arch = "wasm32"))]
     {
-        let mut app = App::new()?;
+        let mut app = App::new(config)?;
+        let source = startup.config_source.take().unwrap_or_default();
+        app.config_watcher = ConfigWatcher::new(source)
+            .inspect_err(|e| log::warn!("Not watching the config for changes: {e:#}"))
+            .ok();
+        app.startup = startup;
         event_loop.run_app(&mut app)?;
     }
 
@@ -535,7 +1860,8 @@ pub fn run() -> anyhow::Result<()> {
     {
         let proxy = event_loop.create_proxy();
         EVENT_LOOP_PROXY.with(|p| *p.lock().unwrap() = Some(proxy));
-        let app = App::new(&event_loop)?;
+        let mut app = App::new(config, &event_loop)?;
+        app.startup = startup;
         // On web, run_app doesn't return normally, so we handle it differently
         use winit::platform::web::EventLoopExtWebSys;
         event_loop.spawn_app(app);
@@ -549,7 +1875,18 @@ pub fn run() -> anyhow::Result<()> {
 pub fn run_web() -> Result<(), wasm_bindgen::JsValue> {
     console_error_panic_hook::set_once();
 
-    match run() {
+    // a shared link carries the grid in its hash
+    let mut startup = Startup::default();
+    match web_files::location_hash() {
+        Ok(hash) if hash.is_empty() => {}
+        Ok(hash) => match SharedState::from_hash(&hash) {
+            Ok(shared) => startup.shared = Some(shared),
+            Err(e) => show_share_message(&format!("Unable to open the link: {e:#}")),
+        },
+        Err(e) => show_share_message(&format!("Unable to read the link: {e:?}")),
+    }
+
+    match run_with(startup) {
         Ok(_) => {
             log::info!("Game of Life initialized successfully");
             Ok(())
@@ -3,31 +3,290 @@ use std::time::Duration;
 #[cfg(target_arch = "wasm32")]
 use web_time::Duration;
 
-use config::Config;
+use crate::{
+    continuous,
+    keybindings::{Action, KeyBindings},
+    rule::Rule,
+    turmite::TurmiteRule,
+};
+use anyhow::{Context, ensure};
+use config::{Config, FileFormat};
 use serde::Deserialize;
-use std::sync::Arc;
+use std::{collections::BTreeMap, fmt, path::PathBuf, sync::Arc};
 use winit::window::Window;
 
 const COMPUTE_WORKGROUP_SIZE: [usize; 2] = [16, 16];
+/// rows or cols when the config leaves them out and there's no window to fit
+const DEFAULT_GRID_LEN: usize = 100;
+
+/// An RGBA colour from the config, like `[255, 60, 60, 255]`. The channels
+/// are read as any whole number so the ones that aren't from 0 to 255 can be
+/// listed with every other invalid setting, see `SettingChecker::colour`.
+pub type Rgba = [i64; 4];
+/// An RGB colour from the config, checked the same way as `Rgba`
+pub type Rgb = [i64; 3];
+
+/// What happens at the edges of the grid
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Topology {
+    /// cells outside the grid are always dead
+    #[default]
+    Bounded,
+    /// the edges wrap around to the opposite side
+    Torus,
+    /// patterns carry on past the edges forever, see tiles.rs
+    Unbounded,
+}
+
+/// Symmetry imposed on randomised soups, named after the symmetry groups
+/// used by apgsearch
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    /// no symmetry
+    #[default]
+    C1,
+    /// 180 degree rotation
+    C2,
+    /// 90 degree rotation
+    C4,
+    /// 90 degree rotation and reflection
+    D8,
+}
+
+/// What the cells hold
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SimulationMode {
+    /// whole number states stepped by `rule`
+    #[default]
+    Discrete,
+    /// values between 0 and 1 stepped by the `[continuous]` table, like Lenia
+    Continuous,
+}
+
+/// Shape of each ring of the continuous kernel, from its inner to outer edge
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KernelCore {
+    /// smooth bump, Lenia's default
+    #[default]
+    Exponential,
+    /// (4r(1 - r))^4
+    Polynomial,
+    /// flat over the middle half of the ring, like SmoothLife
+    Rectangle,
+}
+
+/// How the weighted neighbourhood turns into growth between -1 and 1
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GrowthFunction {
+    /// a bell curve around `mu`
+    #[default]
+    Gaussian,
+    /// a bell curve that reaches -1 at `mu` +/- 3 `sigma`
+    Polynomial,
+    /// 1 within `sigma` of `mu`, -1 everywhere else
+    Step,
+}
+
+/// The `[continuous]` table: the kernel and growth function of continuous mode
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContinuousConfig {
+    /// how far the kernel reaches, in cells
+    pub radius: u32,
+    /// height of each of the kernel's rings, from the middle out
+    pub peaks: Vec<f32>,
+    pub kernel: KernelCore,
+    pub growth: GrowthFunction,
+    /// the weighted neighbourhood that grows fastest
+    pub mu: f32,
+    /// how far from `mu` growth turns into decay
+    pub sigma: f32,
+    /// how much of the growth is added each step
+    pub dt: f32,
+    /// RGBA colours spread evenly from value 0 to value 1, see `Rgba`
+    pub colour_ramp: Vec<Rgba>,
+}
+
+impl Default for ContinuousConfig {
+    fn default() -> Self {
+        // Lenia's Orbium
+        Self {
+            radius: 13,
+            peaks: vec![1.0],
+            kernel: KernelCore::Exponential,
+            growth: GrowthFunction::Gaussian,
+            mu: 0.15,
+            sigma: 0.015,
+            dt: 0.1,
+            colour_ramp: vec![
+                [0, 0, 0, 0],
+                [30, 20, 110, 255],
+                [30, 150, 200, 255],
+                [250, 220, 80, 255],
+                [255, 255, 255, 255],
+            ],
+        }
+    }
+}
+
+/// How turmites share the grid with the rule
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TurmiteMode {
+    /// the rule steps the grid, then the turmites move over the result
+    #[default]
+    Alongside,
+    /// only the turmites change the grid
+    Replace,
+}
+
+/// The `[turmites]` table: agents like Langton's ant that walk over the grid
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TurmiteConfig {
+    /// turns on each colour like `"RL"`, or a full turmite, see turmite.rs
+    pub rule: String,
+    pub mode: TurmiteMode,
+    /// where turmites start, in cells from the middle of the grid, facing up
+    pub start: Vec<[i32; 2]>,
+    pub colour: Rgba,
+}
+
+impl Default for TurmiteConfig {
+    fn default() -> Self {
+        // Langton's ant, waiting to be placed
+        Self {
+            rule: "RL".to_string(),
+            mode: TurmiteMode::Alongside,
+            start: Vec::new(),
+            colour: [255, 60, 60, 255],
+        }
+    }
+}
+
+/// File format of animated recordings
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingFormat {
+    #[default]
+    Gif,
+    Apng,
+}
+
+/// The `[recording]` table: how runs are recorded into animations
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecordingConfig {
+    /// record every `stride`th generation
+    pub stride: u64,
+    /// pixels per cell
+    pub scale: u32,
+    /// playback speed of the animation
+    pub fps: u32,
+    /// colours frames are reduced to, defaults to the background, every state's
+    /// colour and the turmites, or steps along the ramp in continuous mode
+    pub palette: Option<Vec<Rgb>>,
+    pub format: RecordingFormat,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            stride: 1,
+            scale: 4,
+            fps: 20,
+            palette: None,
+            format: RecordingFormat::Gif,
+        }
+    }
+}
+
+/// How imported images are turned into live and dead cells
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageImportMode {
+    /// pixels darker than the threshold are alive
+    #[default]
+    Threshold,
+    /// Floyd-Steinberg dithering, so grey areas become a mix of live and dead cells
+    Dither,
+}
+
+/// The `[image_import]` table: how images are loaded onto the grid
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageImportConfig {
+    pub mode: ImageImportMode,
+    /// brightness between 0 and 1 below which a pixel becomes a live cell
+    pub threshold: f32,
+    /// make light pixels alive instead of dark ones
+    pub invert: bool,
+}
+
+impl Default for ImageImportConfig {
+    fn default() -> Self {
+        Self {
+            mode: ImageImportMode::Threshold,
+            threshold: 0.5,
+            invert: false,
+        }
+    }
+}
 
 #[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct RawConfig {
-    pub rows: usize,
-    pub cols: usize,
+    /// the grid fits the window, `min_cell_array_len` cells across its
+    /// shorter side, unless these are given
+    pub rows: Option<usize>,
+    pub cols: Option<usize>,
     pub min_cell_array_len: usize,
     pub gap_ratio: f32,
     pub fps: usize,
     pub paint_fps: usize,
     pub init_rand_threshold: f64,
+    /// (width, height) in pixels of the desktop window, 800x600 if not given
     pub window_size: Option<(usize, usize)>,
-    pub background_color: [u8; 4],
-    pub cursor_color: [u8; 4],
+    pub background_color: Rgba,
+    pub cursor_color: Rgba,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub mode: SimulationMode,
+    /// B/S rule string, Conway's Life if not given
+    pub rule: Option<String>,
+    #[serde(default)]
+    pub continuous: ContinuousConfig,
+    #[serde(default)]
+    pub turmites: TurmiteConfig,
+    pub seed: Option<u64>,
+    /// (rows, cols) of a centred region to randomise instead of the whole grid
+    pub fill_region: Option<(usize, usize)>,
+    #[serde(default)]
+    pub soup_symmetry: Symmetry,
+    /// (width, height) in pixels of exported images
+    pub export_size: Option<(u32, u32)>,
+    #[serde(default)]
+    pub recording: RecordingConfig,
+    #[serde(default)]
+    pub image_import: ImageImportConfig,
+    /// only step the parts of the grid that are changing, on unless false
+    pub active_regions: Option<bool>,
+    /// key for each action, see keybindings.rs
+    #[serde(default)]
+    pub keybindings: BTreeMap<Action, String>,
 }
 
 #[derive(Debug)]
 pub struct AppConfig {
     pub rows: usize,
     pub cols: usize,
+    /// rows and cols were given rather than fitted to the window
+    pub fixed_grid: bool,
     pub min_cell_array_len: usize,
     pub cell_size: (f32, f32),
     pub gap_ratio: f32,
@@ -40,20 +299,234 @@ pub struct AppConfig {
     pub window_size: Option<(usize, usize)>,
     pub compute_dispatches: [usize; 2],
     pub is_paused: bool,
+    /// state the mouse paints cells in
+    pub paint_state: u32,
     pub background_color: wgpu::Color,
     pub cursor_color: [u8; 4],
+    pub topology: Topology,
+    pub mode: SimulationMode,
+    pub rule: Rule,
+    pub continuous: ContinuousConfig,
+    pub turmites: TurmiteConfig,
+    pub turmite_rule: TurmiteRule,
+    pub seed: Option<u64>,
+    pub fill_region: Option<(usize, usize)>,
+    pub soup_symmetry: Symmetry,
+    pub export_size: Option<(u32, u32)>,
+    pub recording: RecordingConfig,
+    pub image_import: ImageImportConfig,
+    /// see active.rs
+    pub active_regions: bool,
+    pub keybindings: KeyBindings,
+}
+
+/// Why a config couldn't be used
+#[derive(Debug)]
+pub enum ConfigError {
+    /// the file is missing, isn't valid TOML or has a setting of the wrong
+    /// type, like a string where a number should be
+    Read(config::ConfigError),
+    /// every setting with a value that can't be used
+    Invalid(Vec<InvalidSetting>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(e) => write!(f, "unable to read the config: {e}"),
+            ConfigError::Invalid(settings) => {
+                write!(f, "invalid config:")?;
+                for setting in settings {
+                    write!(f, "\n  {setting}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<config::ConfigError> for ConfigError {
+    fn from(e: config::ConfigError) -> Self {
+        ConfigError::Read(e)
+    }
+}
+
+/// A setting that can't be used, and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidSetting {
+    /// where it is in the config, like `recording.fps`
+    pub path: &'static str,
+    pub problem: SettingProblem,
+}
+
+impl fmt::Display for InvalidSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.problem)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingProblem {
+    /// a count or size that has to be at least 1
+    Zero,
+    /// a number outside the range it has to be in
+    OutOfRange { value: f64, min: f64, max: f64 },
+    /// a number that has to be more than 0
+    NotPositive(f64),
+    /// a list that needs at least one entry
+    Empty,
+    /// a string that doesn't parse, like a rule
+    Unparsable(String),
+    /// a key that's bound to another action
+    KeyTaken { key: String, by: &'static str },
+}
+
+impl fmt::Display for SettingProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingProblem::Zero => write!(f, "must be at least 1"),
+            SettingProblem::OutOfRange { value, min, max } => {
+                write!(f, "{value} isn't between {min} and {max}")
+            }
+            SettingProblem::NotPositive(value) => write!(f, "{value} must be more than 0"),
+            SettingProblem::Empty => write!(f, "needs at least one entry"),
+            SettingProblem::Unparsable(e) => write!(f, "{e}"),
+            SettingProblem::KeyTaken { key, by } => write!(f, "{key} is already bound to {by}"),
+        }
+    }
+}
+
+/// Collects every invalid setting rather than stopping at the first
+#[derive(Default)]
+pub struct SettingChecker {
+    pub invalid: Vec<InvalidSetting>,
+}
+
+impl SettingChecker {
+    pub fn fail(&mut self, path: &'static str, problem: SettingProblem) {
+        self.invalid.push(InvalidSetting { path, problem });
+    }
+
+    pub fn at_least_one(&mut self, path: &'static str, value: u64) {
+        if value == 0 {
+            self.fail(path, SettingProblem::Zero);
+        }
+    }
+
+    pub fn in_range(&mut self, path: &'static str, value: f64, min: f64, max: f64) {
+        // NaN isn't in any range
+        if !(min..=max).contains(&value) {
+            self.fail(path, SettingProblem::OutOfRange { value, min, max });
+        }
+    }
+
+    pub fn positive(&mut self, path: &'static str, value: f64) {
+        // NaN isn't positive either
+        if value.is_nan() || value <= 0.0 {
+            self.fail(path, SettingProblem::NotPositive(value));
+        }
+    }
+
+    /// Every channel of a colour is from 0 to 255
+    pub fn colour(&mut self, path: &'static str, channels: &[i64]) {
+        for &channel in channels {
+            self.in_range(path, channel as f64, 0.0, u8::MAX as f64);
+        }
+    }
+
+    pub fn not_empty<T>(&mut self, path: &'static str, list: &[T]) {
+        if list.is_empty() {
+            self.fail(path, SettingProblem::Empty);
+        }
+    }
+
+    /// `value`, if it parsed
+    pub fn parsed<T, E: fmt::Display>(
+        &mut self,
+        path: &'static str,
+        value: Result<T, E>,
+    ) -> Option<T> {
+        value
+            .map_err(|e| self.fail(path, SettingProblem::Unparsable(e.to_string())))
+            .ok()
+    }
+}
+
+impl RawConfig {
+    /// Checks the settings that serde can't, returning the rule, turmite
+    /// rule and key bindings they parse to
+    fn check(&self) -> Result<(Rule, TurmiteRule, KeyBindings), ConfigError> {
+        let mut checker = SettingChecker::default();
+        if let Some(rows) = self.rows {
+            checker.at_least_one("rows", rows as u64);
+        }
+        if let Some(cols) = self.cols {
+            checker.at_least_one("cols", cols as u64);
+        }
+        checker.at_least_one("min_cell_array_len", self.min_cell_array_len as u64);
+        checker.in_range("gap_ratio", self.gap_ratio as f64, 0.0, 1.0);
+        checker.at_least_one("fps", self.fps as u64);
+        checker.at_least_one("paint_fps", self.paint_fps as u64);
+        checker.in_range("init_rand_threshold", self.init_rand_threshold, 0.0, 1.0);
+        checker.colour("background_color", &self.background_color);
+        checker.colour("cursor_color", &self.cursor_color);
+        if let Some((width, height)) = self.window_size {
+            checker.at_least_one("window_size", width.min(height) as u64);
+        }
+        if let Some((width, height)) = self.export_size {
+            checker.at_least_one("export_size", width.min(height) as u64);
+        }
+        let rule = match &self.rule {
+            Some(rule) => checker.parsed("rule", rule.parse()),
+            None => Some(Rule::default()),
+        };
+        self.continuous.check(&mut checker);
+        let turmite_rule = checker.parsed("turmites.rule", self.turmites.rule.parse());
+        checker.colour("turmites.colour", &self.turmites.colour);
+        checker.at_least_one("recording.stride", self.recording.stride);
+        checker.at_least_one("recording.scale", self.recording.scale as u64);
+        checker.at_least_one("recording.fps", self.recording.fps as u64);
+        if let Some(palette) = &self.recording.palette {
+            checker.not_empty("recording.palette", palette);
+            for colour in palette {
+                checker.colour("recording.palette", colour);
+            }
+        }
+        checker.in_range(
+            "image_import.threshold",
+            self.image_import.threshold as f64,
+            0.0,
+            1.0,
+        );
+        let keybindings = KeyBindings::from_table(&self.keybindings, &mut checker);
+
+        match (rule, turmite_rule) {
+            (Some(rule), Some(turmite_rule)) if checker.invalid.is_empty() => {
+                Ok((rule, turmite_rule, keybindings))
+            }
+            _ => Err(ConfigError::Invalid(checker.invalid)),
+        }
+    }
 }
 
-impl From<RawConfig> for AppConfig {
-    fn from(value: RawConfig) -> Self {
+impl TryFrom<RawConfig> for AppConfig {
+    type Error = ConfigError;
+
+    fn try_from(value: RawConfig) -> Result<Self, ConfigError> {
+        let (rule, turmite_rule, keybindings) = value.check()?;
+        let fixed_grid = value.rows.is_some() || value.cols.is_some();
+        let rows = value.rows.unwrap_or(DEFAULT_GRID_LEN);
+        let cols = value.cols.unwrap_or(DEFAULT_GRID_LEN);
         // calculate the cell_size
-        let num_to_fit = value.rows.max(value.cols) as f32;
+        let num_to_fit = grid_width(cols, &rule).max(rows as f32);
         let cell_size = 2.0 / (num_to_fit + (num_to_fit + 1.0) * value.gap_ratio);
         let frame_duration = Duration::from_nanos(1_000_000_000 / value.fps as u64);
         let paint_frame_duration = Duration::from_nanos(1_000_000_000 / value.paint_fps as u64);
         let compute_dispatches = [
-            (value.cols / COMPUTE_WORKGROUP_SIZE[0]) + 1,
-            (value.rows / COMPUTE_WORKGROUP_SIZE[1]) + 1,
+            (cols / COMPUTE_WORKGROUP_SIZE[0]) + 1,
+            (rows / COMPUTE_WORKGROUP_SIZE[1]) + 1,
         ];
         let background_color = wgpu::Color {
             r: value.background_color[0] as f64 / 255.0,
@@ -63,9 +536,10 @@ impl From<RawConfig> for AppConfig {
         };
         let gap_size = (cell_size * value.gap_ratio, cell_size * value.gap_ratio);
         dbg!(&compute_dispatches);
-        Self {
-            rows: value.rows,
-            cols: value.cols,
+        Ok(Self {
+            rows,
+            cols,
+            fixed_grid,
             cell_size: (cell_size, cell_size),
             fps: value.fps,
             paint_fps: value.paint_fps,
@@ -77,53 +551,284 @@ impl From<RawConfig> for AppConfig {
             compute_dispatches,
             window_size: value.window_size,
             is_paused: true,
+            paint_state: 1,
             background_color,
-            cursor_color: value.cursor_color,
+            cursor_color: value.cursor_color.map(|c| c as u8),
             min_cell_array_len: value.min_cell_array_len,
-        }
+            topology: value.topology,
+            mode: value.mode,
+            rule,
+            continuous: value.continuous,
+            turmites: value.turmites,
+            turmite_rule,
+            seed: value.seed,
+            fill_region: value.fill_region,
+            soup_symmetry: value.soup_symmetry,
+            export_size: value.export_size,
+            recording: value.recording,
+            image_import: value.image_import,
+            active_regions: value.active_regions.unwrap_or(true),
+            keybindings,
+        })
     }
 }
 impl AppConfig {
     pub fn num_elements(&self) -> usize {
         self.rows * self.cols
     }
+
+    /// Width of the grid in cells
+    pub fn grid_width(&self) -> f32 {
+        grid_width(self.cols, &self.rule)
+    }
+
+    /// Switches rule, reshaping the cells if it's on a different lattice
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+        if self.paint_state >= self.rule.states {
+            self.paint_state = 1;
+        }
+        self.resize_grid(self.rows, self.cols);
+    }
+
+    /// Paints cells in `state` from now on, if the rule has it
+    pub fn set_paint_state(&mut self, state: u32) {
+        if state < self.rule.states {
+            self.paint_state = state;
+            log::info!("Painting state {state}");
+        } else {
+            log::warn!("Rule {} has no state {state}", self.rule);
+        }
+    }
+
+    /// Linear RGBA colours cells are drawn with: one for each state of the
+    /// rule, or the stops of the continuous colour ramp
+    pub fn cell_colours(&self) -> Vec<[f32; 4]> {
+        match self.mode {
+            SimulationMode::Discrete => {
+                // turmites can write colours the rule doesn't have
+                let states = self.rule.states.max(self.turmite_rule.colours);
+                Rule {
+                    states,
+                    ..self.rule.clone()
+                }
+                .colours()
+            }
+            SimulationMode::Continuous => continuous::colour_ramp(&self.continuous),
+        }
+    }
+
+    /// Takes whatever changed between two reads of the config file that can
+    /// change while the app runs, leaving the grid alone
+    pub fn apply_reload(&mut self, old: &AppConfig, new: &AppConfig) -> ConfigReload {
+        let mut reload = ConfigReload::default();
+        if new.fps != old.fps {
+            self.fps = new.fps;
+            self.frame_duration = new.frame_duration;
+            reload.applied.push("fps");
+        }
+        if new.paint_fps != old.paint_fps {
+            self.paint_fps = new.paint_fps;
+            self.paint_frame_duration = new.paint_frame_duration;
+            reload.applied.push("paint_fps");
+        }
+        if new.background_color != old.background_color {
+            self.background_color = new.background_color;
+            reload.applied.push("background_color");
+        }
+        if new.cursor_color != old.cursor_color {
+            self.cursor_color = new.cursor_color;
+            reload.applied.push("cursor_color");
+            reload.new_cursor = true;
+        }
+        if new.gap_ratio != old.gap_ratio {
+            self.gap_ratio = new.gap_ratio;
+            self.resize_grid(self.rows, self.cols);
+            reload.applied.push("gap_ratio");
+            reload.redraw_cells = true;
+        }
+        if new.continuous.colour_ramp != old.continuous.colour_ramp {
+            self.continuous.colour_ramp = new.continuous.colour_ramp.clone();
+            reload.applied.push("continuous.colour_ramp");
+            reload.redraw_cells = true;
+        }
+        if new.turmites.colour != old.turmites.colour {
+            self.turmites.colour = new.turmites.colour;
+            reload.applied.push("turmites.colour");
+            reload.redraw_cells = true;
+        }
+        if new.keybindings != old.keybindings {
+            self.keybindings = new.keybindings.clone();
+            reload.applied.push("keybindings");
+        }
+        if new.init_rand_threshold != old.init_rand_threshold {
+            self.init_rand_threshold = new.init_rand_threshold;
+            reload.applied.push("init_rand_threshold");
+        }
+        if new.fill_region != old.fill_region {
+            self.fill_region = new.fill_region;
+            reload.applied.push("fill_region");
+        }
+        if new.soup_symmetry != old.soup_symmetry {
+            self.soup_symmetry = new.soup_symmetry;
+            reload.applied.push("soup_symmetry");
+        }
+        if new.export_size != old.export_size {
+            self.export_size = new.export_size;
+            reload.applied.push("export_size");
+        }
+        if new.recording != old.recording {
+            self.recording = new.recording.clone();
+            reload.applied.push("recording");
+        }
+        if new.image_import != old.image_import {
+            self.image_import = new.image_import.clone();
+            reload.applied.push("image_import");
+        }
+
+        // everything the grid or its pipelines were built from
+        let continuous_kernel = |config: &AppConfig| ContinuousConfig {
+            colour_ramp: Vec::new(),
+            ..config.continuous.clone()
+        };
+        let turmites = |config: &AppConfig| TurmiteConfig {
+            colour: [0; 4],
+            ..config.turmites.clone()
+        };
+        reload.need_restart = [
+            (
+                "rows",
+                new.rows != old.rows || new.fixed_grid != old.fixed_grid,
+            ),
+            ("cols", new.cols != old.cols),
+            (
+                "min_cell_array_len",
+                new.min_cell_array_len != old.min_cell_array_len,
+            ),
+            ("window_size", new.window_size != old.window_size),
+            ("topology", new.topology != old.topology),
+            ("mode", new.mode != old.mode),
+            ("rule", new.rule != old.rule),
+            (
+                "continuous",
+                continuous_kernel(new) != continuous_kernel(old),
+            ),
+            ("turmites", turmites(new) != turmites(old)),
+            ("seed", new.seed != old.seed),
+            ("active_regions", new.active_regions != old.active_regions),
+        ]
+        .into_iter()
+        .filter_map(|(path, changed)| changed.then_some(path))
+        .collect();
+        reload
+    }
+}
+
+/// What `AppConfig::apply_reload` did with a changed config file
+#[derive(Debug, Default)]
+pub struct ConfigReload {
+    /// settings in use from now on
+    pub applied: Vec<&'static str>,
+    /// settings that only take effect once the app is started again
+    pub need_restart: Vec<&'static str>,
+    /// the cells are laid out or coloured differently
+    pub redraw_cells: bool,
+    pub new_cursor: bool,
 }
 
+/// Hexagonal grids are half a cell wider for their shifted rows
+fn grid_width(cols: usize, rule: &Rule) -> f32 {
+    cols as f32 + if rule.is_hexagonal() { 0.5 } else { 0.0 }
+}
+
+/// appconfig.toml as it was built, which the config file is read over
+const DEFAULT_CONFIG: &str = include_str!("../appconfig.toml");
+
+/// Where the desktop app reads its config from
+#[derive(Debug, Default, Clone)]
+pub struct ConfigSource {
+    /// `appconfig` in the working directory if not given, or just the
+    /// built in defaults if there isn't one
+    pub path: Option<PathBuf>,
+    /// TOML lines like `rows = 200` applied over the file, see
+    /// `override_line`
+    pub overrides: Vec<String>,
+}
+
+/// Reads `appconfig` from the working directory over the built in defaults
 #[cfg(not(target_arch = "wasm32"))]
-pub fn load_config() -> AppConfig {
-    let raw_config: RawConfig = Config::builder()
-        .add_source(config::File::with_name("appconfig"))
-        .build()
-        .unwrap()
-        .try_deserialize()
-        .unwrap();
-    println!("Raw Config:\n{:?}", &raw_config);
-    let app_config = raw_config.into();
-    println!("App Config:\n{:?}", &app_config);
-    app_config
+pub fn load_config() -> Result<AppConfig, ConfigError> {
+    load_config_from(&ConfigSource::default())
+}
+
+/// Reads the config file over the built in defaults, then the overrides
+/// over that
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_config_from(source: &ConfigSource) -> Result<AppConfig, ConfigError> {
+    let file = match &source.path {
+        Some(path) => config::File::from(path.as_path()).required(true),
+        None => config::File::with_name("appconfig").required(false),
+    };
+    let mut builder = Config::builder()
+        .add_source(config::File::from_str(DEFAULT_CONFIG, FileFormat::Toml))
+        .add_source(file);
+    for line in &source.overrides {
+        builder = builder.add_source(config::File::from_str(line, FileFormat::Toml));
+    }
+    into_app_config(builder.build()?.try_deserialize()?)
 }
 
 /// build at compile time using include_str!
 #[cfg(target_arch = "wasm32")]
-pub fn load_config() -> AppConfig {
-    use config::FileFormat;
-    let config_str: &str = include_str!("../appconfig.toml");
+pub fn load_config() -> Result<AppConfig, ConfigError> {
+    let raw_config = Config::builder()
+        .add_source(config::File::from_str(DEFAULT_CONFIG, FileFormat::Toml))
+        .build()?
+        .try_deserialize()?;
+    into_app_config(raw_config)
+}
 
-    let raw_config: RawConfig = Config::builder()
-        .add_source(config::File::from_str(config_str, FileFormat::Toml))
-        .build()
-        .unwrap()
-        .try_deserialize()
-        .unwrap();
+fn into_app_config(raw_config: RawConfig) -> Result<AppConfig, ConfigError> {
     println!("Raw Config:\n{:?}", &raw_config);
-    let app_config = raw_config.into();
+    let app_config = AppConfig::try_from(raw_config)?;
     println!("App Config:\n{:?}", &app_config);
-    app_config
+    Ok(app_config)
+}
+
+/// Turns `key=value` from the command line into a TOML line for
+/// `ConfigSource::overrides`. Values that aren't valid TOML on their own,
+/// like `rule=B36/S23`, are taken as strings.
+pub fn override_line(setting: &str) -> anyhow::Result<String> {
+    let (key, value) = setting
+        .split_once('=')
+        .with_context(|| format!("expected KEY=VALUE, not {setting:?}"))?;
+    let (key, value) = (key.trim(), value.trim());
+    ensure!(
+        !key.is_empty()
+            && key.split('.').all(|part| !part.is_empty()
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')),
+        "{key:?} isn't a config setting, they look like `rows` or `recording.fps`"
+    );
+    let line = format!("{key} = {value}");
+    let is_toml = Config::builder()
+        .add_source(config::File::from_str(&line, FileFormat::Toml))
+        .build()
+        .is_ok();
+    if is_toml {
+        Ok(line)
+    } else {
+        Ok(format!("{key} = {value:?}"))
+    }
 }
 
 /// get rows and cols from window in webapp
 impl AppConfig {
     pub fn update_cell_configuration(&mut self, window: &Arc<Window>) {
+        if self.fixed_grid {
+            self.resize_grid(self.rows, self.cols);
+            log::info!("Set Rows {}, Cols: {}", self.rows, self.cols);
+            return;
+        }
         let window_size = window.inner_size().to_logical::<f64>(window.scale_factor());
 
         // find the smaller size
@@ -138,9 +843,24 @@ impl AppConfig {
             }
             (_, _) => {}
         };
+        self.resize_grid(self.rows, self.cols);
+        log::info!(
+            "Window size at row col update time: {}x{}",
+            window_size.width,
+            window_size.height
+        );
+        log::info!("Set Rows {}, Cols: {}", self.rows, self.cols);
+        log::info!("Set cell size: {}x{}", self.cell_size.0, self.cell_size.1);
+    }
+
+    /// set the grid dimensions and everything derived from them
+    pub fn resize_grid(&mut self, rows: usize, cols: usize) {
+        self.rows = rows;
+        self.cols = cols;
         // appropriately set the cell_size_x and y accordingly
+        let width = self.grid_width();
         self.cell_size = (
-            2.0 / (self.cols as f32 + (self.cols as f32 + 1.0) * self.gap_ratio),
+            2.0 / (width + (width + 1.0) * self.gap_ratio),
             2.0 / (self.rows as f32 + (self.rows as f32 + 1.0) * self.gap_ratio),
         );
         self.gap_size = (
@@ -151,12 +871,94 @@ impl AppConfig {
             (self.cols / COMPUTE_WORKGROUP_SIZE[0]) + 1,
             (self.rows / COMPUTE_WORKGROUP_SIZE[1]) + 1,
         ];
-        log::info!(
-            "Window size at row col update time: {}x{}",
-            window_size.width,
-            window_size.height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(settings: &[&str]) -> Result<AppConfig, ConfigError> {
+        load_config_from(&ConfigSource {
+            path: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/appconfig.toml").into()),
+            overrides: settings
+                .iter()
+                .map(|setting| override_line(setting).unwrap())
+                .collect(),
+        })
+    }
+
+    fn invalid_paths(settings: &[&str]) -> Vec<&'static str> {
+        match load(settings) {
+            Err(ConfigError::Invalid(invalid)) => invalid.iter().map(|s| s.path).collect(),
+            Err(e) => panic!("expected invalid settings, not {e}"),
+            Ok(_) => panic!("{settings:?} should be invalid"),
+        }
+    }
+
+    #[test]
+    fn the_default_config_is_valid() {
+        let config = load(&[]).unwrap();
+        assert_eq!(config.rule, Rule::default());
+        assert!(config.is_paused);
+    }
+
+    #[test]
+    fn colours_are_checked_with_the_other_settings() {
+        let paths = invalid_paths(&[
+            "background_color=[0, 0, 300, 255]",
+            "cursor_color=[-1, 0, 0, 255]",
+            "turmites.colour=[256, 0, 0, 255]",
+            "continuous.colour_ramp=[[0, 0, 0, 0], [0, 0, 0, 1000]]",
+            "recording.palette=[[0, 0, 0], [1, 2, 3000]]",
+            "fps=0",
+            "rule=B9/S23",
+        ]);
+        assert_eq!(
+            paths,
+            [
+                "fps",
+                "background_color",
+                "cursor_color",
+                "rule",
+                "continuous.colour_ramp",
+                "turmites.colour",
+                "recording.palette",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_bad_channel_is_listed() {
+        let paths = invalid_paths(&["background_color=[-5, 300, 0, 999]"]);
+        assert_eq!(paths, ["background_color"; 3]);
+        let invalid = match load(&["cursor_color=[0, 0, 0, 256]"]) {
+            Err(ConfigError::Invalid(invalid)) => invalid,
+            _ => panic!("cursor_color should be invalid"),
+        };
+        assert_eq!(
+            invalid[0].problem,
+            SettingProblem::OutOfRange {
+                value: 256.0,
+                min: 0.0,
+                max: 255.0
+            }
+        );
+    }
+
+    #[test]
+    fn colours_in_range_are_kept() {
+        let config = load(&[
+            "cursor_color=[0, 128, 255, 255]",
+            "turmites.colour=[1, 2, 3, 4]",
+            "recording.palette=[[0, 0, 0], [255, 255, 255]]",
+        ])
+        .unwrap();
+        assert_eq!(config.cursor_color, [0, 128, 255, 255]);
+        assert_eq!(config.turmites.colour, [1, 2, 3, 4]);
+        assert_eq!(
+            config.recording.palette,
+            Some(vec![[0, 0, 0], [255, 255, 255]])
         );
-        log::info!("Set Rows {}, Cols: {}", self.rows, self.cols);
-        log::info!("Set cell size: {}x{}", self.cell_size.0, self.cell_size.1);
     }
 }
@@ -0,0 +1,92 @@
+// Hot reloading the config file on desktop.
+//
+// The directory the config file is in is watched rather than the file, since
+// most editors save by writing a new file and renaming it over the old one.
+// Editors also tend to write in a few goes, so the file is only read again
+// once it has stopped changing for a moment.
+
+use std::{
+    ffi::OsStr,
+    path::Path,
+    sync::mpsc::{Receiver, channel},
+    time::{Duration, Instant},
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::config::{AppConfig, ConfigReload, ConfigSource, load_config_from};
+
+/// how long the file has to be left alone before it's read
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+pub struct ConfigWatcher {
+    source: ConfigSource,
+    /// the config as the file last had it, which changes are found against
+    last: AppConfig,
+    /// stops watching when dropped
+    _watcher: RecommendedWatcher,
+    changes: Receiver<notify::Result<notify::Event>>,
+    /// when the file last changed, if it hasn't been read since
+    changed_at: Option<Instant>,
+}
+
+impl ConfigWatcher {
+    /// Watches the file `source` reads, `appconfig` in the working directory
+    /// if it doesn't name one
+    pub fn new(source: ConfigSource) -> anyhow::Result<Self> {
+        let last = load_config_from(&source)?;
+        let dir = match source.path.as_deref().and_then(Path::parent) {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let (sender, changes) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        log::info!("Watching {} for config changes", dir.display());
+        Ok(Self {
+            source,
+            last,
+            _watcher: watcher,
+            changes,
+            changed_at: None,
+        })
+    }
+
+    /// Whether `path` is the config file, or any `appconfig` the config
+    /// crate would pick up when no file was given
+    fn is_config_file(&self, path: &Path) -> bool {
+        match &self.source.path {
+            Some(config_path) => path.file_name() == config_path.file_name(),
+            None => path.file_stem() == Some(OsStr::new("appconfig")),
+        }
+    }
+
+    /// Applies the config file to `config` once it has changed and settled.
+    /// Called every frame, so it never waits.
+    pub fn poll(&mut self, config: &mut AppConfig) -> Option<ConfigReload> {
+        for event in self.changes.try_iter() {
+            match event {
+                Ok(event) => {
+                    if !matches!(event.kind, EventKind::Access(_))
+                        && event.paths.iter().any(|path| self.is_config_file(path))
+                    {
+                        self.changed_at = Some(Instant::now());
+                    }
+                }
+                Err(e) => log::warn!("Config watcher error: {e}"),
+            }
+        }
+        if self.changed_at? + SETTLE_TIME > Instant::now() {
+            return None;
+        }
+        self.changed_at = None;
+
+        // a broken file leaves the running config as it is until it's fixed
+        let new = load_config_from(&self.source)
+            .inspect_err(|e| log::error!("Not reloading the config: {e}"))
+            .ok()?;
+        let reload = config.apply_reload(&self.last, &new);
+        self.last = new;
+        Some(reload)
+    }
+}
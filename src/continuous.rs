@@ -0,0 +1,297 @@
+// Continuous cellular automata like Lenia and SmoothLife.
+//
+// Cells hold values between 0 and 1, kept as f32 bits in the usual state
+// buffers. Each step a cell's surroundings are weighted by a radial kernel
+// made of rings, the growth function turns the weighted sum into growth
+// between -1 and 1, and `dt` of that is added to the cell.
+
+use crate::config::{
+    AppConfig, ContinuousConfig, GrowthFunction, KernelCore, SettingChecker, Topology,
+};
+use wgpu::util::DeviceExt;
+
+/// the largest kernel radius the compute shader has room for
+pub const MAX_RADIUS: u32 = 16;
+
+impl ContinuousConfig {
+    pub fn check(&self, checker: &mut SettingChecker) {
+        checker.in_range(
+            "continuous.radius",
+            self.radius as f64,
+            1.0,
+            MAX_RADIUS as f64,
+        );
+        checker.not_empty("continuous.peaks", &self.peaks);
+        checker.positive("continuous.sigma", self.sigma as f64);
+        checker.not_empty("continuous.colour_ramp", &self.colour_ramp);
+        for colour in &self.colour_ramp {
+            checker.colour("continuous.colour_ramp", colour);
+        }
+    }
+}
+
+/// Kernel weights for every offset up to `radius` away, row by row, adding up to 1
+pub fn kernel(settings: &ContinuousConfig) -> Vec<f32> {
+    let radius = settings.radius as i32;
+    let rings = settings.peaks.len() as f32;
+    let mut weights: Vec<f32> = (-radius..=radius)
+        .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| {
+            let distance = ((dx * dx + dy * dy) as f32).sqrt() / radius as f32;
+            if distance >= 1.0 {
+                return 0.0;
+            }
+            let ring = distance * rings;
+            settings.peaks[ring as usize] * kernel_core(settings.kernel, ring.fract())
+        })
+        .collect();
+    let total: f32 = weights.iter().sum();
+    if total > 0.0 {
+        weights.iter_mut().for_each(|weight| *weight /= total);
+    }
+    weights
+}
+
+/// Height of a ring `r` of the way from its inner to its outer edge
+fn kernel_core(core: KernelCore, r: f32) -> f32 {
+    match core {
+        KernelCore::Exponential if r > 0.0 => (4.0 - 1.0 / (r * (1.0 - r))).exp(),
+        KernelCore::Exponential => 0.0,
+        KernelCore::Polynomial => (4.0 * r * (1.0 - r)).powi(4),
+        KernelCore::Rectangle => (0.25..=0.75).contains(&r) as u32 as f32,
+    }
+}
+
+/// The colour ramp as linear RGBA
+pub fn colour_ramp(settings: &ContinuousConfig) -> Vec<[f32; 4]> {
+    settings
+        .colour_ramp
+        .iter()
+        .map(|colour| colour.map(|c| c as f32 / 255.0))
+        .collect()
+}
+
+/// Colour of `value` on the ramp, mixing between the stops either side
+pub fn ramp_colour(ramp: &[[f32; 4]], value: f32) -> [f32; 4] {
+    let position = value.clamp(0.0, 1.0) * (ramp.len() - 1) as f32;
+    let below = position.floor() as usize;
+    let above = (below + 1).min(ramp.len() - 1);
+    let t = position.fract();
+    std::array::from_fn(|i| ramp[below][i] + (ramp[above][i] - ramp[below][i]) * t)
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ContinuousUniform {
+    rows: u32,
+    cols: u32,
+    wrap: u32,
+    radius: u32,
+    mu: f32,
+    sigma: f32,
+    dt: f32,
+    growth: u32,
+}
+
+/// The compute pipeline of continuous mode, used instead of the rule's
+pub struct ContinuousPipeline {
+    pipeline: wgpu::ComputePipeline,
+    uniform_bind_group: wgpu::BindGroup,
+}
+
+impl ContinuousPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &AppConfig,
+        game_state_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let settings = &config.continuous;
+        let uniform = ContinuousUniform {
+            rows: config.rows as u32,
+            cols: config.cols as u32,
+            wrap: (config.topology == Topology::Torus) as u32,
+            radius: settings.radius.min(MAX_RADIUS),
+            mu: settings.mu,
+            sigma: settings.sigma,
+            dt: settings.dt,
+            growth: match settings.growth {
+                GrowthFunction::Gaussian => 0,
+                GrowthFunction::Polynomial => 1,
+                GrowthFunction::Step => 2,
+            },
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Continuous Uniform Buffer"),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let kernel_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Kernel Buffer"),
+            contents: bytemuck::cast_slice(&kernel(settings)),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Continuous Uniform Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Continuous Uniform Bind Group"),
+            layout: &uniform_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: kernel_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Continuous Pipeline Layout"),
+            bind_group_layouts: &[&uniform_bind_group_layout, game_state_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Continuous shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/continuous.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Continuous Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache: None,
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        });
+
+        Self {
+            pipeline,
+            uniform_bind_group,
+        }
+    }
+
+    /// Sets the pipeline and its uniforms, leaving group 1 for the game state
+    pub fn bind(&self, compute_pass: &mut wgpu::ComputePass) {
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(radius: u32, peaks: &[f32], kernel: KernelCore) -> ContinuousConfig {
+        ContinuousConfig {
+            radius,
+            peaks: peaks.to_vec(),
+            kernel,
+            ..Default::default()
+        }
+    }
+
+    /// The weight `dx` right and `dy` up from the middle
+    fn weight(weights: &[f32], radius: i32, dx: i32, dy: i32) -> f32 {
+        let side = 2 * radius + 1;
+        weights[((dy + radius) * side + dx + radius) as usize]
+    }
+
+    #[test]
+    fn kernels_add_up_to_1_and_look_the_same_every_way() {
+        for kernel_core in [
+            KernelCore::Exponential,
+            KernelCore::Polynomial,
+            KernelCore::Rectangle,
+        ] {
+            let weights = kernel(&settings(5, &[1.0, 0.5], kernel_core));
+            assert_eq!(weights.len(), 11 * 11);
+            let total: f32 = weights.iter().sum();
+            assert!((total - 1.0).abs() < 1e-5, "{kernel_core:?}: {total}");
+            for dy in -5..=5 {
+                for dx in -5..=5 {
+                    let w = weight(&weights, 5, dx, dy);
+                    assert_eq!(w, weight(&weights, 5, -dx, dy));
+                    assert_eq!(w, weight(&weights, 5, dy, dx));
+                }
+            }
+            // the corners are past the radius
+            assert_eq!(weight(&weights, 5, 5, 5), 0.0);
+        }
+    }
+
+    #[test]
+    fn a_single_ring_peaks_halfway_out() {
+        let weights = kernel(&settings(8, &[1.0], KernelCore::Exponential));
+        let along: Vec<f32> = (0..8).map(|dx| weight(&weights, 8, dx, 0)).collect();
+        assert_eq!(along[0], 0.0);
+        let peak = (0..8).max_by(|&a, &b| along[a].total_cmp(&along[b]));
+        assert_eq!(peak, Some(4));
+    }
+
+    #[test]
+    fn each_ring_is_scaled_by_its_peak() {
+        // the outer ring has no weight
+        let weights = kernel(&settings(8, &[1.0, 0.0], KernelCore::Polynomial));
+        assert!(weight(&weights, 8, 2, 0) > 0.0);
+        for dx in 4..8 {
+            assert_eq!(weight(&weights, 8, dx, 0), 0.0, "{dx}");
+        }
+        // and an all zero kernel stays zero rather than dividing by it
+        let weights = kernel(&settings(3, &[0.0], KernelCore::Rectangle));
+        assert!(weights.iter().all(|&w| w == 0.0));
+    }
+
+    #[test]
+    fn ramp_colours_mix_between_stops() {
+        let ramp = [
+            [0.0, 0.0, 0.0, 0.0],
+            [1.0, 0.5, 0.0, 1.0],
+            [1.0, 1.0, 1.0, 1.0],
+        ];
+        assert_eq!(ramp_colour(&ramp, 0.0), ramp[0]);
+        assert_eq!(ramp_colour(&ramp, 0.5), ramp[1]);
+        assert_eq!(ramp_colour(&ramp, 1.0), ramp[2]);
+        assert_eq!(ramp_colour(&ramp, 0.25), [0.5, 0.25, 0.0, 0.5]);
+        assert_eq!(ramp_colour(&ramp, 0.75), [1.0, 0.75, 0.5, 1.0]);
+        // values outside 0 to 1 take the end colours
+        assert_eq!(ramp_colour(&ramp, -3.0), ramp[0]);
+        assert_eq!(ramp_colour(&ramp, 7.0), ramp[2]);
+        // a single stop colours everything
+        assert_eq!(ramp_colour(&ramp[1..2], 0.3), ramp[1]);
+    }
+
+    #[test]
+    fn the_configured_ramp_is_scaled_from_255() {
+        let settings = ContinuousConfig {
+            colour_ramp: vec![[0, 51, 255, 255]],
+            ..Default::default()
+        };
+        assert_eq!(colour_ramp(&settings), [[0.0, 0.2, 1.0, 1.0]]);
+    }
+}
@@ -0,0 +1,187 @@
+// Image export: renders the grid into an offscreen texture instead of the
+// window's surface, so exported images have the same size and look no matter
+// how big the window is or which platform took them.
+
+use crate::{
+    config::AppConfig,
+    game_data::GameData,
+    readback::{self, Readback},
+    render_data::RenderData,
+};
+use anyhow::ensure;
+
+/// Exported frames are always tightly packed 8 bit RGBA
+pub const EXPORT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const BYTES_PER_PIXEL: u32 = 4;
+/// Size of a cell in exported images when the config doesn't set `export_size`
+const DEFAULT_PIXELS_PER_CELL: usize = 8;
+
+/// (width, height) of exported images, from the config if it's set and
+/// otherwise big enough to give every cell a few pixels
+pub fn export_size(config: &AppConfig, max_dimension: u32) -> (u32, u32) {
+    config.export_size.unwrap_or_else(|| {
+        let size = |cells: usize| ((cells * DEFAULT_PIXELS_PER_CELL) as u32).min(max_dimension);
+        (size(config.cols), size(config.rows))
+    })
+}
+
+/// Renders frames into an offscreen texture and reads them back
+pub struct FrameRenderer {
+    pub width: u32,
+    pub height: u32,
+    render_data: RenderData,
+    texture: wgpu::Texture,
+    // texture to buffer copies need rows padded to COPY_BYTES_PER_ROW_ALIGNMENT
+    padded_bytes_per_row: u32,
+}
+
+impl FrameRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &AppConfig,
+        (width, height): (u32, u32),
+    ) -> anyhow::Result<Self> {
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        ensure!(
+            (1..=max_dimension).contains(&width) && (1..=max_dimension).contains(&height),
+            "export size {width}x{height} must be between 1 and {max_dimension} pixels a side"
+        );
+
+        let render_data = RenderData::new(
+            device,
+            EXPORT_FORMAT,
+            &GameData::get_render_bind_group_layout(device),
+            config,
+        )?;
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Export Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: EXPORT_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let padded_bytes_per_row =
+            (width * BYTES_PER_PIXEL).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        Ok(Self {
+            width,
+            height,
+            render_data,
+            texture,
+            padded_bytes_per_row,
+        })
+    }
+
+    /// Renders the current generation of `game_data` and calls `on_ready`
+    /// with its RGBA pixels, row by row from the top, or with the error if
+    /// they couldn't be read back.
+    /// Every call reads back through its own staging buffer, so a new frame
+    /// can be rendered before the last one has been mapped.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        game_data: &GameData,
+        config: &AppConfig,
+        on_ready: impl FnOnce(Readback<Vec<u8>>) + wgpu::WasmNotSend + 'static,
+    ) {
+        let view = self
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Export Staging Buffer"),
+            size: (self.padded_bytes_per_row * self.height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Export Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Export Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(config.background_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.render_data.draw(&mut render_pass, game_data, config);
+        }
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &staging_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            self.texture.size(),
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let row_len = (self.width * BYTES_PER_PIXEL) as usize;
+        let padded_row_len = self.padded_bytes_per_row as usize;
+        readback::map_buffer(&staging_buffer, move |padded| {
+            on_ready(padded.map(|padded| {
+                padded
+                    .chunks(padded_row_len)
+                    .flat_map(|row| &row[..row_len])
+                    .copied()
+                    .collect()
+            }))
+        });
+    }
+
+    /// Blocking version of [`FrameRenderer::render`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_blocking(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        game_data: &GameData,
+        config: &AppConfig,
+    ) -> anyhow::Result<Vec<u8>> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.render(device, queue, game_data, config, move |pixels| {
+            let _ = sender.send(pixels);
+        });
+        device.poll(wgpu::PollType::Wait)?;
+        Ok(receiver.recv()??)
+    }
+}
+
+/// Encodes tightly packed RGBA pixels as a PNG file
+pub fn encode_png(width: u32, height: u32, pixels: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+    Ok(bytes)
+}
+
+/// File name for an exported frame of `generation`
+pub fn frame_file_name(generation: u64) -> String {
+    format!("generation_{generation:06}.png")
+}
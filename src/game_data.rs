@@ -1,20 +1,42 @@
 // use crate::config::CONFIG;
-use rand::{Rng, rng, rngs::ThreadRng};
+use rand::{Rng, SeedableRng, rng};
+use rand_chacha::ChaCha8Rng;
 use wgpu::util::DeviceExt;
 
-use crate::config::AppConfig;
+use crate::{
+    active::ActiveRegion,
+    config::{AppConfig, SimulationMode, Symmetry, Topology},
+    continuous::ContinuousPipeline,
+    paint::Selection,
+    readback::{self, Readback},
+    rule::{NeighbourhoodShape, RuleKind, TABLE_SIZE},
+    rule_table::TableNeighbourhood,
+    tiles::Tiles,
+    turmite::Turmites,
+};
 
 pub struct GameData {
-    rng: ThreadRng,
+    /// seed for the next randomised soup
+    next_seed: u64,
     pub game_state_buffer_a: wgpu::Buffer,
     pub game_state_buffer_b: wgpu::Buffer,
     pub is_a_current: bool,
+    /// generations since the grid was last reset, randomised or loaded
+    pub generation: u64,
     pub game_state_bind_group_a: wgpu::BindGroup,
     pub game_state_bind_group_b: wgpu::BindGroup,
     pub render_bind_group_a: wgpu::BindGroup,
     pub render_bind_group_b: wgpu::BindGroup,
     pub compute_uniform_bind_group: wgpu::BindGroup,
     pub compute_pipeline: wgpu::ComputePipeline,
+    /// used instead of `compute_pipeline` in continuous mode
+    pub continuous_pipeline: Option<ContinuousPipeline>,
+    pub turmites: Turmites,
+    /// steps the rule instead of `compute_pipeline` in unbounded universes
+    pub tiles: Option<Tiles>,
+    /// steps the rule instead of `compute_pipeline` where it can skip the
+    /// parts of the grid that aren't changing
+    pub active: Option<ActiveRegion>,
 }
 
 #[repr(C, align(16))]
@@ -22,29 +44,56 @@ pub struct GameData {
 pub struct ComputeUniform {
     rows: u32,
     cols: u32,
-    _pad: [u32; 2],
+    wrap: u32,
+    states: u32,
+    // Larger than Life
+    range: u32,
+    shape: u32,
+    include_middle: u32,
+    birth_min: u32,
+    birth_max: u32,
+    survival_min: u32,
+    survival_max: u32,
+    continuous: u32,
 }
 
 impl ComputeUniform {
-    pub fn new(rows: u32, cols: u32) -> Self {
-        Self {
-            rows,
-            cols,
-            _pad: [0; 2],
+    pub fn new(config: &AppConfig) -> Self {
+        let mut uniform = Self {
+            rows: config.rows as u32,
+            cols: config.cols as u32,
+            wrap: (config.topology == Topology::Torus) as u32,
+            states: config.rule.states,
+            continuous: (config.mode == SimulationMode::Continuous) as u32,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        match &config.rule.kind {
+            RuleKind::Elementary { .. } => uniform.range = 1,
+            RuleKind::Totalistic1d { range, .. } => uniform.range = *range,
+            _ => (),
+        }
+        if let RuleKind::LargerThanLife(ltl) = &config.rule.kind {
+            uniform.range = ltl.range;
+            uniform.shape = match ltl.shape {
+                NeighbourhoodShape::Moore => 0,
+                NeighbourhoodShape::VonNeumann => 1,
+                NeighbourhoodShape::Circular => 2,
+                NeighbourhoodShape::Hexagonal => 3,
+            };
+            uniform.include_middle = ltl.include_middle as u32;
+            (uniform.birth_min, uniform.birth_max) = (*ltl.birth.start(), *ltl.birth.end());
+            (uniform.survival_min, uniform.survival_max) =
+                (*ltl.survival.start(), *ltl.survival.end());
         }
+        uniform
     }
 }
 
 impl GameData {
     pub fn new(device: &wgpu::Device, config: &AppConfig) -> Self {
-        let rng = rng();
+        let next_seed = config.seed.unwrap_or_else(|| rng().random());
 
         let current_state = vec![0_u32; config.num_elements()];
-        // let current_state = random_state(
-        //     &mut rng,
-        //     config.num_elements() as u32,
-        //     config.init_rand_threshold,
-        // );
         let next_state = current_state.clone();
 
         let game_state_buffer_a = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -62,21 +111,37 @@ impl GameData {
                 | wgpu::BufferUsages::COPY_SRC,
         });
 
-        let compute_uniform = ComputeUniform::new(config.rows as u32, config.cols as u32);
+        let compute_uniform = ComputeUniform::new(config);
         let compute_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
             contents: bytemuck::bytes_of(&compute_uniform),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
+        // Larger than Life doesn't use the table but the binding still needs a buffer
+        let rule_table = config
+            .rule
+            .lookup_table()
+            .unwrap_or_else(|| vec![0; TABLE_SIZE]);
+        let rule_table_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Rule Table Buffer"),
+            contents: bytemuck::cast_slice(&rule_table),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
         let compute_uniform_bind_group_layout =
             GameData::get_compute_uniform_bind_group_layout(device);
         let compute_uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Compute Uniform Bind Group"),
             layout: &compute_uniform_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: compute_uniform_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: compute_uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: rule_table_buffer.as_entire_binding(),
+                },
+            ],
         });
 
         let game_state_bind_group_layout = GameData::get_compute_bind_group_layout(device);
@@ -139,9 +204,26 @@ impl GameData {
                 push_constant_ranges: &[],
             });
 
+        // Larger than Life counts over a bigger neighbourhood with its own
+        // shader, and rule tables look up whole neighbourhoods of states
+        let (source, entry_point) = match &config.rule.kind {
+            RuleKind::LargerThanLife(_) => (include_str!("shaders/ltl.wgsl"), "main"),
+            RuleKind::Table(table) => (
+                include_str!("shaders/table.wgsl"),
+                match table.neighbourhood {
+                    TableNeighbourhood::Moore => "main_moore",
+                    TableNeighbourhood::VonNeumann => "main_von_neumann",
+                },
+            ),
+            RuleKind::Hexagonal { .. } => (include_str!("shaders/compute.wgsl"), "main_hex"),
+            RuleKind::Elementary { .. } | RuleKind::Totalistic1d { .. } => {
+                (include_str!("shaders/compute.wgsl"), "main_1d")
+            }
+            _ => (include_str!("shaders/compute.wgsl"), "main"),
+        };
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Compute shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/compute.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
         });
 
         let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -149,14 +231,41 @@ impl GameData {
             layout: Some(&compute_pipeline_layout),
             cache: None,
             module: &shader,
-            entry_point: Some("main"),
+            entry_point: Some(entry_point),
             compilation_options: wgpu::PipelineCompilationOptions::default(),
         });
 
+        let continuous_pipeline = (config.mode == SimulationMode::Continuous)
+            .then(|| ContinuousPipeline::new(device, config, &game_state_bind_group_layout));
+        let turmites = Turmites::new(device, config, &game_state_bind_group_layout);
+        if config.topology == Topology::Unbounded && !Tiles::is_active(config) {
+            log::warn!(
+                "Unbounded universes only run Life-like rules without B0, {} stops at the edges",
+                config.rule
+            );
+        }
+        let tiles = Tiles::is_active(config).then(|| {
+            Tiles::new(
+                device,
+                config,
+                &compute_uniform_bind_group_layout,
+                &game_state_bind_group_layout,
+            )
+        });
+        let active = ActiveRegion::is_active(config, device).then(|| {
+            ActiveRegion::new(
+                device,
+                config,
+                &compute_uniform_bind_group_layout,
+                &game_state_bind_group_layout,
+            )
+        });
+
         Self {
-            rng,
+            next_seed,
             game_state_buffer_a,
             is_a_current: true,
+            generation: 0,
             game_state_buffer_b,
             render_bind_group_a,
             render_bind_group_b,
@@ -164,6 +273,10 @@ impl GameData {
             game_state_bind_group_b,
             compute_uniform_bind_group,
             compute_pipeline,
+            continuous_pipeline,
+            turmites,
+            tiles,
+            active,
         }
     }
 
@@ -183,7 +296,14 @@ impl GameData {
             }],
         })
     }
-    pub fn update_grid_state(&self, new_state: &[u32], queue: &wgpu::Queue) {
+    /// Starts again from `new_state`, with the turmites back at their start
+    pub fn update_grid_state(&mut self, new_state: &[u32], queue: &wgpu::Queue) {
+        self.generation = 0;
+        self.turmites.reset(queue);
+        if let Some(tiles) = &mut self.tiles {
+            tiles.reset(queue, 0);
+        }
+        self.wake(queue);
         queue.write_buffer(
             &self.game_state_buffer_a,
             0,
@@ -195,32 +315,100 @@ impl GameData {
             bytemuck::cast_slice(new_state),
         );
     }
+    /// Shows `state`, worked out somewhere else like HashLife, as generation
+    /// `generation` without starting again
+    pub fn show_grid_state(&mut self, state: &[u32], generation: u64, queue: &wgpu::Queue) {
+        self.generation = generation;
+        // whatever the tiles held beyond the grid is out of date
+        if let Some(tiles) = &mut self.tiles {
+            tiles.reset(queue, generation);
+        }
+        self.wake(queue);
+        queue.write_buffer(
+            self.get_current_state_buffer(),
+            0,
+            bytemuck::cast_slice(state),
+        );
+    }
     pub fn reset_grid_state(&mut self, config: &AppConfig, queue: &wgpu::Queue) {
         let new_state = vec![0_u32; config.num_elements()];
         self.update_grid_state(&new_state, queue);
     }
-    pub fn randomise_grid_state(&mut self, config: &AppConfig, queue: &wgpu::Queue) {
-        let new_state = random_state(
-            &mut self.rng,
-            config.num_elements() as u32,
-            config.init_rand_threshold,
-        );
+    /// Randomises the grid with the next seed in sequence and returns that seed.
+    /// Consecutive calls use consecutive seeds, so a run started from a
+    /// configured `seed` is reproducible.
+    pub fn randomise_grid_state(&mut self, config: &AppConfig, queue: &wgpu::Queue) -> u64 {
+        let seed = self.next_seed;
+        self.next_seed = self.next_seed.wrapping_add(1);
+        self.randomise_grid_state_from_seed(seed, config, queue);
+        seed
+    }
+    pub fn randomise_grid_state_from_seed(
+        &mut self,
+        seed: u64,
+        config: &AppConfig,
+        queue: &wgpu::Queue,
+    ) {
+        let new_state = random_state(seed, config);
         self.update_grid_state(&new_state, queue);
     }
+    /// Randomises the cells in `selection` with the next seed, leaving the
+    /// rest of the grid and the generation alone, and returns that seed.
+    pub fn randomise_selection(
+        &mut self,
+        selection: Selection,
+        config: &AppConfig,
+        queue: &wgpu::Queue,
+    ) -> u64 {
+        let seed = self.next_seed;
+        self.next_seed = self.next_seed.wrapping_add(1);
+        let (rows, cols, soup) = random_soup(seed, config, selection.rows, selection.cols);
+        // rows of the soup are runs of the grid, written where they go
+        // one dimensional rules only seed the newest row, at the bottom
+        let bottom = if config.rule.is_one_dimensional() {
+            0
+        } else {
+            selection.y
+        };
+        let buffer = self.get_current_state_buffer();
+        for (y, row) in soup.chunks(cols).enumerate() {
+            let offset = (selection.x + config.cols * (bottom + y)) * size_of::<u32>();
+            queue.write_buffer(buffer, offset as u64, bytemuck::cast_slice(row));
+        }
+        self.wake(queue);
+        log::info!(
+            "Randomised {rows}x{cols} cells from ({}, {bottom})",
+            selection.x
+        );
+        seed
+    }
 
     pub fn get_compute_uniform_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Compute Uniform Bind Group Layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                // the rule's lookup table
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
         })
     }
     pub fn get_compute_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
@@ -266,13 +454,333 @@ impl GameData {
         }
     }
 
+    pub fn get_current_state_buffer(&self) -> &wgpu::Buffer {
+        if self.is_a_current {
+            &self.game_state_buffer_a
+        } else {
+            &self.game_state_buffer_b
+        }
+    }
+
+    /// Steps the whole grid next generation, after something other than the
+    /// rule has changed it, like painting
+    pub fn wake(&self, queue: &wgpu::Queue) {
+        if let Some(active) = &self.active {
+            active.wake(queue);
+        }
+    }
+
+    /// Called once a generation has been computed into the other buffer
     pub fn swap_current(&mut self) {
         self.is_a_current = !self.is_a_current;
+        self.generation += 1;
+    }
+
+    /// Records a single generation into `encoder`. The caller is responsible
+    /// for calling `swap_current` once it's been submitted.
+    pub fn encode_step(&self, encoder: &mut wgpu::CommandEncoder, config: &AppConfig) {
+        let replace_rule = Turmites::replace_rule(config);
+        if replace_rule {
+            // the turmites change a copy of the grid instead
+            let (current, next) = if self.is_a_current {
+                (&self.game_state_buffer_a, &self.game_state_buffer_b)
+            } else {
+                (&self.game_state_buffer_b, &self.game_state_buffer_a)
+            };
+            encoder.copy_buffer_to_buffer(current, 0, next, 0, current.size());
+        }
+
+        if let Some(tiles) = &self.tiles
+            && !replace_rule
+        {
+            tiles.encode(
+                encoder,
+                config,
+                &self.compute_uniform_bind_group,
+                self.get_current_compute_bind_group(),
+                self.is_a_current,
+            );
+        }
+
+        // turmites change cells the active region wouldn't know about
+        if let Some(active) = &self.active
+            && !replace_rule
+            && self.turmites.count() == 0
+        {
+            active.encode(
+                encoder,
+                &self.compute_uniform_bind_group,
+                self.get_current_compute_bind_group(),
+                self.is_a_current,
+            );
+            return;
+        }
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Compute Pass"),
+            timestamp_writes: None,
+        });
+
+        if !replace_rule && self.tiles.is_none() {
+            match &self.continuous_pipeline {
+                Some(continuous_pipeline) => continuous_pipeline.bind(&mut compute_pass),
+                None => {
+                    compute_pass.set_pipeline(&self.compute_pipeline);
+                    compute_pass.set_bind_group(0, &self.compute_uniform_bind_group, &[]);
+                }
+            }
+            compute_pass.set_bind_group(1, self.get_current_compute_bind_group(), &[]);
+            compute_pass.dispatch_workgroups(
+                config.compute_dispatches[0] as u32,
+                config.compute_dispatches[1] as u32,
+                1,
+            );
+        }
+        if Turmites::is_active(config) {
+            self.turmites
+                .encode(&mut compute_pass, self.get_current_compute_bind_group());
+        }
+    }
+
+    /// Grows and shrinks an unbounded universe to fit its pattern, see
+    /// `Tiles::update`
+    pub fn update_tiles(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if let Some(tiles) = &mut self.tiles {
+            tiles.update(device, queue, self.generation);
+        }
+    }
+
+    /// Whether the next generation can be stepped, or an unbounded universe
+    /// has to wait for its tiles to catch up
+    pub fn can_step(&self) -> bool {
+        self.tiles
+            .as_ref()
+            .is_none_or(|tiles| tiles.can_step(self.generation))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_grid_state(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Vec<u32>> {
+        let bytes = readback::read_buffer_blocking(device, queue, self.get_current_state_buffer())?;
+        Ok(bytemuck::cast_slice(&bytes).to_vec())
+    }
+
+    /// Reads the current state back from the GPU and hands it to `on_ready`
+    /// once it's available.
+    pub fn request_grid_state(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        on_ready: impl FnOnce(Readback<Vec<u32>>) + wgpu::WasmNotSend + 'static,
+    ) {
+        readback::read_buffer(device, queue, self.get_current_state_buffer(), |bytes| {
+            on_ready(bytes.map(|bytes| bytemuck::cast_slice(&bytes).to_vec()))
+        });
+    }
+}
+
+/// A soup drawn from `seed` filling the configured centred region of the
+/// grid with the configured symmetry. Cells outside the region are dead.
+/// In continuous mode the region is filled with values between 0 and 1.
+pub fn random_state(seed: u64, config: &AppConfig) -> Vec<u32> {
+    let (rows, cols) = config
+        .fill_region
+        .map_or((config.rows, config.cols), |(rows, cols)| {
+            (rows.min(config.rows), cols.min(config.cols))
+        });
+    let (region_rows, region_cols, soup) = random_soup(seed, config, rows, cols);
+
+    let row_offset = if config.rule.is_one_dimensional() {
+        0
+    } else {
+        (config.rows - region_rows) / 2
+    };
+    let col_offset = (config.cols - region_cols) / 2;
+    let mut state = vec![0_u32; config.num_elements()];
+    // a region with no columns has no cells
+    for (y, row) in soup.chunks(region_cols.max(1)).enumerate() {
+        let start = col_offset + config.cols * (y + row_offset);
+        state[start..start + region_cols].copy_from_slice(row);
     }
+    state
 }
 
-fn random_state(rng: &mut ThreadRng, num_elements: u32, init_rand_threshold: f64) -> Vec<u32> {
-    (0..num_elements)
-        .map(|_| rng.random_bool(init_rand_threshold) as u32)
-        .collect()
+/// A soup drawn from `seed` with the configured symmetry, at most `rows` by
+/// `cols`. Returns the rows and columns it ended up with and its cells.
+fn random_soup(
+    seed: u64,
+    config: &AppConfig,
+    mut rows: usize,
+    mut cols: usize,
+) -> (usize, usize, Vec<u32>) {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let mut symmetry = config.soup_symmetry;
+    if config.rule.is_one_dimensional() {
+        // only the newest row starts off random, and a row can only be mirrored
+        rows = 1;
+        if matches!(symmetry, Symmetry::C4 | Symmetry::D8) {
+            symmetry = Symmetry::C2;
+        }
+    } else if matches!(symmetry, Symmetry::C4 | Symmetry::D8) {
+        // rotating by 90 degrees needs a square region
+        rows = rows.min(cols);
+        cols = rows;
+    }
+
+    let soup: Vec<u32> = (0..rows * cols)
+        .map(|_| match config.mode {
+            SimulationMode::Discrete => rng.random_bool(config.init_rand_threshold) as u32,
+            SimulationMode::Continuous => rng.random::<f32>().to_bits(),
+        })
+        .collect();
+    let cells = (0..rows)
+        .flat_map(|y| (0..cols).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (source_x, source_y) = symmetry_source(symmetry, x, y, cols, rows);
+            soup[source_x + cols * source_y]
+        })
+        .collect();
+    (rows, cols, cells)
+}
+
+/// Every cell takes its value from the smallest cell in its orbit under the
+/// symmetry, so the whole orbit ends up with the same value.
+fn symmetry_source(
+    symmetry: Symmetry,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> (usize, usize) {
+    let (flip_x, flip_y) = (width - 1 - x, height - 1 - y);
+    // (x, y) pairs. C4 and D8 regions are always square so width == height
+    let orbit: Vec<(usize, usize)> = match symmetry {
+        Symmetry::C1 => vec![(x, y)],
+        Symmetry::C2 => vec![(x, y), (flip_x, flip_y)],
+        Symmetry::C4 => vec![(x, y), (flip_y, x), (flip_x, flip_y), (y, flip_x)],
+        Symmetry::D8 => vec![
+            (x, y),
+            (flip_y, x),
+            (flip_x, flip_y),
+            (y, flip_x),
+            (y, x),
+            (flip_x, y),
+            (x, flip_y),
+            (flip_y, flip_x),
+        ],
+    };
+    orbit
+        .into_iter()
+        .min_by_key(|&(x, y)| (y, x))
+        .unwrap_or((x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConfigSource, load_config_from, override_line};
+
+    /// The built in config with `settings` like `rows=20` over it
+    fn config(settings: &[&str]) -> AppConfig {
+        load_config_from(&ConfigSource {
+            path: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/appconfig.toml").into()),
+            overrides: settings
+                .iter()
+                .map(|setting| override_line(setting).unwrap())
+                .collect(),
+        })
+        .unwrap()
+    }
+
+    /// The `size` x `size` square of `state` with its bottom left at
+    /// (`offset`, `offset`), indexed by x then y
+    fn square(state: &[u32], cols: usize, offset: usize, size: usize) -> Vec<Vec<u32>> {
+        (0..size)
+            .map(|x| {
+                (0..size)
+                    .map(|y| state[(x + offset) + cols * (y + offset)])
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn soups_come_from_their_seed() {
+        let config = config(&["rows=32", "cols=32", "init_rand_threshold=0.5"]);
+        assert_eq!(random_state(7, &config), random_state(7, &config));
+        assert_ne!(random_state(7, &config), random_state(8, &config));
+    }
+
+    #[test]
+    fn soups_only_fill_their_region() {
+        let config = config(&["rows=20", "cols=30", "fill_region=[4,6]"]);
+        let state = random_state(1, &config);
+        for (i, &cell) in state.iter().enumerate() {
+            let (x, y) = (i % 30, i / 30);
+            if !(12..18).contains(&x) || !(8..12).contains(&y) {
+                assert_eq!(cell, 0, "({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn soups_have_their_symmetry() {
+        let size = 10;
+        for symmetry in ["C2", "C4", "D8"] {
+            let config = config(&[
+                "rows=20",
+                "cols=20",
+                "fill_region=[10,10]",
+                &format!("soup_symmetry={symmetry:?}"),
+            ]);
+            let state = random_state(3, &config);
+            let cells = square(&state, 20, 5, size);
+            assert!(cells.iter().flatten().any(|&cell| cell != 0));
+            let flip = |i: usize| size - 1 - i;
+            for x in 0..size {
+                for y in 0..size {
+                    let cell = cells[x][y];
+                    assert_eq!(cell, cells[flip(x)][flip(y)], "{symmetry} ({x}, {y})");
+                    if symmetry != "C2" {
+                        assert_eq!(cell, cells[flip(y)][x], "{symmetry} ({x}, {y})");
+                    }
+                    if symmetry == "D8" {
+                        assert_eq!(cell, cells[flip(x)][y], "{symmetry} ({x}, {y})");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn turned_soups_are_square() {
+        let config = config(&[
+            "rows=20",
+            "cols=20",
+            "fill_region=[4,10]",
+            "soup_symmetry=\"C4\"",
+        ]);
+        let (rows, cols, cells) = random_soup(5, &config, 4, 10);
+        assert_eq!((rows, cols, cells.len()), (4, 4, 16));
+    }
+
+    #[test]
+    fn one_dimensional_soups_only_fill_the_bottom_row() {
+        let config = config(&["rows=20", "cols=20", "rule=\"W30\"", "soup_symmetry=\"D8\""]);
+        let state = random_state(9, &config);
+        assert!(state[20..].iter().all(|&cell| cell == 0));
+        let row = &state[..20];
+        assert!(row.iter().any(|&cell| cell != 0));
+        assert!(row.iter().eq(row.iter().rev()));
+    }
+
+    #[test]
+    fn empty_regions_are_left_empty() {
+        let config = config(&["rows=20", "cols=20", "fill_region=[0,0]"]);
+        assert!(random_state(2, &config).iter().all(|&cell| cell == 0));
+    }
 }
@@ -1,6 +1,5 @@
 use crate::{
-    config::AppConfig, game_data::GameData, paint::MousePainter, render_data::RenderData,
-    vertex::INDICES,
+    config::AppConfig, game_data::GameData, hud::Hud, paint::MousePainter, render_data::RenderData,
 };
 use std::sync::Arc;
 #[cfg(not(target_arch = "wasm32"))]
@@ -27,14 +26,17 @@ pub struct GraphicsContext {
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct RenderUniform {
     pub cell_size: [f32; 2],
-    pub _pad: [f32; 2],
+    pub gap_size: [f32; 2],
+    /// linear RGBA turmites are drawn with
+    pub turmite_colour: [f32; 4],
 }
 
 impl RenderUniform {
-    pub fn new(cell_size: (f32, f32)) -> Self {
+    pub fn new(config: &AppConfig) -> Self {
         Self {
-            cell_size: [cell_size.0, cell_size.1],
-            _pad: [0.0; 2],
+            cell_size: [config.cell_size.0, config.cell_size.1],
+            gap_size: [config.gap_size.0, config.gap_size.1],
+            turmite_colour: config.turmites.colour.map(|c| c as f32 / 255.0),
         }
     }
 }
@@ -134,8 +136,14 @@ impl GraphicsContext {
             println!("Surface configured successfully");
         }
     }
-    pub fn update(&mut self, game_data: &mut GameData, config: &AppConfig) {
+    /// Steps the next generation, returning whether it could. An unbounded
+    /// universe can't while it's waiting for its tiles.
+    pub fn update(&mut self, game_data: &mut GameData, config: &AppConfig) -> bool {
         if !self.is_surface_configured {
+            false
+        } else if !game_data.can_step() {
+            game_data.update_tiles(&self.device, &self.queue);
+            false
         } else {
             let mut encoder = self
                 .device
@@ -143,33 +151,22 @@ impl GraphicsContext {
                     label: Some("Compute Encoder"),
                 });
 
-            {
-                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: Some("Compute Pass"),
-                    timestamp_writes: None,
-                });
-
-                compute_pass.set_pipeline(&game_data.compute_pipeline);
-                compute_pass.set_bind_group(0, &game_data.compute_uniform_bind_group, &[]);
-                compute_pass.set_bind_group(1, game_data.get_current_compute_bind_group(), &[]);
-                compute_pass.dispatch_workgroups(
-                    config.compute_dispatches[0] as u32,
-                    config.compute_dispatches[1] as u32,
-                    1,
-                );
-            }
+            game_data.encode_step(&mut encoder, config);
             // using std::iter::once to make a simple iterable that yields
             self.queue.submit(std::iter::once(encoder.finish()));
 
             game_data.swap_current();
+            game_data.update_tiles(&self.device, &self.queue);
+            true
         }
     }
 
     pub fn render(
         &mut self,
         render_data: &RenderData,
-        game_state_render_bind_group: &wgpu::BindGroup,
+        game_data: &GameData,
         config: &AppConfig,
+        hud: Option<&Hud>,
     ) -> Result<(), wgpu::SurfaceError> {
         if !self.is_surface_configured {
             // don't render unless surface is configured
@@ -213,25 +210,11 @@ impl GraphicsContext {
                     timestamp_writes: None,
                     occlusion_query_set: None,
                 });
-                render_pass.set_pipeline(&render_data.pipeline);
-                render_pass.set_bind_group(0, &render_data.render_uniform_bind_group, &[]);
-                render_pass.set_bind_group(1, game_state_render_bind_group, &[]);
-                render_pass.set_vertex_buffer(0, render_data.vertex_buffer.slice(..));
-                render_pass.set_vertex_buffer(1, render_data.instance_buffer.slice(..));
-                render_pass.set_index_buffer(
-                    render_data.index_buffer.slice(..),
-                    wgpu::IndexFormat::Uint16,
-                );
-
-                // here is where we will choose which instances to draw in different
-                // draw calls.
-                // Our current state_buffer in the game_state_bind group will control which
-                // cells are shown as alive.
-                render_pass.draw_indexed(
-                    0..INDICES.len() as u32,
-                    0,
-                    0..config.num_elements() as u32,
-                );
+                render_data.draw(&mut render_pass, game_data, config);
+                // text goes over the cells
+                if let Some(hud) = hud {
+                    hud.draw(&mut render_pass);
+                }
             } // using std::iter::once to make a simple iterable that yields
             // a single item. This means I don't need to make a vec or array.
             self.queue.submit(std::iter::once(encoder.finish()));
@@ -285,7 +268,9 @@ impl GraphicsContext {
     }
 }
 
-pub fn get_window(event_loop: &ActiveEventLoop) -> Arc<Window> {
+// the config is only used for the window size on desktop
+#[allow(unused_variables)]
+pub fn get_window(event_loop: &ActiveEventLoop, config: &AppConfig) -> Arc<Window> {
     #[allow(unused_mut)]
     let mut window_attributes = Window::default_attributes();
 
@@ -297,8 +282,10 @@ pub fn get_window(event_loop: &ActiveEventLoop) -> Arc<Window> {
 
     #[cfg(not(target_arch = "wasm32"))]
     {
-        // For native, use a reasonable default size
-        window_attributes = window_attributes.with_inner_size(PhysicalSize::new(800, 600));
+        // For native, use the configured size or a reasonable default
+        let (width, height) = config.window_size.unwrap_or((800, 600));
+        window_attributes =
+            window_attributes.with_inner_size(PhysicalSize::new(width as u32, height as u32));
     }
 
     // a winit window requires a an event loop to create it
@@ -0,0 +1,759 @@
+// HashLife: Gosper's algorithm for jumping huge numbers of generations.
+//
+// The universe is a quadtree where identical squares are stored once, so a
+// repeating pattern costs about as much as one copy of it. Each square of
+// side 2^n remembers what its middle looks like 2^k generations later, for
+// k up to n - 2, and that result is built out of the results of its
+// quarters. Patterns that repeat themselves in space and time, like guns and
+// breeders, get faster the further they run.
+//
+// Only two state rules on the Moore neighbourhood are supported, since the
+// rule is applied through its lookup table. The universe is centred on
+// (0, 0) with y going up, like the grid.
+//
+// Macrocell (`.mc`) files, Golly's format for HashLife patterns, list the
+// squares one per line, smallest first. 8x8 squares are drawn with `.` for
+// dead and `*` for live cells, `$` ending each row from the top. Bigger ones
+// are `size n nw ne sw se`, for a square of side 2^size made of the squares
+// on lines n (counting from 1, with 0 for an empty square). The last line
+// is the whole universe.
+//
+//   [M2] (game-of-life)
+//   #R B3/S23
+//   .*$..*$***$
+//   4 0 0 1 0
+
+use anyhow::{Context, bail, ensure};
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use crate::{
+    rle::Pattern,
+    rule::{Rule, RuleKind},
+};
+
+/// Where macrocell files are saved on desktop
+pub const DEFAULT_MACROCELL_PATH: &str = "pattern.mc";
+/// the most generations a single step can jump, as a power of 2
+pub const MAX_STEP_LOG2: u32 = 48;
+/// the node store is cleared out once it has more nodes than this
+const MAX_NODES: usize = 1 << 23;
+/// squares of side 2^3 are written out cell by cell in macrocell files
+const LEAF_LEVEL: u8 = 3;
+
+type NodeId = u32;
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    /// the north west, north east, south west and south east quarters
+    children: [NodeId; 4],
+    /// the node is a square of side 2^level
+    level: u8,
+    population: u64,
+}
+
+pub struct HashLife {
+    rule: Rule,
+    /// whether a cell is alive next generation, by its neighbourhood
+    table: Vec<bool>,
+    nodes: Vec<Node>,
+    /// the node made of each set of quarters
+    index: HashMap<[NodeId; 4], NodeId>,
+    /// the middle of each node `2^step_log2` generations on, or fewer for
+    /// nodes too small to go that far
+    results: HashMap<NodeId, NodeId>,
+    /// the empty node of each level
+    empty: Vec<NodeId>,
+    root: NodeId,
+    step_log2: u32,
+    generation: u64,
+}
+
+impl HashLife {
+    /// Whether HashLife can run `rule`. B0 rules are left out as empty
+    /// nodes have to stay empty.
+    pub fn supports(rule: &Rule) -> bool {
+        matches!(
+            rule.kind,
+            RuleKind::Totalistic { .. } | RuleKind::Isotropic { .. } | RuleKind::Map { .. }
+        ) && rule.states == 2
+            && !rule.births_from_nothing()
+    }
+
+    /// An empty universe
+    pub fn new(rule: &Rule) -> anyhow::Result<Self> {
+        ensure!(
+            Self::supports(rule),
+            "HashLife only runs two state Life-like, isotropic and MAP rules without B0, not {rule}"
+        );
+        let table = rule
+            .lookup_table()
+            .context("rule has no lookup table")?
+            .iter()
+            .map(|&alive| alive == 1)
+            .collect();
+        let leaf = |population| Node {
+            children: [DEAD; 4],
+            level: 0,
+            population,
+        };
+        let mut hashlife = Self {
+            rule: rule.clone(),
+            table,
+            nodes: vec![leaf(0), leaf(1)],
+            index: HashMap::new(),
+            results: HashMap::new(),
+            empty: vec![DEAD],
+            root: DEAD,
+            step_log2: 0,
+            generation: 0,
+        };
+        hashlife.root = hashlife.empty_node(LEAF_LEVEL);
+        Ok(hashlife)
+    }
+
+    /// A universe holding `state`, a `rows` x `cols` grid, centred so that
+    /// `to_grid` with the same size gives it back
+    pub fn from_grid(state: &[u32], rows: usize, cols: usize, rule: &Rule) -> anyhow::Result<Self> {
+        let mut hashlife = Self::new(rule)?;
+        // big enough that the grid is inside the middle half
+        let level = ((usize::BITS - rows.max(cols).leading_zeros()) as u8 + 1).max(LEAF_LEVEL);
+        let (left, bottom) = (cols as i64 / 2, rows as i64 / 2);
+        let alive = |x: i64, y: i64| {
+            let (col, row) = (x + left, y + bottom);
+            (0..cols as i64).contains(&col)
+                && (0..rows as i64).contains(&row)
+                && state[row as usize * cols + col as usize] != 0
+        };
+        let corner = -(1_i64 << (level - 1));
+        hashlife.root = hashlife.build(level, corner, corner, &alive, (left, bottom, cols, rows));
+        hashlife.shrink();
+        Ok(hashlife)
+    }
+
+    /// A universe holding `pattern` in the middle, in its own rule or Life
+    pub fn from_pattern(pattern: &Pattern) -> anyhow::Result<Self> {
+        // pattern rows start at the top, grid rows at the bottom
+        let state: Vec<u32> = pattern
+            .cells
+            .chunks(pattern.width.max(1))
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        let rule = pattern.rule.clone().unwrap_or_default();
+        Self::from_grid(&state, pattern.height, pattern.width, &rule)
+    }
+
+    /// Builds the node of side 2^level with its bottom left cell at (x, y)
+    fn build(
+        &mut self,
+        level: u8,
+        x: i64,
+        y: i64,
+        alive: &impl Fn(i64, i64) -> bool,
+        (left, bottom, cols, rows): (i64, i64, usize, usize),
+    ) -> NodeId {
+        let size = 1_i64 << level;
+        // nothing outside the grid
+        if x + size <= -left
+            || y + size <= -bottom
+            || x >= cols as i64 - left
+            || y >= rows as i64 - bottom
+        {
+            return self.empty_node(level);
+        }
+        if level == 0 {
+            return alive(x, y) as NodeId;
+        }
+        let half = size / 2;
+        let bounds = (left, bottom, cols, rows);
+        let nw = self.build(level - 1, x, y + half, alive, bounds);
+        let ne = self.build(level - 1, x + half, y + half, alive, bounds);
+        let sw = self.build(level - 1, x, y, alive, bounds);
+        let se = self.build(level - 1, x + half, y, alive, bounds);
+        self.join([nw, ne, sw, se])
+    }
+
+    /// The middle `rows` x `cols` cells of the universe, laid out like the grid
+    pub fn to_grid(&self, rows: usize, cols: usize) -> Vec<u32> {
+        let mut grid = vec![0; rows * cols];
+        let level = self.nodes[self.root as usize].level;
+        let corner = -(1_i64 << (level - 1));
+        let (left, bottom) = (cols as i64 / 2, rows as i64 / 2);
+        let mut stack = vec![(self.root, corner, corner)];
+        while let Some((id, x, y)) = stack.pop() {
+            let node = self.nodes[id as usize];
+            let size = 1_i64 << node.level;
+            let (col, row) = (x + left, y + bottom);
+            if node.population == 0
+                || col + size <= 0
+                || row + size <= 0
+                || col >= cols as i64
+                || row >= rows as i64
+            {
+                continue;
+            }
+            if node.level == 0 {
+                grid[row as usize * cols + col as usize] = 1;
+                continue;
+            }
+            let half = size / 2;
+            let [nw, ne, sw, se] = node.children;
+            stack.extend([
+                (nw, x, y + half),
+                (ne, x + half, y + half),
+                (sw, x, y),
+                (se, x + half, y),
+            ]);
+        }
+        grid
+    }
+
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root as usize].population
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Carries on counting from `generation`, e.g. that of the grid it came from
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    /// how many generations `step` jumps, as a power of 2
+    pub fn step_log2(&self) -> u32 {
+        self.step_log2
+    }
+
+    pub fn set_step_log2(&mut self, step_log2: u32) {
+        let step_log2 = step_log2.min(MAX_STEP_LOG2);
+        if step_log2 != self.step_log2 {
+            // results are only kept for one step size
+            self.results.clear();
+            self.step_log2 = step_log2;
+        }
+    }
+
+    /// Jumps `2^step_log2` generations
+    pub fn step(&mut self) {
+        if self.nodes.len() > MAX_NODES {
+            self.collect_garbage();
+        }
+        // the result is the middle half of the root, so pad the pattern out
+        // until it's in the middle quarter and there's room to go far enough
+        while self.nodes[self.root as usize].level < self.step_log2 as u8 + 2 || !self.is_padded() {
+            self.root = self.expand(self.root);
+        }
+        self.root = self.expand(self.root);
+        self.root = self.next(self.root);
+        self.shrink();
+        self.generation = self.generation.saturating_add(1 << self.step_log2);
+    }
+
+    /// Whether the root's live cells are all in its middle half
+    fn is_padded(&mut self) -> bool {
+        let root = self.nodes[self.root as usize];
+        root.level >= LEAF_LEVEL && {
+            let middle = self.middle(self.root);
+            self.nodes[middle as usize].population == root.population
+        }
+    }
+
+    /// Cuts the root down to its middle half while that holds everything
+    fn shrink(&mut self) {
+        while self.nodes[self.root as usize].level > LEAF_LEVEL && self.is_padded() {
+            self.root = self.middle(self.root);
+        }
+    }
+
+    /// The node with these quarters, made if it doesn't exist yet
+    fn join(&mut self, children: [NodeId; 4]) -> NodeId {
+        if let Some(&id) = self.index.get(&children) {
+            return id;
+        }
+        let level = self.nodes[children[0] as usize].level + 1;
+        let population = children
+            .iter()
+            .map(|&child| self.nodes[child as usize].population)
+            .fold(0_u64, u64::saturating_add);
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node {
+            children,
+            level,
+            population,
+        });
+        self.index.insert(children, id);
+        id
+    }
+
+    fn empty_node(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let smaller = *self.empty.last().unwrap();
+            let id = self.join([smaller; 4]);
+            self.empty.push(id);
+        }
+        self.empty[level as usize]
+    }
+
+    /// The node one level up with `id` in the middle
+    fn expand(&mut self, id: NodeId) -> NodeId {
+        let node = self.nodes[id as usize];
+        let empty = self.empty_node(node.level - 1);
+        let [nw, ne, sw, se] = node.children;
+        let nw = self.join([empty, empty, empty, nw]);
+        let ne = self.join([empty, empty, ne, empty]);
+        let sw = self.join([empty, sw, empty, empty]);
+        let se = self.join([se, empty, empty, empty]);
+        self.join([nw, ne, sw, se])
+    }
+
+    fn children(&self, id: NodeId) -> [NodeId; 4] {
+        self.nodes[id as usize].children
+    }
+
+    /// The middle half of a node
+    fn middle(&mut self, id: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.children(id);
+        self.join([
+            self.children(nw)[3],
+            self.children(ne)[2],
+            self.children(sw)[1],
+            self.children(se)[0],
+        ])
+    }
+
+    /// The middle half of the rectangle made by `west` and `east` side by side
+    fn middle_horizontal(&mut self, west: NodeId, east: NodeId) -> NodeId {
+        let ([_, w_ne, _, w_se], [e_nw, _, e_sw, _]) = (self.children(west), self.children(east));
+        self.join([w_ne, e_nw, w_se, e_sw])
+    }
+
+    /// The middle half of the rectangle made by `north` above `south`
+    fn middle_vertical(&mut self, north: NodeId, south: NodeId) -> NodeId {
+        let ([_, _, n_sw, n_se], [s_nw, s_ne, _, _]) = (self.children(north), self.children(south));
+        self.join([n_sw, n_se, s_nw, s_ne])
+    }
+
+    /// The middle half of a node of level 2 or more, `2^min(step_log2,
+    /// level - 2)` generations on
+    fn next(&mut self, id: NodeId) -> NodeId {
+        if let Some(&result) = self.results.get(&id) {
+            return result;
+        }
+        let node = self.nodes[id as usize];
+        let result = if node.population == 0 {
+            self.empty_node(node.level - 1)
+        } else if node.level == 2 {
+            self.next_generation(id)
+        } else {
+            let [nw, ne, sw, se] = node.children;
+            let north = self.middle_horizontal(nw, ne);
+            let west = self.middle_vertical(nw, sw);
+            let middle = self.middle(id);
+            let east = self.middle_vertical(ne, se);
+            let south = self.middle_horizontal(sw, se);
+            let squares = [nw, north, ne, west, middle, east, sw, south, se];
+
+            // the nine overlapping squares are taken half of the way there,
+            // or only cut down to size if the step is shorter than that
+            let full_speed = self.step_log2 + 2 >= node.level as u32;
+            let mut halfway = [DEAD; 9];
+            for (square, result) in squares.into_iter().zip(&mut halfway) {
+                *result = if full_speed {
+                    self.next(square)
+                } else {
+                    self.middle(square)
+                };
+            }
+            let [a, b, c, d, e, f, g, h, i] = halfway;
+            let quarters = [[a, b, d, e], [b, c, e, f], [d, e, g, h], [e, f, h, i]];
+            let mut results = [DEAD; 4];
+            for (quarter, result) in quarters.into_iter().zip(&mut results) {
+                let joined = self.join(quarter);
+                *result = self.next(joined);
+            }
+            self.join(results)
+        };
+        self.results.insert(id, result);
+        result
+    }
+
+    /// The middle 2x2 cells of a 4x4 node one generation on
+    fn next_generation(&mut self, id: NodeId) -> NodeId {
+        // cells by (x, y) from the bottom left
+        let mut cells = [[false; 4]; 4];
+        let [nw, ne, sw, se] = self.children(id);
+        for (quarter, (x, y)) in [(nw, (0, 2)), (ne, (2, 2)), (sw, (0, 0)), (se, (2, 0))] {
+            let [q_nw, q_ne, q_sw, q_se] = self.children(quarter);
+            cells[x][y + 1] = q_nw == ALIVE;
+            cells[x + 1][y + 1] = q_ne == ALIVE;
+            cells[x][y] = q_sw == ALIVE;
+            cells[x + 1][y] = q_se == ALIVE;
+        }
+        let next = |x: usize, y: usize| {
+            // the neighbourhood's cells as bits, top left first
+            let mut index = 0;
+            for dy in [1, 0, -1] {
+                for dx in [-1, 0, 1] {
+                    let alive = cells[(x as i32 + dx) as usize][(y as i32 + dy) as usize];
+                    index = (index << 1) | alive as usize;
+                }
+            }
+            self.table[index] as NodeId
+        };
+        let result = [next(1, 2), next(2, 2), next(1, 1), next(2, 1)];
+        self.join(result)
+    }
+
+    /// Drops every node the root doesn't use
+    fn collect_garbage(&mut self) {
+        let old_nodes = std::mem::take(&mut self.nodes);
+        self.nodes = old_nodes[..2].to_vec();
+        self.index.clear();
+        self.results.clear();
+        self.empty = vec![DEAD];
+
+        let mut copied: HashMap<NodeId, NodeId> = HashMap::from([(DEAD, DEAD), (ALIVE, ALIVE)]);
+        // children are copied before their parents
+        let mut stack = vec![(self.root, false)];
+        while let Some((id, children_done)) = stack.pop() {
+            if copied.contains_key(&id) {
+                continue;
+            }
+            let children = old_nodes[id as usize].children;
+            if children_done {
+                let new_id = self.join(children.map(|child| copied[&child]));
+                copied.insert(id, new_id);
+            } else {
+                stack.push((id, true));
+                stack.extend(children.map(|child| (child, false)));
+            }
+        }
+        self.root = copied[&self.root];
+        log::info!("HashLife kept {} nodes", self.nodes.len());
+    }
+
+    /// Reads a macrocell file
+    pub fn parse_macrocell(text: &str) -> anyhow::Result<Self> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        let header = lines.next().context("empty macrocell file")?;
+        ensure!(header.starts_with("[M2]"), "not a macrocell file");
+
+        let mut rule = Rule::default();
+        let mut generation = 0;
+        // node ids by line number, from 1
+        let mut ids = vec![];
+        let mut hashlife: Option<Self> = None;
+        for line in lines {
+            if let Some(comment) = line.strip_prefix('#') {
+                if let Some(name) = comment.strip_prefix('R') {
+                    rule = name.trim().parse()?;
+                } else if let Some(number) = comment.strip_prefix('G') {
+                    generation = number
+                        .trim()
+                        .parse()
+                        .with_context(|| format!("bad generation {number:?}"))?;
+                }
+                continue;
+            }
+            // the rule comes before the first node
+            if hashlife.is_none() {
+                hashlife = Some(Self::new(&rule)?);
+            }
+            let hashlife = hashlife.as_mut().unwrap();
+            let id = if line.starts_with(['.', '*', '$']) {
+                hashlife.parse_leaf(line)?
+            } else {
+                let numbers = line
+                    .split_whitespace()
+                    .map(|number| {
+                        number
+                            .parse::<usize>()
+                            .with_context(|| format!("bad number {number:?} in {line:?}"))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let &[level, nw, ne, sw, se] = numbers.as_slice() else {
+                    bail!("expected a size and 4 nodes in {line:?}");
+                };
+                ensure!(
+                    (LEAF_LEVEL as usize + 1..64).contains(&level),
+                    "squares in {line:?} have to be bigger than 8x8"
+                );
+                let mut quarters = [DEAD; 4];
+                for (child, quarter) in [nw, ne, sw, se].into_iter().zip(&mut quarters) {
+                    *quarter = match child {
+                        0 => hashlife.empty_node(level as u8 - 1),
+                        n => {
+                            let id = *ids.get(n - 1).with_context(|| {
+                                format!("node {n} isn't defined before {line:?}")
+                            })?;
+                            ensure!(
+                                hashlife.nodes[id as usize].level as usize == level - 1,
+                                "node {n} is the wrong size for {line:?}"
+                            );
+                            id
+                        }
+                    };
+                }
+                hashlife.join(quarters)
+            };
+            ids.push(id);
+        }
+        let mut hashlife = hashlife.context("macrocell file has no cells")?;
+        hashlife.root = *ids.last().unwrap();
+        hashlife.generation = generation;
+        hashlife.shrink();
+        Ok(hashlife)
+    }
+
+    /// Reads an 8x8 square drawn with `.`, `*` and `$`
+    fn parse_leaf(&mut self, line: &str) -> anyhow::Result<NodeId> {
+        let size = 1 << LEAF_LEVEL;
+        let mut alive = [[false; 8]; 8];
+        let (mut x, mut row) = (0, 0);
+        for c in line.chars() {
+            match c {
+                '.' | '*' => {
+                    ensure!(x < size && row < size, "{line:?} is more than 8x8");
+                    // rows go from the top
+                    alive[x][size - 1 - row] = c == '*';
+                    x += 1;
+                }
+                '$' => (x, row) = (0, row + 1),
+                _ => bail!("unexpected {c:?} in {line:?}"),
+            }
+        }
+        let cell = |x: i64, y: i64| alive[x as usize][y as usize];
+        Ok(self.build(LEAF_LEVEL, 0, 0, &cell, (0, 0, size, size)))
+    }
+
+    /// Writes the universe as a macrocell file
+    pub fn to_macrocell(&self) -> String {
+        let mut text = format!("[M2] (game-of-life)\n#R {}\n", self.rule);
+        if self.generation > 0 {
+            writeln!(text, "#G {}", self.generation).unwrap();
+        }
+        // line numbers of the nodes written so far
+        let mut lines: HashMap<NodeId, usize> = HashMap::new();
+        let mut stack = vec![(self.root, false)];
+        while let Some((id, children_done)) = stack.pop() {
+            let node = self.nodes[id as usize];
+            if lines.contains_key(&id) || (node.population == 0 && id != self.root) {
+                continue;
+            }
+            if node.level == LEAF_LEVEL {
+                text += &self.leaf_line(id);
+            } else if children_done {
+                let children = node
+                    .children
+                    .map(|child| lines.get(&child).copied().unwrap_or(0));
+                let [nw, ne, sw, se] = children;
+                writeln!(text, "{} {nw} {ne} {sw} {se}", node.level).unwrap();
+            } else {
+                stack.push((id, true));
+                stack.extend(node.children.iter().rev().map(|&child| (child, false)));
+                continue;
+            }
+            lines.insert(id, lines.len() + 1);
+        }
+        text
+    }
+
+    /// An 8x8 node drawn a row at a time from the top, leaving out dead
+    /// cells at the ends of rows and empty rows at the bottom
+    fn leaf_line(&self, id: NodeId) -> String {
+        let size = 1_i64 << LEAF_LEVEL;
+        let mut cells = vec![0; (size * size) as usize];
+        let mut stack = vec![(id, 0, 0)];
+        while let Some((id, x, y)) = stack.pop() {
+            let node = self.nodes[id as usize];
+            if node.population == 0 {
+                continue;
+            }
+            if node.level == 0 {
+                cells[(y * size + x) as usize] = 1;
+                continue;
+            }
+            let half = 1 << (node.level - 1);
+            let [nw, ne, sw, se] = node.children;
+            stack.extend([
+                (nw, x, y + half),
+                (ne, x + half, y + half),
+                (sw, x, y),
+                (se, x + half, y),
+            ]);
+        }
+        let rows: Vec<String> = cells
+            .chunks(size as usize)
+            .rev()
+            .map(|row| {
+                row.iter()
+                    .map(|&cell| if cell == 1 { '*' } else { '.' })
+                    .collect::<String>()
+                    .trim_end_matches('.')
+                    .to_string()
+            })
+            .collect();
+        // an empty square is a single `$`
+        let used = rows
+            .iter()
+            .rposition(|row| !row.is_empty())
+            .map_or(1, |i| i + 1);
+        let mut line: String = rows[..used].iter().map(|row| format!("{row}$")).collect();
+        line.push('\n');
+        line
+    }
+}
+
+/// Loads a `.mc` or `.rle` pattern, jumps it forward 2^`step_log2`
+/// generations and prints how it ended up, saving it to `out` if given.
+pub fn run_hashlife(path: &Path, step_log2: u32, out: Option<&Path>) -> anyhow::Result<()> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("unable to read {}", path.display()))?;
+    let mut hashlife = if text.trim_start().starts_with("[M2]") {
+        HashLife::parse_macrocell(&text)?
+    } else {
+        HashLife::from_pattern(&Pattern::parse(&text)?)?
+    };
+    println!(
+        "Loaded {} cells of {}",
+        hashlife.population(),
+        hashlife.rule()
+    );
+    hashlife.set_step_log2(step_log2);
+    let start = std::time::Instant::now();
+    hashlife.step();
+    println!(
+        "Generation {}: {} cells, in {:.2?}",
+        hashlife.generation(),
+        hashlife.population(),
+        start.elapsed()
+    );
+    if let Some(out) = out {
+        std::fs::write(out, hashlife.to_macrocell())?;
+        println!("Saved pattern to {}", out.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the R-pentomino, which keeps changing for over a thousand generations
+    const R_PENTOMINO: &str = "x = 3, y = 3, rule = B3/S23\nb2o$2o$bo!";
+
+    fn grid(rle: &str, rows: usize, cols: usize) -> Vec<u32> {
+        Pattern::parse(rle).unwrap().to_grid(rows, cols).unwrap()
+    }
+
+    /// One generation of `rule` on a bounded grid, the slow way
+    fn naive_step(state: &[u32], rows: usize, cols: usize, rule: &Rule) -> Vec<u32> {
+        let table = rule.lookup_table().unwrap();
+        let alive = |x: i64, y: i64| {
+            (0..cols as i64).contains(&x)
+                && (0..rows as i64).contains(&y)
+                && state[y as usize * cols + x as usize] != 0
+        };
+        (0..rows as i64)
+            .flat_map(|y| (0..cols as i64).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                // NW N NE W C E SW S SE, with north being up a row
+                let index = [1, 0, -1]
+                    .iter()
+                    .flat_map(|dy| [-1, 0, 1].map(|dx| alive(x + dx, y + dy)))
+                    .fold(0, |index, alive| index << 1 | alive as usize);
+                table[index]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn jumps_match_stepping_one_generation_at_a_time() {
+        let (rows, cols) = (128, 128);
+        for rule in ["B3/S23", "B36/S23", "B3/S23-a", "B3/S2-i34q"] {
+            let rule: Rule = rule.parse().unwrap();
+            let mut state = grid(R_PENTOMINO, rows, cols);
+            let mut hashlife = HashLife::from_grid(&state, rows, cols, &rule).unwrap();
+            hashlife.set_step_log2(5);
+            hashlife.step();
+            for _ in 0..32 {
+                state = naive_step(&state, rows, cols, &rule);
+            }
+            assert_eq!(hashlife.generation(), 32);
+            assert_eq!(hashlife.to_grid(rows, cols), state, "{rule}");
+            let population = state.iter().filter(|&&cell| cell != 0).count();
+            assert_eq!(hashlife.population(), population as u64);
+        }
+    }
+
+    #[test]
+    fn single_steps_match_too() {
+        let (rows, cols) = (64, 64);
+        let rule = Rule::default();
+        let mut state = grid(R_PENTOMINO, rows, cols);
+        let mut hashlife = HashLife::from_grid(&state, rows, cols, &rule).unwrap();
+        for _ in 0..10 {
+            hashlife.step();
+            state = naive_step(&state, rows, cols, &rule);
+            assert_eq!(hashlife.to_grid(rows, cols), state);
+        }
+    }
+
+    #[test]
+    fn b0_rules_arent_supported() {
+        for rule in ["B03/S23", "B0/S8"] {
+            let rule: Rule = rule.parse().unwrap();
+            assert!(!HashLife::supports(&rule));
+            assert!(HashLife::new(&rule).is_err());
+        }
+        assert!(!HashLife::supports(&"B2/S/C3".parse().unwrap()));
+    }
+
+    #[test]
+    fn macrocells_round_trip() {
+        let glider = "[M2] (game-of-life)\n#R B3/S23\n.*$..*$***$\n4 0 0 1 0\n";
+        let hashlife = HashLife::parse_macrocell(glider).unwrap();
+        assert_eq!(hashlife.population(), 5);
+        assert_eq!(hashlife.rule(), &Rule::default());
+
+        let (rows, cols) = (64, 64);
+        let mut hashlife =
+            HashLife::from_grid(&grid(R_PENTOMINO, rows, cols), rows, cols, &Rule::default())
+                .unwrap();
+        hashlife.set_step_log2(6);
+        hashlife.step();
+        let text = hashlife.to_macrocell();
+        assert!(text.contains("#G 64"), "{text}");
+        let loaded = HashLife::parse_macrocell(&text).unwrap();
+        assert_eq!(loaded.generation(), 64);
+        assert_eq!(loaded.population(), hashlife.population());
+        assert_eq!(loaded.to_grid(rows, cols), hashlife.to_grid(rows, cols));
+    }
+
+    #[test]
+    fn corrupt_macrocells_are_rejected() {
+        for text in [
+            "",
+            "#R B3/S23\n.*$\n",
+            "[M2]\n#R B3/S23\n",
+            "[M2]\n.*$..*$***$\n4 0 0 2 0\n",
+            "[M2]\n.*$..*$***$\n5 0 0 1 0\n",
+            "[M2]\n.*$..*$***$\n3 0 0 1 0\n",
+            "[M2]\n.*$..*$***$\n4 0 0 1\n",
+            "[M2]\n.........*$\n",
+            "[M2]\n#G many\n.*$\n",
+            "[M2]\n#R B03/S23\n.*$\n",
+        ] {
+            assert!(HashLife::parse_macrocell(text).is_err(), "{text:?}");
+        }
+    }
+}
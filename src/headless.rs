@@ -0,0 +1,232 @@
+// Runs the simulation without a window, for batch jobs on the desktop.
+// The grid size comes straight from the config rather than from a window.
+
+use crate::{
+    analysis::Census,
+    config::{AppConfig, RecordingFormat},
+    export::{self, FrameRenderer},
+    game_data::GameData,
+    readback,
+    recording::Recording,
+    tiles,
+};
+use std::path::Path;
+
+pub struct Headless {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub game_data: GameData,
+    pub config: AppConfig,
+}
+
+impl Headless {
+    pub fn new(config: AppConfig) -> anyhow::Result<Self> {
+        pollster::block_on(Headless::new_async(config))
+    }
+
+    async fn new_async(config: AppConfig) -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY,
+            ..Default::default()
+        });
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptionsBase {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await?;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("headless device"),
+                required_features: wgpu::Features::empty(),
+                required_limits: wgpu::Limits::default(),
+                memory_hints: Default::default(),
+                trace: wgpu::Trace::Off,
+            })
+            .await?;
+        let game_data = GameData::new(&device, &config);
+
+        Ok(Self {
+            device,
+            queue,
+            game_data,
+            config,
+        })
+    }
+
+    /// Runs `generations` generations in a single submission, or in batches
+    /// with the tiles growing in between in an unbounded universe.
+    pub fn step(&mut self, generations: usize) {
+        let batch_size = self.batch_size(generations);
+        let mut remaining = generations;
+        while remaining > 0 {
+            let batch = remaining.min(batch_size);
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Headless Compute Encoder"),
+                });
+            for _ in 0..batch {
+                self.game_data.encode_step(&mut encoder, &self.config);
+                self.game_data.swap_current();
+            }
+            self.queue.submit(std::iter::once(encoder.finish()));
+            self.update_tiles();
+            remaining -= batch;
+        }
+    }
+
+    /// how many of `generations` can go in one submission
+    fn batch_size(&self, generations: usize) -> usize {
+        if self.game_data.tiles.is_some() {
+            tiles::STEPS_BETWEEN_UPDATES
+        } else {
+            generations.max(1)
+        }
+    }
+
+    fn update_tiles(&mut self) {
+        if let Some(tiles) = &mut self.game_data.tiles
+            && let Err(e) =
+                tiles.update_blocking(&self.device, &self.queue, self.game_data.generation)
+        {
+            log::error!("Unable to update the tiles: {e}");
+        }
+    }
+
+    pub fn read_grid_state(&self) -> anyhow::Result<Vec<u32>> {
+        self.game_data.read_grid_state(&self.device, &self.queue)
+    }
+
+    /// Runs `generations` generations and returns the state after each one.
+    /// Every state is copied into one staging buffer so there's only a single
+    /// readback for the whole batch.
+    pub fn step_recording(&mut self, generations: usize) -> anyhow::Result<Vec<Vec<u32>>> {
+        let state_size = (self.config.num_elements() * std::mem::size_of::<u32>()) as u64;
+        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Recording Staging Buffer"),
+            size: state_size * generations as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let batch_size = self.batch_size(generations) as u64;
+        for batch_start in (0..generations as u64).step_by(batch_size as usize) {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Headless Recording Encoder"),
+                });
+            for generation in batch_start..(batch_start + batch_size).min(generations as u64) {
+                self.game_data.encode_step(&mut encoder, &self.config);
+                self.game_data.swap_current();
+                encoder.copy_buffer_to_buffer(
+                    self.game_data.get_current_state_buffer(),
+                    0,
+                    &staging_buffer,
+                    generation * state_size,
+                    state_size,
+                );
+            }
+            self.queue.submit(std::iter::once(encoder.finish()));
+            self.update_tiles();
+        }
+
+        let bytes = readback::map_buffer_blocking(&self.device, &staging_buffer)?;
+        Ok(bytemuck::cast_slice::<u8, u32>(&bytes)
+            .chunks(self.config.num_elements())
+            .map(|state| state.to_vec())
+            .collect())
+    }
+
+    /// Renders the current generation to a PNG file
+    pub fn export_png(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let size = export::export_size(&self.config, self.device.limits().max_texture_dimension_2d);
+        let renderer = FrameRenderer::new(&self.device, &self.config, size)?;
+        let pixels =
+            renderer.render_blocking(&self.device, &self.queue, &self.game_data, &self.config)?;
+        std::fs::write(
+            path,
+            export::encode_png(renderer.width, renderer.height, &pixels)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// Randomises a grid, runs it for `generations` and prints the census.
+pub fn run_census(config: AppConfig, generations: usize) -> anyhow::Result<()> {
+    let mut headless = Headless::new(config)?;
+    let seed = headless
+        .game_data
+        .randomise_grid_state(&headless.config, &headless.queue);
+    println!("Randomised with seed {seed}");
+    headless.step(generations);
+
+    let state = headless.read_grid_state()?;
+    let census = Census::of_grid(
+        &state,
+        headless.config.rows,
+        headless.config.cols,
+        headless.config.topology,
+        &headless.config.rule,
+    )?;
+    println!("Census after {generations} generations:\n{census}");
+    Ok(())
+}
+
+/// Randomises a grid, runs it for `generations` and saves the result as a PNG.
+pub fn run_export(config: AppConfig, path: &Path, generations: usize) -> anyhow::Result<()> {
+    let mut headless = Headless::new(config)?;
+    let seed = headless
+        .game_data
+        .randomise_grid_state(&headless.config, &headless.queue);
+    println!("Randomised with seed {seed}");
+    headless.step(generations);
+    headless.export_png(path)?;
+    println!("Exported generation {generations} to {}", path.display());
+    Ok(())
+}
+
+/// Randomises a grid and records `generations` generations of it into an
+/// animated GIF or APNG, depending on the extension of `path`.
+pub fn run_recording(config: AppConfig, path: &Path, generations: usize) -> anyhow::Result<()> {
+    let format = RecordingFormat::from_path(path)?;
+    let mut headless = Headless::new(config)?;
+    let seed = headless
+        .game_data
+        .randomise_grid_state(&headless.config, &headless.queue);
+    println!("Randomised with seed {seed}");
+
+    let mut recording = Recording::new(
+        &headless.device,
+        &headless.config,
+        &headless.game_data,
+        format,
+    )?;
+    recording.capture_frame(
+        &headless.device,
+        &headless.queue,
+        &headless.game_data,
+        &headless.config,
+    )?;
+    for _ in 0..generations {
+        headless.step(1);
+        if recording.wants_generation(headless.game_data.generation) {
+            recording.capture_frame(
+                &headless.device,
+                &headless.queue,
+                &headless.game_data,
+                &headless.config,
+            )?;
+        }
+    }
+
+    std::fs::write(path, recording.encode()?)?;
+    println!(
+        "Recorded {} frames to {}",
+        recording.frame_count(),
+        path.display()
+    );
+    Ok(())
+}
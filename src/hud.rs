@@ -0,0 +1,305 @@
+// The HUD: lines of text drawn over the top left of the grid after the cells,
+// so the desktop build shows what the HTML controls show on the web.
+//
+// Each character is a quad whose fragment shader looks its pixels up in the
+// glyphs from font.rs, which sit in a storage buffer rather than a texture.
+// The population is counted from a copy of the grid read back every so
+// often, without waiting for it.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+#[cfg(target_arch = "wasm32")]
+use web_time::{Duration, Instant};
+
+use std::sync::{Arc, Mutex};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    font::{GLYPH_SIZE, GLYPHS, glyph_index},
+    game_data::GameData,
+    readback::Readback,
+};
+
+/// most characters on screen at once
+const MAX_CHARS: usize = 4096;
+/// pixels between the HUD and the edges of the window
+const MARGIN: f32 = 8.0;
+/// how often the population is counted
+const COUNT_INTERVAL: Duration = Duration::from_millis(250);
+/// how long the generation rate is measured over
+const RATE_INTERVAL: Duration = Duration::from_millis(500);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct HudUniform {
+    screen_size: [f32; 2],
+    scale: f32,
+    glyph_width: f32,
+    glyph_height: f32,
+    _padding: [f32; 3],
+    text_colour: [f32; 4],
+    background_colour: [f32; 4],
+}
+
+/// One character on screen
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Character {
+    /// top left, in pixels from the top left of the screen
+    position: [f32; 2],
+    glyph: u32,
+}
+
+impl Character {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Uint32];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Character>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+pub struct Hud {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    character_buffer: wgpu::Buffer,
+    character_count: u32,
+    /// whether anything is drawn at all
+    pub visible: bool,
+    /// whether the key bindings are listed under the status
+    pub show_help: bool,
+    /// the last population read back, waiting to be picked up
+    counted: Arc<Mutex<Option<Readback<u64>>>>,
+    population: Option<u64>,
+    is_counting: bool,
+    last_count: Instant,
+    /// generation and time the rate is being measured from
+    rate_start: (u64, Instant),
+    rate: f64,
+}
+
+impl Hud {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HUD Uniform Buffer"),
+            size: size_of::<HudUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // a u32 for each row of each glyph
+        let glyph_rows: Vec<u32> = GLYPHS.iter().flatten().map(|&row| row as u32).collect();
+        let glyph_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("HUD Glyph Buffer"),
+            contents: bytemuck::cast_slice(&glyph_rows),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let character_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HUD Character Buffer"),
+            size: (MAX_CHARS * size_of::<Character>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("HUD Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("HUD Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: glyph_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("HUD Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("HUD shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/hud.wgsl").into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("HUD Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[Character::desc()],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        let now = Instant::now();
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+            character_buffer,
+            character_count: 0,
+            visible: true,
+            show_help: false,
+            counted: Arc::new(Mutex::new(None)),
+            population: None,
+            is_counting: false,
+            last_count: now,
+            rate_start: (0, now),
+            rate: 0.0,
+        }
+    }
+
+    /// Lays `lines` out from the top left of a `screen_size` surface, with
+    /// each font pixel `scale` pixels across. Lines are padded to the same
+    /// length so their background makes one box.
+    pub fn set_lines(
+        &mut self,
+        queue: &wgpu::Queue,
+        lines: &[String],
+        screen_size: (u32, u32),
+        scale: f32,
+    ) {
+        let uniform = HudUniform {
+            screen_size: [screen_size.0 as f32, screen_size.1 as f32],
+            scale,
+            glyph_width: GLYPH_SIZE[0] as f32,
+            glyph_height: GLYPH_SIZE[1] as f32,
+            _padding: [0.0; 3],
+            text_colour: [1.0, 1.0, 1.0, 1.0],
+            background_colour: [0.0, 0.0, 0.0, 0.6],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+
+        let width = lines.iter().map(|line| line.chars().count()).max();
+        let (glyph_width, glyph_height) =
+            (GLYPH_SIZE[0] as f32 * scale, GLYPH_SIZE[1] as f32 * scale);
+        let characters: Vec<Character> = lines
+            .iter()
+            .enumerate()
+            .flat_map(|(row, line)| {
+                let padding = width.unwrap_or(0) - line.chars().count();
+                line.chars()
+                    .chain(std::iter::repeat_n(' ', padding))
+                    .enumerate()
+                    .map(move |(col, c)| Character {
+                        position: [
+                            MARGIN + col as f32 * glyph_width,
+                            MARGIN + row as f32 * glyph_height,
+                        ],
+                        glyph: glyph_index(c),
+                    })
+            })
+            .take(MAX_CHARS)
+            .collect();
+        queue.write_buffer(&self.character_buffer, 0, bytemuck::cast_slice(&characters));
+        self.character_count = characters.len() as u32;
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        if !self.visible || self.character_count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.character_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.character_count);
+    }
+
+    /// Live cells on the grid as of the last count, starting another count
+    /// if it's due
+    pub fn population(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        game_data: &GameData,
+    ) -> Option<u64> {
+        // desktop map callbacks only run when the device is polled
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = device.poll(wgpu::PollType::Poll) {
+            log::error!("Unable to poll the device: {e}");
+        }
+        if let Some(counted) = self.counted.lock().unwrap().take() {
+            self.is_counting = false;
+            match counted {
+                Ok(population) => self.population = Some(population),
+                Err(e) => log::error!("Unable to count the population: {e}"),
+            }
+        }
+        if !self.is_counting && self.last_count.elapsed() >= COUNT_INTERVAL {
+            self.is_counting = true;
+            self.last_count = Instant::now();
+            let slot = self.counted.clone();
+            game_data.request_grid_state(device, queue, move |state| {
+                let population =
+                    state.map(|state| state.iter().filter(|&&cell| cell != 0).count() as u64);
+                *slot.lock().unwrap() = Some(population);
+            });
+        }
+        self.population
+    }
+
+    /// Generations a second, measured over the last moment
+    pub fn generation_rate(&mut self, generation: u64) -> f64 {
+        let (start_generation, start) = self.rate_start;
+        let elapsed = start.elapsed();
+        if elapsed >= RATE_INTERVAL || generation < start_generation {
+            self.rate = generation.saturating_sub(start_generation) as f64 / elapsed.as_secs_f64();
+            self.rate_start = (generation, Instant::now());
+        }
+        self.rate
+    }
+}
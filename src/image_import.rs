@@ -0,0 +1,164 @@
+// Turns a PNG or JPEG into a starting grid. The image is scaled to the grid's
+// rows and columns, and every pixel becomes a live or dead cell depending on
+// how dark it is.
+
+use crate::config::{AppConfig, ImageImportConfig, ImageImportMode, SimulationMode};
+use image::imageops::FilterType;
+
+/// Decodes `bytes` and converts the image into a grid state of
+/// `config.rows` x `config.cols` cells
+pub fn image_to_state(bytes: &[u8], config: &AppConfig) -> anyhow::Result<Vec<u32>> {
+    let image = image::load_from_memory(bytes)?;
+    let image = image::imageops::resize(
+        &image.to_rgba8(),
+        config.cols as u32,
+        config.rows as u32,
+        FilterType::Triangle,
+    );
+    // images start at the top but row 0 of the grid is drawn at the bottom
+    let image = image::imageops::flip_vertical(&image);
+
+    // brightness of every cell, with transparent areas counting as white
+    let brightness: Vec<f32> = image
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b, a] = pixel.0.map(|c| c as f32 / 255.0);
+            let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            luma * a + (1.0 - a)
+        })
+        .collect();
+
+    let cells = cells_from_brightness(brightness, config.cols, &config.image_import);
+    Ok(match config.mode {
+        SimulationMode::Discrete => cells,
+        // continuous cells hold the f32 bits of 0 or 1
+        SimulationMode::Continuous => cells
+            .into_iter()
+            .map(|cell| (cell as f32).to_bits())
+            .collect(),
+    })
+}
+
+fn cells_from_brightness(
+    mut brightness: Vec<f32>,
+    cols: usize,
+    settings: &ImageImportConfig,
+) -> Vec<u32> {
+    if settings.invert {
+        brightness.iter_mut().for_each(|b| *b = 1.0 - *b);
+    }
+
+    match settings.mode {
+        ImageImportMode::Threshold => brightness
+            .iter()
+            .map(|&b| (b < settings.threshold) as u32)
+            .collect(),
+        ImageImportMode::Dither => {
+            let rows = brightness.len() / cols;
+            let mut cells = vec![0; brightness.len()];
+            for row in 0..rows {
+                for col in 0..cols {
+                    let idx = row * cols + col;
+                    let alive = brightness[idx] < settings.threshold;
+                    cells[idx] = alive as u32;
+
+                    // push the rounding error onto the neighbours still to be visited
+                    let error = brightness[idx] - if alive { 0.0 } else { 1.0 };
+                    let mut spread = |row: usize, col: Option<usize>, weight: f32| {
+                        if let Some(col) = col.filter(|&c| c < cols)
+                            && row < rows
+                        {
+                            brightness[row * cols + col] += error * weight;
+                        }
+                    };
+                    spread(row, Some(col + 1), 7.0 / 16.0);
+                    spread(row + 1, col.checked_sub(1), 3.0 / 16.0);
+                    spread(row + 1, Some(col), 5.0 / 16.0);
+                    spread(row + 1, Some(col + 1), 1.0 / 16.0);
+                }
+            }
+            cells
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConfigSource, load_config_from, override_line};
+
+    fn settings(mode: ImageImportMode, invert: bool) -> ImageImportConfig {
+        ImageImportConfig {
+            mode,
+            threshold: 0.5,
+            invert,
+        }
+    }
+
+    #[test]
+    fn dark_pixels_are_alive() {
+        let brightness = vec![0.0, 0.49, 0.5, 1.0];
+        let threshold = settings(ImageImportMode::Threshold, false);
+        assert_eq!(
+            cells_from_brightness(brightness.clone(), 2, &threshold),
+            [1, 1, 0, 0]
+        );
+        // inverting flips the brightness, so the threshold itself stays dead
+        let inverted = settings(ImageImportMode::Threshold, true);
+        assert_eq!(
+            cells_from_brightness(brightness, 2, &inverted),
+            [0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn dithering_keeps_the_overall_brightness() {
+        let dither = settings(ImageImportMode::Dither, false);
+        let (rows, cols) = (20, 20);
+        for (grey, expected) in [(0.0, 400), (0.25, 300), (0.5, 200), (1.0, 0)] {
+            let cells = cells_from_brightness(vec![grey; rows * cols], cols, &dither);
+            let alive = cells.iter().sum::<u32>() as i32;
+            assert!((alive - expected).abs() <= 10, "{grey}: {alive}");
+        }
+        // unlike a threshold, which puts a mid grey all on one side
+        let threshold = settings(ImageImportMode::Threshold, false);
+        let cells = cells_from_brightness(vec![0.49; rows * cols], cols, &threshold);
+        assert_eq!(cells.iter().sum::<u32>(), 400);
+    }
+
+    #[test]
+    fn images_are_scaled_and_flipped_onto_the_grid() {
+        // black on top, white underneath, twice the size of the grid
+        let mut image = image::RgbaImage::new(4, 4);
+        for (_, y, pixel) in image.enumerate_pixels_mut() {
+            let c = if y < 2 { 0 } else { 255 };
+            *pixel = image::Rgba([c, c, c, 255]);
+        }
+        let mut png = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let config = |settings: &[&str]| {
+            load_config_from(&ConfigSource {
+                path: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/appconfig.toml").into()),
+                overrides: settings
+                    .iter()
+                    .map(|setting| override_line(setting).unwrap())
+                    .collect(),
+            })
+            .unwrap()
+        };
+        let discrete = config(&["rows=2", "cols=2"]);
+        // row 0 is the bottom of the grid
+        assert_eq!(image_to_state(&png, &discrete).unwrap(), [0, 0, 1, 1]);
+
+        let continuous = config(&["rows=2", "cols=2", "mode=\"continuous\""]);
+        let (dead, alive) = (0.0_f32.to_bits(), 1.0_f32.to_bits());
+        assert_eq!(
+            image_to_state(&png, &continuous).unwrap(),
+            [dead, dead, alive, alive]
+        );
+        assert!(image_to_state(b"not an image", &discrete).is_err());
+    }
+}
@@ -0,0 +1,393 @@
+// Key bindings: the `[keybindings]` table maps each action to a key, so
+// layouts other than QWERTY can put them somewhere sensible.
+//
+// Keys are physical `KeyCode`s named the way winit names them, like `KeyS`,
+// `Digit1` or `ArrowRight`, so a binding stays in the same place whatever the
+// layout. Letters and digits can be written on their own, and modifiers go in
+// front, like `Ctrl+S` or `Shift+R`.
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use serde::Deserialize;
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::config::{SettingChecker, SettingProblem};
+
+/// Something a key can do
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    PlayPause,
+    Step,
+    Reset,
+    Randomise,
+    Census,
+    ExportPng,
+    ToggleRecording,
+    ToggleHashlife,
+    FasterHashlife,
+    SlowerHashlife,
+    SaveSnapshot,
+    LoadSnapshot,
+    SavePattern,
+    SaveMacrocell,
+    ToggleHud,
+    Help,
+}
+
+impl Action {
+    /// Where the action's key is set in the config
+    pub fn setting(self) -> &'static str {
+        match self {
+            Action::Quit => "keybindings.quit",
+            Action::PlayPause => "keybindings.play_pause",
+            Action::Step => "keybindings.step",
+            Action::Reset => "keybindings.reset",
+            Action::Randomise => "keybindings.randomise",
+            Action::Census => "keybindings.census",
+            Action::ExportPng => "keybindings.export_png",
+            Action::ToggleRecording => "keybindings.toggle_recording",
+            Action::ToggleHashlife => "keybindings.toggle_hashlife",
+            Action::FasterHashlife => "keybindings.faster_hashlife",
+            Action::SlowerHashlife => "keybindings.slower_hashlife",
+            Action::SaveSnapshot => "keybindings.save_snapshot",
+            Action::LoadSnapshot => "keybindings.load_snapshot",
+            Action::SavePattern => "keybindings.save_pattern",
+            Action::SaveMacrocell => "keybindings.save_macrocell",
+            Action::ToggleHud => "keybindings.toggle_hud",
+            Action::Help => "keybindings.help",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::PlayPause => "play or pause",
+            Action::Step => "step one generation",
+            Action::Reset => "clear the grid",
+            Action::Randomise => "randomise the grid",
+            Action::Census => "count objects",
+            Action::ExportPng => "save a PNG",
+            Action::ToggleRecording => "start or stop recording",
+            Action::ToggleHashlife => "switch HashLife on or off",
+            Action::FasterHashlife => "double the HashLife step",
+            Action::SlowerHashlife => "halve the HashLife step",
+            Action::SaveSnapshot => "save a snapshot",
+            Action::LoadSnapshot => "load the snapshot",
+            Action::SavePattern => "save an RLE pattern",
+            Action::SaveMacrocell => "save a macrocell pattern",
+            Action::ToggleHud => "show or hide the HUD",
+            Action::Help => "show or hide the key bindings",
+        }
+    }
+}
+
+/// Keys that can be bound, found by their names
+const KEY_CODES: &[KeyCode] = &[
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Escape,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Delete,
+    KeyCode::Insert,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Backslash,
+    KeyCode::Semicolon,
+    KeyCode::Quote,
+    KeyCode::Backquote,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::IntlBackslash,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::NumpadAdd,
+    KeyCode::NumpadSubtract,
+    KeyCode::NumpadMultiply,
+    KeyCode::NumpadDivide,
+    KeyCode::NumpadEnter,
+    KeyCode::NumpadDecimal,
+];
+
+/// modifier names, in the order they're written
+const MODIFIERS: [(&str, ModifiersState); 4] = [
+    ("Ctrl", ModifiersState::CONTROL),
+    ("Alt", ModifiersState::ALT),
+    ("Shift", ModifiersState::SHIFT),
+    ("Super", ModifiersState::SUPER),
+];
+
+/// A key and exactly the modifiers held with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: ModifiersState,
+}
+
+impl FromStr for KeyBinding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key = parts.pop().unwrap_or_default();
+        let mut modifiers = ModifiersState::empty();
+        for part in parts {
+            let (_, modifier) = MODIFIERS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(part))
+                .ok_or_else(|| format!("{part:?} isn't Ctrl, Alt, Shift or Super"))?;
+            modifiers |= *modifier;
+        }
+        // `S` for `KeyS` and `1` for `Digit1`
+        let name = match key.as_bytes() {
+            [c] if c.is_ascii_alphabetic() => format!("Key{}", key.to_ascii_uppercase()),
+            [c] if c.is_ascii_digit() => format!("Digit{key}"),
+            _ => key.to_string(),
+        };
+        let code = KEY_CODES
+            .iter()
+            .find(|code| format!("{code:?}").eq_ignore_ascii_case(&name))
+            .ok_or_else(|| {
+                format!("{key:?} isn't a key, they look like \"S\" or \"ArrowRight\"")
+            })?;
+        Ok(Self {
+            code: *code,
+            modifiers,
+        })
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, modifier) in MODIFIERS {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{name}+")?;
+            }
+        }
+        let name = format!("{:?}", self.code);
+        let short = name
+            .strip_prefix("Key")
+            .or_else(|| name.strip_prefix("Digit"))
+            .filter(|short| short.len() == 1);
+        write!(f, "{}", short.unwrap_or(&name))
+    }
+}
+
+/// The key for each action, from the `[keybindings]` table
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyBindings {
+    bindings: Vec<(KeyBinding, Action)>,
+}
+
+impl KeyBindings {
+    /// Parses the table, leaving out any key that's already taken or
+    /// doesn't parse. An empty string leaves the action without a key.
+    pub fn from_table(table: &BTreeMap<Action, String>, checker: &mut SettingChecker) -> Self {
+        let mut bindings: Vec<(KeyBinding, Action)> = Vec::new();
+        for (&action, key) in table {
+            if key.trim().is_empty() {
+                continue;
+            }
+            let Some(binding) = checker.parsed(action.setting(), key.parse::<KeyBinding>()) else {
+                continue;
+            };
+            match bindings.iter().find(|(taken, _)| *taken == binding) {
+                Some((_, other)) => checker.fail(
+                    action.setting(),
+                    SettingProblem::KeyTaken {
+                        key: binding.to_string(),
+                        by: other.setting(),
+                    },
+                ),
+                None => bindings.push((binding, action)),
+            }
+        }
+        Self { bindings }
+    }
+
+    /// The action for a key pressed with exactly `modifiers` held
+    pub fn action(&self, code: KeyCode, modifiers: ModifiersState) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding.code == code && binding.modifiers == modifiers)
+            .map(|(_, action)| *action)
+    }
+
+    /// The key bound to `action`, if there is one
+    pub fn key(&self, action: Action) -> Option<KeyBinding> {
+        self.bindings
+            .iter()
+            .find(|(_, bound)| *bound == action)
+            .map(|(binding, _)| *binding)
+    }
+
+    /// Each key and what it does, in the order of the `Action`s
+    pub fn iter(&self) -> impl Iterator<Item = &(KeyBinding, Action)> {
+        self.bindings.iter()
+    }
+}
+
+impl fmt::Display for KeyBindings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (binding, action) in self.iter() {
+            writeln!(f, "{:<12} {}", binding.to_string(), action.description())?;
+        }
+        write!(f, "{:<12} paint with that state", "0-9")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(text: &str) -> KeyBinding {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn keys_are_found_by_name() {
+        assert_eq!(binding("S").code, KeyCode::KeyS);
+        assert_eq!(binding("s").code, KeyCode::KeyS);
+        assert_eq!(binding("1").code, KeyCode::Digit1);
+        assert_eq!(binding("ArrowRight").code, KeyCode::ArrowRight);
+        assert_eq!(binding("space").code, KeyCode::Space);
+        assert_eq!(binding("F1").code, KeyCode::F1);
+        assert_eq!(binding("KeyQ").code, KeyCode::KeyQ);
+        assert_eq!(binding("S").modifiers, ModifiersState::empty());
+    }
+
+    #[test]
+    fn modifiers_are_parsed_in_any_order() {
+        let save = binding("shift + ctrl+S");
+        assert_eq!(save.code, KeyCode::KeyS);
+        assert_eq!(
+            save.modifiers,
+            ModifiersState::CONTROL | ModifiersState::SHIFT
+        );
+        assert_eq!(save, binding("Ctrl+Shift+S"));
+    }
+
+    #[test]
+    fn bindings_are_written_back_the_way_they_read() {
+        for text in [
+            "S",
+            "1",
+            "Ctrl+S",
+            "Ctrl+Alt+Shift+Super+F12",
+            "Shift+ArrowRight",
+        ] {
+            assert_eq!(binding(text).to_string(), text);
+        }
+        assert_eq!(binding("shift+ctrl+s").to_string(), "Ctrl+Shift+S");
+    }
+
+    #[test]
+    fn bad_keys_are_rejected() {
+        for text in ["", "Ctrl+", "Hyper+S", "Ctrl+Nothing", "SS", "+S", "é"] {
+            assert!(text.parse::<KeyBinding>().is_err(), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn tables_skip_taken_and_bad_keys() {
+        let table = BTreeMap::from([
+            (Action::Quit, "Q".to_string()),
+            (Action::PlayPause, "Space".to_string()),
+            (Action::Step, "Q".to_string()),
+            (Action::Reset, "Nothing".to_string()),
+            (Action::Randomise, "Shift+R".to_string()),
+            (Action::Census, " ".to_string()),
+        ]);
+        let mut checker = SettingChecker::default();
+        let bindings = KeyBindings::from_table(&table, &mut checker);
+
+        let problems: Vec<&str> = checker.invalid.iter().map(|invalid| invalid.path).collect();
+        assert_eq!(problems, ["keybindings.step", "keybindings.reset"]);
+        assert_eq!(
+            bindings.action(KeyCode::KeyQ, ModifiersState::empty()),
+            Some(Action::Quit)
+        );
+        assert_eq!(
+            bindings.action(KeyCode::KeyR, ModifiersState::SHIFT),
+            Some(Action::Randomise)
+        );
+        // the modifiers have to match exactly
+        assert_eq!(
+            bindings.action(KeyCode::KeyR, ModifiersState::empty()),
+            None
+        );
+        assert_eq!(bindings.action(KeyCode::KeyQ, ModifiersState::SHIFT), None);
+        assert_eq!(bindings.key(Action::Census), None);
+        assert_eq!(bindings.key(Action::Step), None);
+        assert_eq!(bindings.key(Action::PlayPause), Some(binding("Space")));
+    }
+}
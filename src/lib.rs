@@ -1,9 +1,37 @@
+pub mod active;
+pub mod analysis;
 pub mod app;
 pub mod config;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod config_watcher;
+pub mod continuous;
+pub mod export;
+pub mod font;
 pub mod game_data;
 pub mod graphics;
+pub mod hashlife;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
+pub mod hud;
+pub mod image_import;
+pub mod keybindings;
 pub mod paint;
+pub mod readback;
+pub mod recording;
 pub mod render_data;
+pub mod rle;
+pub mod rule;
+pub mod rule_table;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod search;
+pub mod share;
+pub mod snapshot;
+pub mod tiles;
+pub mod turmite;
 pub mod vertex;
 #[cfg(target_arch = "wasm32")]
+pub mod web_api;
+#[cfg(target_arch = "wasm32")]
 pub mod web_controls;
+#[cfg(target_arch = "wasm32")]
+pub mod web_files;
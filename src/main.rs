@@ -1,5 +1,192 @@
-use game_of_life::app;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use game_of_life::{
+    app,
+    config::{AppConfig, ConfigSource, load_config_from, override_line},
+    hashlife::{self, HashLife},
+    headless,
+    rle::Pattern,
+    search,
+    snapshot::Snapshot,
+};
+
+/// Cellular automata on the GPU
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// config file to read instead of appconfig.toml
+    #[arg(short, long, value_name = "FILE", global = true)]
+    config: Option<PathBuf>,
+    /// change any config setting, like `--set rows=200` or
+    /// `--set recording.fps=30`. Can be given more than once
+    #[arg(
+        short = 's',
+        long = "set",
+        value_name = "KEY=VALUE",
+        global = true,
+        value_parser = override_line
+    )]
+    overrides: Vec<String>,
+    /// rule to run, like B36/S23
+    #[arg(long, global = true)]
+    rule: Option<String>,
+    /// size of the grid in cells
+    #[arg(long, value_name = "ROWSxCOLS", global = true, value_parser = parse_size)]
+    grid: Option<(usize, usize)>,
+    /// size of the window in pixels
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    window_size: Option<(usize, usize)>,
+    /// start with the simulation running rather than paused
+    #[arg(long)]
+    running: bool,
+    /// start from an RLE or macrocell pattern, a snapshot or an image,
+    /// depending on the file extension
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        visible_aliases = ["rle", "mc", "load", "image"]
+    )]
+    pattern: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Batch jobs that run without a window
+#[derive(Subcommand)]
+enum Command {
+    /// randomise a grid, run it and count the objects left
+    Census {
+        #[arg(default_value_t = 1000)]
+        generations: usize,
+    },
+    /// run seeded soups on a torus and record the interesting ones
+    Search {
+        #[arg(default_value_t = search::SearchOptions::default().soups)]
+        soups: u64,
+        /// tab separated results
+        #[arg(default_value = "search_results.tsv")]
+        results: PathBuf,
+    },
+    /// randomise a grid, run it and save an image of it
+    Png {
+        #[arg(default_value = "generation.png")]
+        path: PathBuf,
+        #[arg(default_value_t = 0)]
+        generations: usize,
+    },
+    /// randomise a grid and record it into a GIF, or an APNG for .png files
+    Record {
+        #[arg(default_value = "recording.gif")]
+        path: PathBuf,
+        #[arg(long, default_value_t = 500)]
+        gens: usize,
+    },
+    /// jump an RLE or macrocell pattern forward 2^STEP_LOG2 generations
+    Hashlife {
+        pattern: PathBuf,
+        step_log2: u32,
+        /// where to save the result as a macrocell file
+        out: Option<PathBuf>,
+    },
+}
+
+/// Parses sizes like `100x200`
+fn parse_size(size: &str) -> Result<(usize, usize), String> {
+    let parse = |n: &str| n.trim().parse().map_err(|e| format!("{n:?}: {e}"));
+    let (a, b) = size
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected a size like 100x200, not {size:?}"))?;
+    Ok((parse(a)?, parse(b)?))
+}
+
+impl Cli {
+    /// The config file and every setting from the command line
+    fn config_source(&self) -> ConfigSource {
+        let mut overrides = self.overrides.clone();
+        if let Some(rule) = &self.rule {
+            overrides.push(format!("rule = {rule:?}"));
+        }
+        if let Some((rows, cols)) = self.grid {
+            overrides.push(format!("rows = {rows}"));
+            overrides.push(format!("cols = {cols}"));
+        }
+        if let Some((width, height)) = self.window_size {
+            overrides.push(format!("window_size = [{width}, {height}]"));
+        }
+        ConfigSource {
+            path: self.config.clone(),
+            overrides,
+        }
+    }
+
+    /// The config file with every setting from the command line applied
+    fn config(&self) -> anyhow::Result<AppConfig> {
+        let mut config = load_config_from(&self.config_source())?;
+        config.is_paused = !self.running;
+        Ok(config)
+    }
+
+    fn startup(&self) -> anyhow::Result<app::Startup> {
+        let mut startup = app::Startup {
+            config: Some(self.config()?),
+            config_source: Some(self.config_source()),
+            ..Default::default()
+        };
+        if let Some(path) = &self.pattern {
+            load_startup_file(path, &mut startup)
+                .with_context(|| format!("unable to load {}", path.display()))?;
+        }
+        Ok(startup)
+    }
+}
+
+/// Reads whatever `path` is into `startup`
+fn load_startup_file(path: &Path, startup: &mut app::Startup) -> anyhow::Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gols") => startup.snapshot = Some(Snapshot::load(path)?),
+        Some("png" | "jpg" | "jpeg") => startup.image = Some(std::fs::read(path)?),
+        Some("mc") => {
+            startup.hashlife = Some(HashLife::parse_macrocell(&std::fs::read_to_string(path)?)?)
+        }
+        _ => startup.pattern = Some(Pattern::parse(&std::fs::read_to_string(path)?)?),
+    }
+    Ok(())
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    match &cli.command {
+        None => app::run_with(cli.startup()?),
+        Some(Command::Census { generations }) => headless::run_census(cli.config()?, *generations),
+        Some(Command::Search { soups, results }) => {
+            let options = search::SearchOptions {
+                soups: *soups,
+                results_path: results.clone(),
+                ..Default::default()
+            };
+            search::run_search(cli.config()?, &options)
+        }
+        Some(Command::Png { path, generations }) => {
+            headless::run_export(cli.config()?, path, *generations)
+        }
+        Some(Command::Record { path, gens }) => headless::run_recording(cli.config()?, path, *gens),
+        Some(Command::Hashlife {
+            pattern,
+            step_log2,
+            out,
+        }) => hashlife::run_hashlife(pattern, *step_log2, out.as_deref()),
+    }
+}
 
 fn main() {
-    _ = app::run();
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("Error: {e:#}");
+        std::process::exit(1);
+    }
 }
@@ -10,11 +10,43 @@ pub struct MousePainter {
     pub is_pressed: bool,
     pub pos: LogicalPosition<f64>,
     pub paint_buffer_cpu: Vec<u32>,
+    /// whether any cells have been added since the buffer was cleared
+    pub has_paint: bool,
     pub paint_buffer_gpu: wgpu::Buffer,
     pub array_div_factor: (f32, f32),
     pub painter_pipeline: wgpu::ComputePipeline,
     pub painter_buffer_bind_group: wgpu::BindGroup,
     pub finger_id: Option<u64>,
+    /// the cell a right-drag started on, while it's held
+    select_start: Option<(usize, usize)>,
+    /// the rectangle randomised in place of the whole grid
+    pub selection: Option<Selection>,
+}
+
+/// A rectangle of cells, from its bottom left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub x: usize,
+    pub y: usize,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl Selection {
+    /// The rectangle with `a` and `b` at opposite corners
+    pub fn from_corners(a: (usize, usize), b: (usize, usize)) -> Self {
+        Self {
+            x: a.0.min(b.0),
+            y: a.1.min(b.1),
+            rows: a.1.abs_diff(b.1) + 1,
+            cols: a.0.abs_diff(b.0) + 1,
+        }
+    }
+}
+
+/// 0 leaves a cell alone, so painted cells are marked with their state plus one
+fn paint_value(config: &AppConfig) -> u32 {
+    config.paint_state + 1
 }
 
 fn get_window_logical_size(window: &Arc<Window>) -> (f32, f32) {
@@ -112,50 +144,106 @@ impl MousePainter {
             is_pressed: false,
             pos: LogicalPosition { x: 0.0, y: 0.0 },
             paint_buffer_cpu: paint_buffer,
+            has_paint: false,
             paint_buffer_gpu: painter_buffer_gpu,
             array_div_factor,
             painter_pipeline,
             painter_buffer_bind_group,
             finger_id,
+            select_start: None,
+            selection: None,
         }
     }
-    // set the cell array position to 1
+    // mark the cell under the cursor to be set to the paint state
     pub fn add_to_buffer(&mut self, config: &AppConfig) {
+        if let Some((x, y)) = self.cell_under_cursor(config) {
+            self.paint_buffer_cpu[x + config.cols * y] = paint_value(config);
+            self.has_paint = true;
+        }
+    }
+    /// Column and row of the cell under the cursor, if it's over the grid
+    pub fn cell_under_cursor(&self, config: &AppConfig) -> Option<(usize, usize)> {
+        if config.rule.is_hexagonal() {
+            return self.hex_cell_under_cursor(config);
+        }
         // we need to convert the physical coords into the array index for the cell
         let (div_x, div_y) = self.array_div_factor;
         let x = (self.pos.x as f32 / div_x) as usize;
         if x >= config.cols {
-            return;
+            return None;
         }
         // we do this because NDC is from down to up in y.
         // but the window coordinates are top to bottom
         // need to do a checked subtraction to prevent overflow issues
         let y = (config.rows - 1).checked_sub((self.pos.y as f32 / div_y) as usize);
+        if y.is_none() {
+            log::warn!("Invalid Y coordinate calculation");
+        }
+        y.map(|y| (x, y))
+    }
+    /// Hexagons overlap the rows above and below, so find the hexagon whose
+    /// middle is closest instead
+    fn hex_cell_under_cursor(&self, config: &AppConfig) -> Option<(usize, usize)> {
+        let (div_x, div_y) = self.array_div_factor;
+        // position in cells from the bottom left, since NDC is from down to up in y
+        let x = self.pos.x as f32 / div_x;
+        let y = config.rows as f32 - self.pos.y as f32 / div_y;
+
+        let nearest_row = (y - 0.5).round() as i64;
+        let closest = (nearest_row - 1..=nearest_row + 1)
+            .map(|row| {
+                let shift = if row.rem_euclid(2) == 1 { 0.5 } else { 0.0 };
+                let col = (x - 0.5 - shift).round();
+                let (dx, dy) = (x - (col + 0.5 + shift), y - (row as f32 + 0.5));
+                // rows are sqrt(3)/2 hexagon widths apart
+                let distance = dx * dx + 0.75 * dy * dy;
+                (col as i64, row, distance)
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2));
 
-        if let Some(y) = y {
-            // now get the array_pos:
-            let array_pos = x + config.cols * y;
-
-            if array_pos < self.paint_buffer_cpu.len() {
-                self.paint_buffer_cpu[array_pos] = 1;
-            } else {
-                log::warn!(
-                    "Paint position out of bounds: array_pos {} >= buffer_len {}",
-                    array_pos,
-                    self.paint_buffer_cpu.len()
-                );
+        match closest {
+            Some((col, row, _))
+                if (0..config.cols as i64).contains(&col)
+                    && (0..config.rows as i64).contains(&row) =>
+            {
+                Some((col as usize, row as usize))
             }
-        } else {
-            log::warn!("Invalid Y coordinate calculation");
+            _ => None,
         }
     }
+    /// Starts selecting from the cell under the cursor
+    pub fn start_selection(&mut self, config: &AppConfig) {
+        self.select_start = self.cell_under_cursor(config);
+        self.selection = None;
+    }
+    /// Stretches the selection to the cell under the cursor
+    pub fn extend_selection(&mut self, config: &AppConfig) {
+        if let (Some(start), Some(end)) = (self.select_start, self.cell_under_cursor(config)) {
+            self.selection = Some(Selection::from_corners(start, end));
+        }
+    }
+    /// Finishes the selection. Clicking without dragging clears it.
+    pub fn end_selection(&mut self, config: &AppConfig) {
+        self.extend_selection(config);
+        if self
+            .selection
+            .is_some_and(|selection| selection.rows * selection.cols == 1)
+        {
+            self.selection = None;
+        }
+        self.select_start = None;
+    }
+    pub fn is_selecting(&self) -> bool {
+        self.select_start.is_some()
+    }
     pub fn clear_buffer(&mut self) {
         self.paint_buffer_cpu.iter_mut().for_each(|x| *x = 0);
+        self.has_paint = false;
     }
     pub fn calc_array_div_factor(window: &Arc<Window>, config: &AppConfig) -> (f32, f32) {
         let window_size = get_window_logical_size(window);
         (
-            window_size.0 / config.cols as f32,
+            window_size.0 / config.grid_width(),
             window_size.1 / config.rows as f32,
         )
     }
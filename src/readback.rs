@@ -0,0 +1,84 @@
+// Helpers for copying GPU buffers back to the CPU.
+//
+// Mapping a buffer is asynchronous. On desktop we can block on the device
+// until the copy is done, but on the web the callback only fires once control
+// goes back to the browser's event loop, so callers there have to hand over
+// a closure and pick up the data later (usually via the EventLoopProxy).
+// The closure is called either way, so callers waiting on it can tell a
+// failed readback from one that hasn't finished.
+
+/// What a readback's closure is called with
+pub type Readback<T> = Result<T, wgpu::BufferAsyncError>;
+
+/// Copies the whole of `src` into a staging buffer and calls `on_ready`
+/// with its bytes once the staging buffer has been mapped, or with the error
+/// if it couldn't be.
+pub fn read_buffer(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    src: &wgpu::Buffer,
+    on_ready: impl FnOnce(Readback<Vec<u8>>) + wgpu::WasmNotSend + 'static,
+) {
+    let size = src.size();
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Staging Buffer"),
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback Encoder"),
+    });
+    encoder.copy_buffer_to_buffer(src, 0, &staging_buffer, 0, size);
+    queue.submit(std::iter::once(encoder.finish()));
+
+    map_buffer(&staging_buffer, on_ready);
+}
+
+/// Maps a `MAP_READ` buffer and calls `on_ready` with a copy of its bytes.
+pub fn map_buffer(
+    staging_buffer: &wgpu::Buffer,
+    on_ready: impl FnOnce(Readback<Vec<u8>>) + wgpu::WasmNotSend + 'static,
+) {
+    // the callback needs its own handle to the buffer to read the mapped range
+    let mapped_buffer = staging_buffer.clone();
+    staging_buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            on_ready(result.map(|()| {
+                let data = mapped_buffer.slice(..).get_mapped_range().to_vec();
+                mapped_buffer.unmap();
+                data
+            }))
+        });
+}
+
+/// Blocking version of [`read_buffer`] for the desktop and headless runs.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_buffer_blocking(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    src: &wgpu::Buffer,
+) -> anyhow::Result<Vec<u8>> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    read_buffer(device, queue, src, move |data| {
+        let _ = sender.send(data);
+    });
+    device.poll(wgpu::PollType::Wait)?;
+    Ok(receiver.recv()??)
+}
+
+/// Blocking version of [`map_buffer`].
+#[cfg(not(target_arch = "wasm32"))]
+pub fn map_buffer_blocking(
+    device: &wgpu::Device,
+    staging_buffer: &wgpu::Buffer,
+) -> anyhow::Result<Vec<u8>> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    map_buffer(staging_buffer, move |data| {
+        let _ = sender.send(data);
+    });
+    device.poll(wgpu::PollType::Wait)?;
+    Ok(receiver.recv()??)
+}
@@ -0,0 +1,327 @@
+// Animated recordings of a run.
+//
+// Every `stride`th generation is rendered offscreen, reduced to the recording
+// palette and kept in memory. When the recording stops the frames are encoded
+// as an animated GIF or APNG.
+
+use crate::{
+    config::{AppConfig, RecordingConfig, RecordingFormat, SimulationMode},
+    continuous,
+    export::FrameRenderer,
+    game_data::GameData,
+    readback::Readback,
+    turmite::Turmites,
+};
+use anyhow::{bail, ensure};
+use std::{borrow::Cow, collections::HashMap, path::Path};
+
+impl RecordingFormat {
+    /// Picks the format from a file extension, `.gif` or `.png`/`.apng`
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("gif") => Ok(RecordingFormat::Gif),
+            Some("png" | "apng") => Ok(RecordingFormat::Apng),
+            _ => bail!("recordings must be .gif or .png files"),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "gif",
+            RecordingFormat::Apng => "png",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "image/gif",
+            RecordingFormat::Apng => "image/apng",
+        }
+    }
+}
+
+/// most colours GIF and APNG palettes can hold
+const MAX_PALETTE_LEN: usize = 256;
+
+pub struct Recording {
+    pub format: RecordingFormat,
+    settings: RecordingConfig,
+    renderer: FrameRenderer,
+    palette: Vec<[u8; 3]>,
+    /// palette indices of each frame's pixels
+    frames: Vec<Vec<u8>>,
+    /// frames rendered but not read back yet
+    pending_frames: usize,
+    stopped: bool,
+}
+
+impl Recording {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &AppConfig,
+        game_data: &GameData,
+        format: RecordingFormat,
+    ) -> anyhow::Result<Self> {
+        let settings = config.recording.clone();
+        ensure!(settings.stride > 0, "recording stride must be at least 1");
+        ensure!(settings.fps > 0, "recording fps must be at least 1");
+
+        let palette = match &settings.palette {
+            // the config has checked every channel fits
+            Some(palette) => palette
+                .iter()
+                .map(|colour| colour.map(|c| c as u8))
+                .collect(),
+            None => default_palette(
+                config,
+                Turmites::is_active(config) && game_data.turmites.count() > 0,
+            ),
+        };
+        ensure!(
+            (1..=MAX_PALETTE_LEN).contains(&palette.len()),
+            "recording palette must have between 1 and {MAX_PALETTE_LEN} colours"
+        );
+
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        let size = |cells: usize| (cells as u32 * settings.scale).min(max_dimension);
+        let (width, height) = (size(config.cols), size(config.rows));
+        if format == RecordingFormat::Gif {
+            ensure!(
+                width <= u16::MAX as u32 && height <= u16::MAX as u32,
+                "GIF frames can be at most {} pixels a side",
+                u16::MAX
+            );
+        }
+        let renderer = FrameRenderer::new(device, config, (width, height))?;
+
+        Ok(Self {
+            format,
+            settings,
+            renderer,
+            palette,
+            frames: Vec::new(),
+            pending_frames: 0,
+            stopped: false,
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether `generation` falls on the recording stride
+    pub fn wants_generation(&self, generation: u64) -> bool {
+        !self.stopped && generation.is_multiple_of(self.settings.stride)
+    }
+
+    /// Renders the current generation and calls `on_ready` with its pixels,
+    /// which should then be handed to [`Recording::add_frame`], or
+    /// [`Recording::drop_frame`] if they couldn't be read back
+    pub fn request_frame(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        game_data: &GameData,
+        config: &AppConfig,
+        on_ready: impl FnOnce(Readback<Vec<u8>>) + wgpu::WasmNotSend + 'static,
+    ) {
+        self.pending_frames += 1;
+        self.renderer
+            .render(device, queue, game_data, config, on_ready);
+    }
+
+    /// Renders the current generation and adds it straight away
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn capture_frame(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        game_data: &GameData,
+        config: &AppConfig,
+    ) -> anyhow::Result<()> {
+        let pixels = self
+            .renderer
+            .render_blocking(device, queue, game_data, config)?;
+        self.frames.push(self.palette_indices(&pixels));
+        Ok(())
+    }
+
+    pub fn add_frame(&mut self, pixels: &[u8]) {
+        self.pending_frames = self.pending_frames.saturating_sub(1);
+        self.frames.push(self.palette_indices(pixels));
+    }
+
+    /// Gives up on a requested frame that couldn't be read back
+    pub fn drop_frame(&mut self) {
+        self.pending_frames = self.pending_frames.saturating_sub(1);
+    }
+
+    /// Stops taking new frames. Frames already requested still get added.
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+
+    /// Stopped and every requested frame has arrived
+    pub fn is_finished(&self) -> bool {
+        self.stopped && self.pending_frames == 0
+    }
+
+    /// Maps every RGBA pixel to the nearest palette colour
+    fn palette_indices(&self, pixels: &[u8]) -> Vec<u8> {
+        // frames only have a handful of distinct colours
+        let mut nearest: HashMap<[u8; 3], u8> = HashMap::new();
+        pixels
+            .chunks_exact(4)
+            .map(|pixel| {
+                let colour = [pixel[0], pixel[1], pixel[2]];
+                *nearest
+                    .entry(colour)
+                    .or_insert_with(|| nearest_colour(&self.palette, colour))
+            })
+            .collect()
+    }
+
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        ensure!(!self.frames.is_empty(), "nothing was recorded");
+        let palette: Vec<u8> = self.palette.iter().flatten().copied().collect();
+        let mut bytes = Vec::new();
+        match self.format {
+            RecordingFormat::Gif => {
+                let (width, height) = (self.renderer.width as u16, self.renderer.height as u16);
+                let mut encoder = gif::Encoder::new(&mut bytes, width, height, &palette)?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+                // GIF delays are in hundredths of a second
+                let delay = (100 / self.settings.fps).max(1) as u16;
+                for frame in &self.frames {
+                    encoder.write_frame(&gif::Frame {
+                        width,
+                        height,
+                        delay,
+                        buffer: Cow::Borrowed(frame),
+                        ..Default::default()
+                    })?;
+                }
+                encoder.into_inner()?;
+            }
+            RecordingFormat::Apng => {
+                let mut encoder =
+                    png::Encoder::new(&mut bytes, self.renderer.width, self.renderer.height);
+                encoder.set_color(png::ColorType::Indexed);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_palette(palette);
+                encoder.set_animated(self.frames.len() as u32, 0)?;
+                encoder.set_frame_delay(1, self.settings.fps.min(u16::MAX as u32) as u16)?;
+                let mut writer = encoder.write_header()?;
+                for frame in &self.frames {
+                    writer.write_image_data(frame)?;
+                }
+                writer.finish()?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// File name for a recording that ended at `generation`
+    pub fn file_name(&self, generation: u64) -> String {
+        format!("recording_{generation:06}.{}", self.format.extension())
+    }
+}
+
+/// The background, the colour of every live or decaying state and the
+/// turmites if `with_turmites`, or steps along the colour ramp in continuous
+/// mode. Rules with the most states leave no room for the turmites, which get
+/// the nearest state's colour instead.
+fn default_palette(config: &AppConfig, with_turmites: bool) -> Vec<[u8; 3]> {
+    let background = config.background_color;
+    let background = [background.r, background.g, background.b];
+    let colours = match config.mode {
+        SimulationMode::Discrete => {
+            let mut colours = config.cell_colours()[1..].to_vec();
+            if with_turmites {
+                colours.push(config.turmites.colour.map(|c| c as f32 / 255.0));
+            }
+            colours
+        }
+        SimulationMode::Continuous => {
+            let ramp = config.cell_colours();
+            (1..256)
+                .map(|step| continuous::ramp_colour(&ramp, step as f32 / 255.0))
+                .collect()
+        }
+    };
+    let states = colours
+        .iter()
+        .map(|&[r, g, b, _]| [r as f64, g as f64, b as f64]);
+    // the colours are linear but frames are read back as sRGB
+    std::iter::once(background)
+        .chain(states)
+        .map(|colour| colour.map(|c| (linear_to_srgb(c) * 255.0).round() as u8))
+        .take(MAX_PALETTE_LEN)
+        .collect()
+}
+
+fn nearest_colour(palette: &[[u8; 3]], colour: [u8; 3]) -> u8 {
+    let distance = |entry: &[u8; 3]| -> u32 {
+        entry
+            .iter()
+            .zip(colour)
+            .map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32)
+            .sum()
+    };
+    (0..palette.len())
+        .min_by_key(|&i| distance(&palette[i]))
+        .unwrap_or(0) as u8
+}
+
+fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConfigSource, load_config_from, override_line};
+
+    /// The built in config with `settings` like `rows=20` over it
+    fn config(settings: &[&str]) -> AppConfig {
+        load_config_from(&ConfigSource {
+            path: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/appconfig.toml").into()),
+            overrides: settings
+                .iter()
+                .map(|setting| override_line(setting).unwrap())
+                .collect(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn turmites_only_get_a_colour_when_there_are_some() {
+        let config = config(&["rule=\"B3/S23/C4\"", "turmites.colour=[255, 255, 255, 255]"]);
+        let without = default_palette(&config, false);
+        assert_eq!(without.len(), 4);
+        let with = default_palette(&config, true);
+        assert_eq!(with.len(), 5);
+        assert_eq!(with[..4], without[..]);
+        assert_eq!(with[4], [255, 255, 255]);
+    }
+
+    #[test]
+    fn palettes_fit_the_most_states() {
+        let generations = config(&["rule=\"B3/S23/C256\""]);
+        assert_eq!(default_palette(&generations, true).len(), MAX_PALETTE_LEN);
+        let continuous = config(&["mode=\"continuous\""]);
+        assert_eq!(default_palette(&continuous, false).len(), MAX_PALETTE_LEN);
+    }
+
+    #[test]
+    fn pixels_take_the_nearest_colour() {
+        let palette = [[0, 0, 0], [255, 0, 0], [255, 255, 255]];
+        assert_eq!(nearest_colour(&palette, [10, 5, 0]), 0);
+        assert_eq!(nearest_colour(&palette, [200, 40, 30]), 1);
+        assert_eq!(nearest_colour(&palette, [250, 240, 250]), 2);
+    }
+}
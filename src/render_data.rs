@@ -1,23 +1,28 @@
 use wgpu::{BufferUsages, util::DeviceExt};
 
 use crate::{
-    config::AppConfig,
+    config::{AppConfig, SimulationMode},
+    game_data::GameData,
     graphics::RenderUniform,
-    vertex::{CELL_VERTICES, INDICES, Instance, Vertex, get_instances},
+    turmite::Turmites,
+    vertex::{Instance, Vertex, cell_shape, get_instances},
 };
 
 pub struct RenderData {
     pub pipeline: wgpu::RenderPipeline,
+    /// draws turmites over the cells
+    pub turmite_pipeline: wgpu::RenderPipeline,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub instance_buffer: wgpu::Buffer,
+    pub index_count: u32,
     pub render_uniform_bind_group: wgpu::BindGroup,
 }
 
 impl RenderData {
     pub fn new(
         device: &wgpu::Device,
-        surface_config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
         game_state_render_bind_group_layout: &wgpu::BindGroupLayout,
         config: &AppConfig,
     ) -> anyhow::Result<Self> {
@@ -26,20 +31,28 @@ impl RenderData {
         // then make a bind group
         // then a render pipeline
 
-        // this makes a vertex buffer with the 4 vertices i need for my cell!
+        // this makes a vertex buffer with the vertices i need for my cell!
+        let hexagonal = config.rule.is_hexagonal();
+        let (vertices, indices) = cell_shape(hexagonal);
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
             usage: wgpu::BufferUsages::VERTEX,
-            contents: bytemuck::cast_slice(CELL_VERTICES),
+            contents: bytemuck::cast_slice(vertices),
         });
 
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Cell Index buffer"),
-            contents: bytemuck::cast_slice(INDICES),
+            contents: bytemuck::cast_slice(indices),
             usage: BufferUsages::INDEX,
         });
 
-        let instances = get_instances(config.rows, config.cols, config.gap_size, config.cell_size);
+        let instances = get_instances(
+            config.rows,
+            config.cols,
+            config.gap_size,
+            config.cell_size,
+            hexagonal,
+        );
 
         let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Instance Buffer"),
@@ -47,35 +60,60 @@ impl RenderData {
             usage: wgpu::BufferUsages::VERTEX,
         });
 
-        let uniform = RenderUniform::new(config.cell_size);
+        let uniform = RenderUniform::new(config);
         let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Render Uniform Buffer"),
             contents: bytemuck::bytes_of(&uniform),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        // one colour per cell state
+        let palette_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Palette Buffer"),
+            contents: bytemuck::cast_slice(&config.cell_colours()),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
         let render_uniform_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("Render Uniform Bind Group Layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                ],
             });
 
         let render_uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Render Uniform Bind Group"),
             layout: &render_uniform_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: palette_buffer.as_entire_binding(),
+                },
+            ],
         });
 
         let render_pipeline_layout =
@@ -93,6 +131,26 @@ impl RenderData {
             source: wgpu::ShaderSource::Wgsl(include_str!("shaders/render.wgsl").into()),
         });
 
+        let primitive = wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        };
+        let multisample = wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        };
+        let targets = [Some(wgpu::ColorTargetState {
+            format,
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            write_mask: wgpu::ColorWrites::ALL,
+        })];
+
         let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Render Pipeline"),
             layout: Some(&render_pipeline_layout),
@@ -102,30 +160,49 @@ impl RenderData {
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
                 buffers: &[Vertex::desc(), Instance::desc()],
             },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
+            primitive,
             depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
+            multisample,
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some(match config.mode {
+                    SimulationMode::Discrete => "fs_main",
+                    SimulationMode::Continuous => "fs_continuous",
+                }),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &targets,
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        let turmite_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Turmite Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    &render_uniform_bind_group_layout,
+                    game_state_render_bind_group_layout,
+                    &Turmites::get_render_bind_group_layout(device),
+                ],
+                push_constant_ranges: &[],
+            });
+        let turmite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Turmite Render Pipeline"),
+            layout: Some(&turmite_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_turmite"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[Vertex::desc()],
             },
+            primitive,
+            depth_stencil: None,
+            multisample,
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
-                entry_point: Some("fs_main"),
+                entry_point: Some("fs_turmite"),
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
+                targets: &targets,
             }),
             multiview: None,
             cache: None,
@@ -133,10 +210,40 @@ impl RenderData {
 
         Ok(Self {
             pipeline,
+            turmite_pipeline,
             vertex_buffer,
             index_buffer,
             instance_buffer,
+            index_count: indices.len() as u32,
             render_uniform_bind_group,
         })
     }
+
+    /// Draws every cell of the grid into `render_pass`, then the turmites
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        game_data: &GameData,
+        config: &AppConfig,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.render_uniform_bind_group, &[]);
+        render_pass.set_bind_group(1, game_data.get_current_render_bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        // here is where we will choose which instances to draw in different
+        // draw calls.
+        // Our current state_buffer in the game_state_bind group will control which
+        // cells are shown as alive.
+        render_pass.draw_indexed(0..self.index_count, 0, 0..config.num_elements() as u32);
+
+        let turmites = &game_data.turmites;
+        if Turmites::is_active(config) && turmites.count() > 0 {
+            render_pass.set_pipeline(&self.turmite_pipeline);
+            render_pass.set_bind_group(2, &turmites.render_bind_group, &[]);
+            render_pass.draw_indexed(0..self.index_count, 0, 0..turmites.count());
+        }
+    }
 }
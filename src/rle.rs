@@ -0,0 +1,325 @@
+// Run length encoded patterns, the format Life patterns are usually shared in:
+//
+//   #C optional comment lines
+//   x = 3, y = 3, rule = B3/S23
+//   bo$2bo$3o!
+//
+// Two state patterns use `b` for dead and `o` for live cells. Patterns with
+// more states use `.` for dead cells and `A`..`X` for states 1 to 24, with a
+// prefix from `p` to `y` for the states above that, like Golly does.
+
+use crate::rule::Rule;
+use anyhow::{Context, bail, ensure};
+use std::fmt::Write;
+
+/// Where patterns are saved on desktop
+pub const DEFAULT_PATTERN_PATH: &str = "pattern.rle";
+/// RLE lines are kept shorter than this
+const MAX_LINE_LEN: usize = 70;
+/// states per prefix letter in multi-state RLE
+const STATES_PER_PREFIX: u32 = 24;
+/// most cells a pattern can cover, so a bad header can't use up the memory
+const MAX_CELLS: usize = 1 << 26;
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub width: usize,
+    pub height: usize,
+    /// the rule from the header, if it had one
+    pub rule: Option<Rule>,
+    /// cell states row by row, starting at the top
+    pub cells: Vec<u32>,
+}
+
+impl Pattern {
+    /// The smallest rectangle of `state` holding all of its non-dead cells
+    pub fn from_grid(state: &[u32], rows: usize, cols: usize, rule: &Rule) -> Self {
+        let occupied = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .filter(|&(row, col)| state[row * cols + col] != 0);
+        let bounds = occupied.fold(None, |bounds, (row, col)| match bounds {
+            None => Some((row, row, col, col)),
+            Some((min_row, max_row, min_col, max_col)) => Some((
+                min_row.min(row),
+                max_row.max(row),
+                min_col.min(col),
+                max_col.max(col),
+            )),
+        });
+
+        let Some((min_row, max_row, min_col, max_col)) = bounds else {
+            return Self {
+                width: 0,
+                height: 0,
+                rule: Some(rule.clone()),
+                cells: Vec::new(),
+            };
+        };
+        // grid row 0 is drawn at the bottom, so the top row of the pattern is the last one
+        let cells = (min_row..=max_row)
+            .rev()
+            .flat_map(|row| (min_col..=max_col).map(move |col| state[row * cols + col]))
+            .collect();
+        Self {
+            width: max_col - min_col + 1,
+            height: max_row - min_row + 1,
+            rule: Some(rule.clone()),
+            cells,
+        }
+    }
+
+    /// Places the pattern in the middle of an empty `rows` x `cols` grid
+    pub fn to_grid(&self, rows: usize, cols: usize) -> anyhow::Result<Vec<u32>> {
+        ensure!(
+            self.width <= cols && self.height <= rows,
+            "a {}x{} pattern doesn't fit on a {cols}x{rows} grid",
+            self.width,
+            self.height
+        );
+        // an empty pattern on a one row grid has no top row at all
+        let top = ((rows + self.height) / 2).saturating_sub(1);
+        let left = (cols - self.width) / 2;
+        let mut grid = vec![0; rows * cols];
+        for (y, row) in self.cells.chunks(self.width.max(1)).enumerate() {
+            let grid_row = top - y;
+            grid[grid_row * cols + left..][..row.len()].copy_from_slice(row);
+        }
+        Ok(grid)
+    }
+
+    pub fn parse(rle: &str) -> anyhow::Result<Self> {
+        let mut lines = rle
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        let header = lines.next().context("pattern is empty")?;
+
+        let (mut width, mut height, mut rule) = (None::<usize>, None::<usize>, None);
+        // the rule comes last and Larger than Life rules have commas of their own
+        let (sizes, rule_field) = match header.find("rule") {
+            Some(start) => (&header[..start], Some(&header[start..])),
+            None => (header, None),
+        };
+        let fields = sizes.split(',').chain(rule_field);
+        for field in fields.filter(|field| !field.trim().is_empty()) {
+            let (key, value) = field
+                .split_once('=')
+                .with_context(|| format!("unexpected {field:?} in RLE header"))?;
+            let value = value.trim();
+            match key.trim() {
+                "x" => width = Some(value.parse().context("bad pattern width")?),
+                "y" => height = Some(value.parse().context("bad pattern height")?),
+                "rule" => rule = Some(value.parse::<Rule>()?),
+                _ => {}
+            }
+        }
+        let (Some(width), Some(height)) = (width, height) else {
+            bail!("RLE header {header:?} needs an x and a y");
+        };
+
+        let num_cells = width
+            .checked_mul(height)
+            .filter(|&num_cells| num_cells <= MAX_CELLS)
+            .with_context(|| format!("a {width}x{height} pattern is too big to load"))?;
+        let mut cells = vec![0; num_cells];
+        let (mut x, mut y) = (0usize, 0usize);
+        let mut run = String::new();
+        let mut prefix = None;
+        'body: for c in lines.flat_map(str::chars) {
+            let count = || run.parse::<usize>().unwrap_or(1);
+            let state = match c {
+                '0'..='9' => {
+                    run.push(c);
+                    continue;
+                }
+                'p'..='y' => {
+                    prefix = Some(c as u32 - 'p' as u32 + 1);
+                    continue;
+                }
+                '$' => {
+                    y = y.saturating_add(count());
+                    x = 0;
+                    run.clear();
+                    continue;
+                }
+                '!' => break 'body,
+                c if c.is_whitespace() => continue,
+                'b' | '.' => 0,
+                'o' => 1,
+                'A'..='X' => {
+                    prefix.take().unwrap_or(0) * STATES_PER_PREFIX + (c as u32 - 'A' as u32) + 1
+                }
+                _ => bail!("unexpected {c:?} in RLE"),
+            };
+            let count = count();
+            ensure!(
+                y < height && x.checked_add(count).is_some_and(|end| end <= width),
+                "pattern runs outside its {width}x{height} bounds"
+            );
+            cells[y * width + x..][..count].fill(state);
+            x += count;
+            run.clear();
+        }
+
+        if let Some(rule) = &rule {
+            ensure!(
+                cells.iter().all(|&state| state < rule.states),
+                "pattern has cells in states that rule {rule} doesn't have"
+            );
+        }
+        Ok(Self {
+            width,
+            height,
+            rule,
+            cells,
+        })
+    }
+
+    pub fn to_rle(&self) -> String {
+        let multi_state = self.rule.as_ref().is_some_and(Rule::is_generations)
+            || self.cells.iter().any(|&state| state > 1);
+
+        let mut header = format!("x = {}, y = {}", self.width, self.height);
+        if let Some(rule) = &self.rule {
+            let _ = write!(header, ", rule = {rule}");
+        }
+
+        // runs of (count, token), with rows ending in `$` and trailing dead cells dropped
+        let mut runs: Vec<(usize, String)> = Vec::new();
+        let mut push = |count: usize, token: String| match runs.last_mut() {
+            Some((last_count, last_token)) if *last_token == token => *last_count += count,
+            _ => runs.push((count, token)),
+        };
+        for (y, row) in self.cells.chunks(self.width.max(1)).enumerate() {
+            if y > 0 {
+                push(1, "$".to_string());
+            }
+            let len = row
+                .iter()
+                .rposition(|&state| state != 0)
+                .map_or(0, |i| i + 1);
+            for &state in &row[..len] {
+                push(1, state_token(state, multi_state));
+            }
+        }
+        // blank rows at the end don't need writing either
+        while runs.last().is_some_and(|(_, token)| token == "$") {
+            runs.pop();
+        }
+
+        let mut rle = header + "\n";
+        let mut line_len = 0;
+        let items = runs
+            .into_iter()
+            .map(|(count, token)| match count {
+                1 => token,
+                count => format!("{count}{token}"),
+            })
+            .chain(std::iter::once("!".to_string()));
+        for item in items {
+            if line_len + item.len() > MAX_LINE_LEN {
+                rle.push('\n');
+                line_len = 0;
+            }
+            line_len += item.len();
+            rle.push_str(&item);
+        }
+        rle.push('\n');
+        rle
+    }
+}
+
+fn state_token(state: u32, multi_state: bool) -> String {
+    match (state, multi_state) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (state, true) => {
+            let prefix = (state - 1) / STATES_PER_PREFIX;
+            let letter = char::from(b'A' + ((state - 1) % STATES_PER_PREFIX) as u8);
+            match prefix {
+                0 => letter.to_string(),
+                prefix => format!("{}{letter}", char::from(b'p' + prefix as u8 - 1)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+
+    #[test]
+    fn parses_a_glider() {
+        let glider = Pattern::parse(&format!("#C a glider\n{GLIDER}")).unwrap();
+        assert_eq!((glider.width, glider.height), (3, 3));
+        assert_eq!(glider.rule, Some("B3/S23".parse().unwrap()));
+        assert_eq!(glider.cells, [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        assert_eq!(Pattern::parse(GLIDER).unwrap().to_rle(), GLIDER);
+    }
+
+    #[test]
+    fn multi_state_cells_round_trip() {
+        let pattern = Pattern {
+            width: 4,
+            height: 1,
+            rule: Some("B2/S/C30".parse().unwrap()),
+            cells: vec![1, 0, 24, 29],
+        };
+        let rle = pattern.to_rle();
+        assert!(rle.contains("A.XpE!"), "{rle}");
+        assert_eq!(Pattern::parse(&rle).unwrap().cells, pattern.cells);
+    }
+
+    #[test]
+    fn grids_round_trip() {
+        let rule: Rule = "B3/S23".parse().unwrap();
+        let grid = Pattern::parse(GLIDER).unwrap().to_grid(5, 6).unwrap();
+        let pattern = Pattern::from_grid(&grid, 5, 6, &rule);
+        assert_eq!(pattern.to_rle(), GLIDER);
+        assert_eq!(pattern.to_grid(5, 6).unwrap(), grid);
+    }
+
+    #[test]
+    fn empty_patterns_fit_any_grid() {
+        let empty = Pattern::from_grid(&[0; 4], 1, 4, &"B3/S23".parse().unwrap());
+        assert_eq!((empty.width, empty.height), (0, 0));
+        assert_eq!(empty.to_grid(1, 4).unwrap(), [0; 4]);
+    }
+
+    #[test]
+    fn oversized_headers_are_rejected() {
+        for rle in [
+            "x = 100000, y = 100000\n!",
+            "x = 18446744073709551615, y = 2\n!",
+        ] {
+            assert!(Pattern::parse(rle).is_err(), "{rle}");
+        }
+    }
+
+    #[test]
+    fn corrupt_patterns_are_rejected() {
+        for rle in [
+            "",
+            "bo$2bo$3o!",
+            "x = 3, y = 3\n4o!",
+            "x = 3, y = 3\n3$o!",
+            "x = 3, y = 3\n18446744073709551615o!",
+            "x = 3, y = 3\nz!",
+            "x = 3, y = 1, rule = B3/S23\nA.B!",
+        ] {
+            assert!(Pattern::parse(rle).is_err(), "{rle:?}");
+        }
+    }
+
+    #[test]
+    fn big_patterns_dont_fit_small_grids() {
+        assert!(Pattern::parse(GLIDER).unwrap().to_grid(2, 10).is_err());
+    }
+}
@@ -0,0 +1,773 @@
+// Cellular automaton rules.
+//
+// Rules are written in the usual B/S notation, e.g. `B3/S23` for Conway's
+// Life. Generations rules add a state count, e.g. `B2/S/C3` for Brian's Brain:
+// live cells that don't survive pass through the decay states 2..C-1 before
+// they're dead, and only fully live cells count as neighbours. The older
+// S/B/C form (`345/2/4`) is read as well.
+//
+// Counts can be narrowed down with Hensel's letters for how the neighbours are
+// arranged, e.g. `B2-a/S12`, for isotropic non-totalistic rules. MAP rules
+// give the next state of every 3x3 neighbourhood as 512 bits of base64. Both
+// run from a 512 entry lookup table, see `Rule::lookup_table`. B/S rules
+// ending in `H`, e.g. `B2/S34H`, run on a hexagonal grid with 6 neighbours.
+//
+// Larger than Life rules use the `R5,C0,M1,S34..58,B34..45,NM` notation:
+// range, states (0 or 2 for two states), whether the middle cell counts
+// itself, the survival and birth ranges and the neighbourhood shape.
+//
+// One dimensional rules run along the bottom row, and each generation pushes
+// the ones before it up a row. `W30` is one of Wolfram's 256 elementary
+// rules, and `T20,R2` a totalistic rule where bit n of the code is set if a
+// cell with n live cells within `R` of it, counting itself, is alive.
+//
+// Anything else is the name of a rule table, see rule_table.rs. On desktop a
+// path to a `.rule` or `.table` file loads that file.
+
+use anyhow::{Context, anyhow, bail, ensure};
+use base64::{Engine, prelude::BASE64_STANDARD_NO_PAD};
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+use crate::rule_table::RuleTable;
+
+/// the most states a cell can have, so states fit in a byte in snapshots
+pub const MAX_STATES: u32 = 256;
+/// the largest Larger than Life range the compute shader has room for
+pub const MAX_RANGE: u32 = 16;
+/// entries in the lookup table, one for each 3x3 neighbourhood
+pub const TABLE_SIZE: usize = 512;
+/// the largest range of one dimensional totalistic rules, so their 2 R + 1
+/// cells index the lookup table
+pub const MAX_RANGE_1D: u32 = 4;
+
+/// Hensel's letters for each neighbour count, in Golly's order
+const HENSEL_LETTERS: [&str; 9] = [
+    "",
+    "ce",
+    "ceaikn",
+    "ceaiknjqry",
+    "ceaiknjqrytwz",
+    "ceaiknjqry",
+    "ceaikn",
+    "ce",
+    "",
+];
+/// One neighbourhood for each letter of counts up to 4, as table index bits.
+/// Counts above 4 use the complement of `8 - count`'s.
+const HENSEL_NEIGHBOURHOODS: [&[u16]; 5] = [
+    &[0],
+    &[1, 2],
+    &[5, 10, 3, 40, 33, 68],
+    &[69, 42, 11, 7, 98, 13, 14, 70, 41, 97],
+    &[325, 170, 15, 45, 99, 71, 106, 102, 43, 101, 105, 78, 108],
+];
+/// table index bit of the cell itself
+const MIDDLE: u16 = 1 << 4;
+/// table index bits of the 8 neighbours
+const NEIGHBOURS: u16 = 0x1ff & !MIDDLE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub kind: RuleKind,
+    /// number of cell states, 2 for Life-like rules
+    pub states: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleKind {
+    /// B/S rules on the 8 nearest neighbours
+    Totalistic {
+        /// bit n is set if a dead cell with n live neighbours comes alive
+        birth: u32,
+        /// bit n is set if a live cell with n live neighbours survives
+        survival: u32,
+    },
+    /// B/S rules on the 6 neighbours of a hexagonal grid
+    Hexagonal {
+        birth: u32,
+        survival: u32,
+    },
+    /// B/S rules where counts are split up by Hensel's letters
+    Isotropic {
+        /// for each neighbour count, bit n is set if its nth letter gives birth
+        birth: [u16; 9],
+        /// for each neighbour count, bit n is set if its nth letter survives
+        survival: [u16; 9],
+    },
+    /// the next state of every neighbourhood, in table index order
+    Map {
+        table: [u8; TABLE_SIZE / 8],
+    },
+    LargerThanLife(LargerThanLife),
+    /// Wolfram's one dimensional rules on a cell and its left and right
+    /// neighbours, bit n of `number` is set if pattern n is alive next
+    Elementary {
+        number: u8,
+    },
+    /// one dimensional rules on the number of live cells within `range`
+    Totalistic1d {
+        /// bit n is set if a cell with n live cells around it, itself
+        /// included, is alive next generation
+        code: u32,
+        range: u32,
+    },
+    /// any number of states, from a Golly rule table
+    Table(RuleTable),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LargerThanLife {
+    pub range: u32,
+    pub shape: NeighbourhoodShape,
+    /// whether a live cell counts itself as a neighbour
+    pub include_middle: bool,
+    pub birth: RangeInclusive<u32>,
+    pub survival: RangeInclusive<u32>,
+}
+
+/// Which cells within the range are neighbours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighbourhoodShape {
+    /// the whole square
+    Moore,
+    /// a diamond, cells within `range` steps without moving diagonally
+    VonNeumann,
+    /// cells with dx² + dy² <= range² + range
+    Circular,
+    /// a hexagon, as used by hexagonal rules on a square grid
+    Hexagonal,
+}
+
+impl NeighbourhoodShape {
+    fn letter(&self) -> char {
+        match self {
+            NeighbourhoodShape::Moore => 'M',
+            NeighbourhoodShape::VonNeumann => 'N',
+            NeighbourhoodShape::Circular => 'C',
+            NeighbourhoodShape::Hexagonal => 'H',
+        }
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        // Conway's Life, B3/S23
+        Self {
+            kind: RuleKind::Totalistic {
+                birth: 1 << 3,
+                survival: (1 << 2) | (1 << 3),
+            },
+            states: 2,
+        }
+    }
+}
+
+impl Rule {
+    /// Whether cells have decay states between alive and dead
+    pub fn is_generations(&self) -> bool {
+        self.states > 2
+    }
+
+    /// Whether the grid is made of hexagons
+    pub fn is_hexagonal(&self) -> bool {
+        matches!(self.kind, RuleKind::Hexagonal { .. })
+    }
+
+    /// Whether the rule runs along the bottom row, with history above it
+    pub fn is_one_dimensional(&self) -> bool {
+        matches!(
+            self.kind,
+            RuleKind::Elementary { .. } | RuleKind::Totalistic1d { .. }
+        )
+    }
+
+    /// Whether dead cells with no live neighbours are born (B0), so an empty
+    /// plane doesn't stay empty
+    pub fn births_from_nothing(&self) -> bool {
+        match &self.kind {
+            RuleKind::LargerThanLife(ltl) => ltl.birth.contains(&0),
+            RuleKind::Table(table) => table.next[0] != 0,
+            _ => self.lookup_table().is_some_and(|table| table[0] != 0),
+        }
+    }
+
+    /// Whether the rule can be parsed back from its name in another
+    /// session. Rule tables opened from a file only keep their name.
+    pub fn can_be_found(&self) -> bool {
+        match &self.kind {
+            RuleKind::Table(table) => table.can_be_found(),
+            _ => true,
+        }
+    }
+
+    /// Linear RGBA colour of every state. Dead cells are transparent so the
+    /// background shows through, live cells are white and decaying cells
+    /// fade from orange to dark red. Rule tables use their own colours where
+    /// they have them.
+    pub fn colours(&self) -> Vec<[f32; 4]> {
+        let mut colours = self.default_colours();
+        if let RuleKind::Table(table) = &self.kind {
+            for (colour, own) in colours.iter_mut().zip(&table.colours).skip(1) {
+                if let Some([r, g, b]) = own {
+                    *colour = [*r, *g, *b, 255].map(|c| c as f32 / 255.0);
+                }
+            }
+        }
+        colours
+    }
+
+    fn default_colours(&self) -> Vec<[f32; 4]> {
+        let mut colours = vec![[0.0; 4], [1.0; 4]];
+        let decay_states = self.states.saturating_sub(2);
+        colours.extend((0..decay_states).map(|i| {
+            let t = if decay_states > 1 {
+                i as f32 / (decay_states - 1) as f32
+            } else {
+                0.0
+            };
+            let mix = |from: f32, to: f32| from + (to - from) * t;
+            [mix(1.0, 0.25), mix(0.45, 0.0), mix(0.05, 0.05), 1.0]
+        }));
+        colours
+    }
+
+    /// Whether a cell in state 0 or 1 is alive next generation, for every
+    /// neighbourhood of live cells. Indexed by the bits NW N NE W C E SW S SE,
+    /// most significant first, the order MAP rules use. Hexagonal rules only
+    /// use the slots either side of N and S. One dimensional rules are
+    /// indexed by the 2 R + 1 cells of their row, leftmost first. Larger than
+    /// Life rules don't have a table, and rule tables give their own, see
+    /// `RuleTable::packed`.
+    pub fn lookup_table(&self) -> Option<Vec<u32>> {
+        let alive = |index: u16| -> bool {
+            let neighbourhood = index & NEIGHBOURS;
+            let count = neighbourhood.count_ones();
+            let is_alive = index & MIDDLE != 0;
+            match &self.kind {
+                RuleKind::Totalistic { birth, survival }
+                | RuleKind::Hexagonal { birth, survival } => {
+                    let counts = if is_alive { survival } else { birth };
+                    counts & (1 << count) != 0
+                }
+                RuleKind::Isotropic { birth, survival } => {
+                    let letters = if is_alive { survival } else { birth };
+                    letters[count as usize] & (1 << hensel_letter(neighbourhood)) != 0
+                }
+                RuleKind::Map { table } => table[index as usize / 8] & (0x80 >> (index % 8)) != 0,
+                RuleKind::Elementary { number } => index < 8 && number & (1 << index) != 0,
+                RuleKind::Totalistic1d { code, range } => {
+                    index < 1 << (2 * range + 1) && code & (1 << index.count_ones()) != 0
+                }
+                RuleKind::LargerThanLife(_) | RuleKind::Table(_) => false,
+            }
+        };
+        match &self.kind {
+            RuleKind::LargerThanLife(_) => None,
+            RuleKind::Table(table) => Some(table.packed()),
+            _ => Some(
+                (0..TABLE_SIZE as u16)
+                    .map(|index| alive(index) as u32)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// table index bit of the cell `x` right and `y` down from the middle
+fn cell_bit(x: i32, y: i32) -> u16 {
+    1 << (8 - (y + 1) * 3 - (x + 1))
+}
+
+/// The neighbourhood rotated and reflected each of the 8 ways a square can be
+fn symmetries(neighbourhood: u16) -> impl Iterator<Item = u16> {
+    (0..8).map(move |symmetry| {
+        let mut transformed = 0;
+        for y in -1..=1 {
+            for x in -1..=1 {
+                if neighbourhood & cell_bit(x, y) == 0 {
+                    continue;
+                }
+                let (mut x, mut y) = if symmetry >= 4 { (-x, y) } else { (x, y) };
+                for _ in 0..symmetry % 4 {
+                    (x, y) = (-y, x);
+                }
+                transformed |= cell_bit(x, y);
+            }
+        }
+        transformed
+    })
+}
+
+/// Position of the neighbourhood's Hensel letter in `HENSEL_LETTERS`
+fn hensel_letter(neighbourhood: u16) -> usize {
+    let count = neighbourhood.count_ones() as usize;
+    let representative = |letter: usize| match count {
+        0..=4 => HENSEL_NEIGHBOURHOODS[count][letter],
+        _ => !HENSEL_NEIGHBOURHOODS[8 - count][letter] & NEIGHBOURS,
+    };
+    (0..HENSEL_LETTERS[count].len())
+        .position(|letter| symmetries(representative(letter)).any(|n| n == neighbourhood))
+        .unwrap_or(0)
+}
+
+/// Mask with a bit for every letter of `count`, or one bit if it has none
+fn all_letters(count: usize) -> u16 {
+    (1 << HENSEL_LETTERS[count].len().max(1)) - 1
+}
+
+/// Parses neighbour counts like `23` or `2-a3ce` into a mask of letters for
+/// each count
+fn parse_counts(counts: &str) -> anyhow::Result<[u16; 9]> {
+    let mut letters = [0; 9];
+    let mut chars = counts.chars().peekable();
+    while let Some(c) = chars.next() {
+        let count = match c.to_digit(10) {
+            Some(n) if n <= 8 => n as usize,
+            _ => bail!("{c:?} is not a neighbour count between 0 and 8"),
+        };
+        let exclude = chars.next_if_eq(&'-').is_some();
+        let mut mask = 0;
+        while let Some(letter) = chars.next_if(char::is_ascii_lowercase) {
+            let Some(i) = HENSEL_LETTERS[count].find(letter) else {
+                bail!("{count}{letter} is not a neighbourhood in Hensel notation");
+            };
+            mask |= 1 << i;
+        }
+        letters[count] |= match (exclude, mask) {
+            (true, mask) => all_letters(count) & !mask,
+            (false, 0) => all_letters(count),
+            (false, mask) => mask,
+        };
+    }
+    Ok(letters)
+}
+
+/// The simplest kind of rule with these letters for each count
+fn outer_totalistic(birth: [u16; 9], survival: [u16; 9]) -> RuleKind {
+    let counts = |letters: [u16; 9]| -> Option<u32> {
+        (0..9).try_fold(0, |mask, count| match letters[count] {
+            0 => Some(mask),
+            all if all == all_letters(count) => Some(mask | (1 << count)),
+            _ => None,
+        })
+    };
+    match (counts(birth), counts(survival)) {
+        (Some(birth), Some(survival)) => RuleKind::Totalistic { birth, survival },
+        _ => RuleKind::Isotropic { birth, survival },
+    }
+}
+
+fn parse_states(states: &str) -> anyhow::Result<u32> {
+    let states: u32 = states
+        .parse()
+        .map_err(|_| anyhow::anyhow!("{states:?} is not a number of states"))?;
+    ensure!(
+        (2..=MAX_STATES).contains(&states),
+        "rules must have between 2 and {MAX_STATES} states"
+    );
+    Ok(states)
+}
+
+fn parse_number(number: &str, what: &str) -> anyhow::Result<u32> {
+    number
+        .parse()
+        .with_context(|| format!("{number:?} is not a valid {what}"))
+}
+
+/// Parses a Larger than Life count range like `34..58`
+fn parse_count_range(range: &str) -> anyhow::Result<RangeInclusive<u32>> {
+    let (min, max) = range.split_once("..").unwrap_or((range, range));
+    let (min, max) = (parse_number(min, "count")?, parse_number(max, "count")?);
+    ensure!(min <= max, "count range {range:?} is empty");
+    Ok(min..=max)
+}
+
+fn parse_totalistic(rule: &str) -> anyhow::Result<Rule> {
+    let parts: Vec<&str> = rule.split('/').collect();
+    let starts_with_letter = |part: &str| part.starts_with(|c: char| c.is_ascii_alphabetic());
+
+    if parts.iter().any(|part| starts_with_letter(part)) {
+        // B3/S23 or B2/S/C3, in any order
+        let (mut birth, mut survival, mut states) = (None, None, 2);
+        for part in parts {
+            let mut chars = part.chars();
+            let prefix = chars.next().map(|c| c.to_ascii_uppercase());
+            let rest = chars.as_str();
+            match prefix {
+                Some('B') => birth = Some(parse_counts(rest)?),
+                Some('S') => survival = Some(parse_counts(rest)?),
+                Some('C' | 'G') => states = parse_states(rest)?,
+                _ => bail!("unexpected {part:?} in rule {rule:?}"),
+            }
+        }
+        match (birth, survival) {
+            (Some(birth), Some(survival)) => Ok(Rule {
+                kind: outer_totalistic(birth, survival),
+                states,
+            }),
+            _ => bail!("rule {rule:?} needs both a B and an S part"),
+        }
+    } else {
+        // S/B or S/B/C
+        let (survival, birth, states) = match parts.as_slice() {
+            [survival, birth] => (survival, birth, 2),
+            [survival, birth, states] => (survival, birth, parse_states(states)?),
+            _ => bail!("unrecognised rule {rule:?}"),
+        };
+        Ok(Rule {
+            kind: outer_totalistic(parse_counts(birth)?, parse_counts(survival)?),
+            states,
+        })
+    }
+}
+
+fn parse_hexagonal(rule: &str) -> anyhow::Result<Rule> {
+    let Rule { kind, states } = parse_totalistic(rule)?;
+    let RuleKind::Totalistic { birth, survival } = kind else {
+        bail!("Hensel letters aren't supported on hexagonal grids");
+    };
+    ensure!(
+        (birth | survival) < 1 << 7,
+        "hexagonal cells have at most 6 neighbours"
+    );
+    Ok(Rule {
+        kind: RuleKind::Hexagonal { birth, survival },
+        states,
+    })
+}
+
+fn parse_map(rule: &str) -> anyhow::Result<Rule> {
+    let (map, states) = match rule.split_once('/') {
+        Some((map, states)) => (map, parse_states(states.trim_start_matches(['C', 'G']))?),
+        None => (rule, 2),
+    };
+    let bad_map = || anyhow!("MAP rules need {TABLE_SIZE} bits of base64");
+    let bytes = BASE64_STANDARD_NO_PAD
+        .decode(map.trim_end_matches('='))
+        .map_err(|_| bad_map())?;
+    Ok(Rule {
+        kind: RuleKind::Map {
+            table: bytes.try_into().map_err(|_| bad_map())?,
+        },
+        states,
+    })
+}
+
+fn parse_elementary(number: &str) -> anyhow::Result<Rule> {
+    let number = number
+        .parse()
+        .map_err(|_| anyhow!("elementary rules are numbered 0 to 255, not {number:?}"))?;
+    Ok(Rule {
+        kind: RuleKind::Elementary { number },
+        states: 2,
+    })
+}
+
+/// Parses one dimensional totalistic rules like `T20,R2`
+fn parse_totalistic_1d(rule: &str) -> anyhow::Result<Rule> {
+    let (code, range) = rule.split_once(',').unwrap_or((rule, "R1"));
+    let code = parse_number(&code[1..], "totalistic code")?;
+    let range = range
+        .strip_prefix(['R', 'r'])
+        .with_context(|| format!("unexpected {range:?} in rule {rule:?}"))?;
+    let range = parse_number(range, "range")?;
+    ensure!(
+        (1..=MAX_RANGE_1D).contains(&range),
+        "one dimensional ranges must be between 1 and {MAX_RANGE_1D}"
+    );
+    // sums go from 0 to 2 R + 1
+    ensure!(
+        code < 1 << (2 * range + 2),
+        "a range {range} code must be below {}",
+        1 << (2 * range + 2)
+    );
+    Ok(Rule {
+        kind: RuleKind::Totalistic1d { code, range },
+        states: 2,
+    })
+}
+
+fn parse_larger_than_life(rule: &str) -> anyhow::Result<Rule> {
+    let (mut range, mut states, mut include_middle) = (None, 2, false);
+    let (mut birth, mut survival, mut shape) = (None, None, NeighbourhoodShape::Moore);
+    for part in rule.split(',') {
+        let (prefix, rest) = part.split_at_checked(1).unwrap_or((part, ""));
+        match prefix.to_ascii_uppercase().as_str() {
+            "R" => range = Some(parse_number(rest, "range")?),
+            // C0 and C1 both mean two states
+            "C" => states = parse_number(rest, "number of states")?.max(2),
+            "M" => include_middle = parse_number(rest, "middle flag")? == 1,
+            "S" => survival = Some(parse_count_range(rest)?),
+            "B" => birth = Some(parse_count_range(rest)?),
+            "N" => {
+                shape = match rest.to_ascii_uppercase().as_str() {
+                    "M" => NeighbourhoodShape::Moore,
+                    "N" => NeighbourhoodShape::VonNeumann,
+                    "C" => NeighbourhoodShape::Circular,
+                    "H" => NeighbourhoodShape::Hexagonal,
+                    _ => bail!("unknown neighbourhood {part:?}"),
+                }
+            }
+            _ => bail!("unexpected {part:?} in rule {rule:?}"),
+        }
+    }
+
+    let range = range.context("Larger than Life rules need a range")?;
+    ensure!(
+        (1..=MAX_RANGE).contains(&range),
+        "ranges must be between 1 and {MAX_RANGE}"
+    );
+    ensure!(
+        states <= MAX_STATES,
+        "rules must have between 2 and {MAX_STATES} states"
+    );
+    let (Some(birth), Some(survival)) = (birth, survival) else {
+        bail!("rule {rule:?} needs both a B and an S range");
+    };
+    Ok(Rule {
+        kind: RuleKind::LargerThanLife(LargerThanLife {
+            range,
+            shape,
+            include_middle,
+            birth,
+            survival,
+        }),
+        states,
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_rule_file(path: &str) -> anyhow::Result<Rule> {
+    Ok(RuleTable::load_file(path.as_ref())?.into())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_rule_file(path: &str) -> anyhow::Result<Rule> {
+    bail!("{path} can't be read on the web, open the rule file instead")
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(rule: &str) -> anyhow::Result<Self> {
+        let rule = rule.trim();
+        let is_numbered = |prefix: [char; 2]| {
+            rule.starts_with(prefix) && rule[1..].starts_with(|c: char| c.is_ascii_digit())
+        };
+        // B/S rules always have a slash, names never do
+        let is_name = !rule.contains('/') && rule.starts_with(|c: char| c.is_ascii_alphabetic());
+        if let Some(map) = rule.strip_prefix("MAP") {
+            parse_map(map)
+        } else if is_numbered(['R', 'r']) {
+            parse_larger_than_life(rule)
+        } else if is_numbered(['W', 'w']) {
+            parse_elementary(&rule[1..])
+        } else if is_numbered(['T', 't']) {
+            parse_totalistic_1d(rule)
+        } else if rule.ends_with(".rule") || rule.ends_with(".table") {
+            load_rule_file(rule)
+        } else if is_name {
+            Ok(RuleTable::find(rule)?.into())
+        } else if let Some(rule) = rule.strip_suffix(['H', 'h']) {
+            parse_hexagonal(rule)
+        } else {
+            parse_totalistic(rule)
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            RuleKind::Totalistic { birth, survival } | RuleKind::Hexagonal { birth, survival } => {
+                let counts = |mask: u32| -> String {
+                    (0..=8)
+                        .filter(|n| mask & (1 << n) != 0)
+                        .map(|n| char::from(b'0' + n as u8))
+                        .collect()
+                };
+                write!(f, "B{}/S{}", counts(*birth), counts(*survival))?;
+            }
+            RuleKind::Isotropic { birth, survival } => {
+                write!(f, "B{}/S{}", hensel(birth), hensel(survival))?;
+            }
+            RuleKind::Map { table } => write!(f, "MAP{}", BASE64_STANDARD_NO_PAD.encode(table))?,
+            RuleKind::LargerThanLife(ltl) => write!(
+                f,
+                "R{},C{},M{},S{}..{},B{}..{},N{}",
+                ltl.range,
+                if self.is_generations() {
+                    self.states
+                } else {
+                    0
+                },
+                ltl.include_middle as u32,
+                ltl.survival.start(),
+                ltl.survival.end(),
+                ltl.birth.start(),
+                ltl.birth.end(),
+                ltl.shape.letter()
+            )?,
+            RuleKind::Elementary { number } => write!(f, "W{number}")?,
+            RuleKind::Totalistic1d { code, range } => write!(f, "T{code},R{range}")?,
+            RuleKind::Table(table) => write!(f, "{}", table.name)?,
+        }
+        let has_own_states = matches!(self.kind, RuleKind::LargerThanLife(_) | RuleKind::Table(_));
+        if self.is_generations() && !has_own_states {
+            write!(f, "/C{}", self.states)?;
+        }
+        if self.is_hexagonal() {
+            write!(f, "H")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<RuleTable> for Rule {
+    fn from(table: RuleTable) -> Self {
+        Self {
+            states: table.states,
+            kind: RuleKind::Table(table),
+        }
+    }
+}
+
+/// Writes counts with their letters, or the letters they leave out if that's shorter
+fn hensel(letters: &[u16; 9]) -> String {
+    let mut text = String::new();
+    for (count, &mask) in letters.iter().enumerate() {
+        if mask == 0 {
+            continue;
+        }
+        text.push(char::from(b'0' + count as u8));
+        if mask == all_letters(count) {
+            continue;
+        }
+        let spell = |mask: u16| -> String {
+            HENSEL_LETTERS[count]
+                .chars()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, letter)| letter)
+                .collect()
+        };
+        let (included, excluded) = (spell(mask), spell(all_letters(count) & !mask));
+        if excluded.len() < included.len() {
+            text.push('-');
+            text.push_str(&excluded);
+        } else {
+            text.push_str(&included);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(text: &str) -> String {
+        text.parse::<Rule>().unwrap().to_string()
+    }
+
+    #[test]
+    fn life_like_rules_round_trip() {
+        assert_eq!(round_trip("B3/S23"), "B3/S23");
+        assert_eq!(round_trip("b36/s23"), "B36/S23");
+        assert_eq!(round_trip("B/S"), "B/S");
+        assert_eq!(round_trip("B2/S34H"), "B2/S34H");
+    }
+
+    #[test]
+    fn life_is_totalistic() {
+        let rule: Rule = "B3/S23".parse().unwrap();
+        assert_eq!(
+            rule.kind,
+            RuleKind::Totalistic {
+                birth: 1 << 3,
+                survival: 1 << 2 | 1 << 3,
+            }
+        );
+        assert_eq!(rule.states, 2);
+    }
+
+    #[test]
+    fn generations_rules_round_trip() {
+        let brain: Rule = "B2/S/C3".parse().unwrap();
+        assert_eq!(brain.states, 3);
+        assert!(brain.is_generations());
+        assert_eq!(brain.to_string(), "B2/S/C3");
+        // the older S/B/C form
+        assert_eq!(round_trip("345/2/4"), "B2/S345/C4");
+    }
+
+    #[test]
+    fn other_families_round_trip() {
+        assert_eq!(
+            round_trip("R5,C0,M1,S34..58,B34..45,NM"),
+            "R5,C0,M1,S34..58,B34..45,NM"
+        );
+        assert_eq!(round_trip("W110"), "W110");
+        assert_eq!(round_trip("T20,R2"), "T20,R2");
+    }
+
+    /// Moves the cells of a lookup table index with `map`, which takes a
+    /// row and column of the 3x3 neighbourhood to where they end up
+    fn transform(index: usize, map: impl Fn(usize, usize) -> (usize, usize)) -> usize {
+        (0..9)
+            .filter(|bit| index & (1 << (8 - bit)) != 0)
+            .map(|bit| {
+                let (row, col) = map(bit / 3, bit % 3);
+                1 << (8 - (3 * row + col))
+            })
+            .sum()
+    }
+
+    #[test]
+    fn isotropic_rules_round_trip() {
+        assert_eq!(round_trip("B2-a/S12"), "B2-a/S12");
+        assert_eq!(round_trip("B2ce3/S23k"), "B2ce3/S23k");
+        // every letter of a count is the same as the count on its own
+        assert_eq!(round_trip("B2cekain3/S23"), "B23/S23");
+        // 1 neighbour can only be in a corner or an edge
+        assert!("B1a/S".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn isotropic_rules_dont_care_how_the_grid_is_turned() {
+        let table = "B2-a3j/S12k4ei"
+            .parse::<Rule>()
+            .unwrap()
+            .lookup_table()
+            .unwrap();
+        for index in 0..TABLE_SIZE {
+            let turned = transform(index, |row, col| (col, 2 - row));
+            let mirrored = transform(index, |row, col| (row, 2 - col));
+            assert_eq!(table[index], table[turned], "{index:09b}");
+            assert_eq!(table[index], table[mirrored], "{index:09b}");
+        }
+    }
+
+    #[test]
+    fn map_rules_match_the_rule_they_spell_out() {
+        let life: Rule = "B3/S23".parse().unwrap();
+        let table = life.lookup_table().unwrap();
+        let bytes: Vec<u8> = table
+            .chunks(8)
+            .map(|bits| bits.iter().fold(0, |byte, &bit| byte << 1 | bit as u8))
+            .collect();
+        let map: Rule = format!("MAP{}", BASE64_STANDARD_NO_PAD.encode(&bytes))
+            .parse()
+            .unwrap();
+        assert_eq!(map.lookup_table().unwrap(), table);
+        assert_eq!(round_trip(&map.to_string()), map.to_string());
+        assert!("MAPAAAA".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn bad_rules_are_rejected() {
+        for rule in ["B9/S23", "B3/S23/C1", "B3/S2x3", "W256"] {
+            assert!(rule.parse::<Rule>().is_err(), "{rule} parsed");
+        }
+    }
+}
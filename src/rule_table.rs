@@ -0,0 +1,727 @@
+// Multi-state rules from Golly's `.rule` and `.table` files, like WireWorld
+// and Langton's loops.
+//
+// A `.rule` file has an `@RULE` name, an `@TABLE` of transitions and
+// optionally the `@COLORS` of each state. A `.table` file is just the table.
+// Each transition lists the cell and its neighbours, then the cell's next
+// state:
+//
+//   n_states:4
+//   neighborhood:Moore
+//   symmetries:rotate8
+//   var a={0,1,2,3}
+//   # C,N,NE,E,SE,S,SW,W,NW,C'
+//   1,a,a,a,a,a,a,a,a,2
+//
+// Variables stand for any of their states, and a variable used more than once
+// in a transition takes the same state each time. The first transition that
+// matches wins, and cells no transition matches stay as they are. The table
+// is compiled into the next state of every possible neighbourhood, which the
+// compute shader looks up directly.
+
+use anyhow::{Context, bail, ensure};
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+use crate::rule::MAX_STATES;
+
+/// the most neighbourhoods a compiled table can have, so it fits in a
+/// reasonably sized GPU buffer
+pub const MAX_TABLE_ENTRIES: usize = 1 << 24;
+/// where rules are looked up by name, like Golly's Rules folder
+#[cfg(not(target_arch = "wasm32"))]
+pub const RULES_DIR: &str = "rules";
+
+/// rules that are always available by name
+const BUILT_IN: [&str; 1] = [include_str!("rules/WireWorld.rule")];
+
+/// rules loaded from files so far, so patterns and snapshots naming them load
+static LOADED: Mutex<Vec<RuleTable>> = Mutex::new(Vec::new());
+
+/// Which cells are neighbours, in the order transitions list them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableNeighbourhood {
+    /// N, NE, E, SE, S, SW, W, NW
+    Moore,
+    /// N, E, S, W
+    VonNeumann,
+}
+
+impl TableNeighbourhood {
+    pub fn neighbours(&self) -> usize {
+        match self {
+            TableNeighbourhood::Moore => 8,
+            TableNeighbourhood::VonNeumann => 4,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct RuleTable {
+    pub name: String,
+    pub neighbourhood: TableNeighbourhood,
+    pub states: u32,
+    /// next state of every neighbourhood. The cell is the least significant
+    /// digit of the index in base `states`, then each neighbour in order.
+    pub next: Vec<u8>,
+    /// sRGB colour of each state, if the file gave one
+    pub colours: Vec<Option<[u8; 3]>>,
+}
+
+// the compiled table is far too long to print with the rest of the config
+impl fmt::Debug for RuleTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RuleTable")
+            .field("name", &self.name)
+            .field("neighbourhood", &self.neighbourhood)
+            .field("states", &self.states)
+            .field("colours", &self.colours)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A state, or the position of a variable in the transition's list of them
+#[derive(Clone, Copy)]
+enum Term {
+    State(u8),
+    Variable(usize),
+}
+
+struct Transition {
+    /// the cell then its neighbours
+    inputs: Vec<Term>,
+    output: Term,
+    /// the states each variable can take
+    variables: Vec<Vec<u8>>,
+}
+
+impl RuleTable {
+    /// Parses a `.rule` file, or a `.table` file called `name`
+    pub fn parse(text: &str, name: &str) -> anyhow::Result<Self> {
+        let is_rule_file = text.lines().any(|line| line.trim_start().starts_with('@'));
+        let mut name = name.to_string();
+        let (mut table, mut colours) = (Vec::new(), Vec::new());
+        let mut section = if is_rule_file { "" } else { "@TABLE" };
+        let mut has_tree = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if let Some(header) = line.strip_prefix('@') {
+                let (keyword, rest) = header
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((header, ""));
+                section = match keyword {
+                    "RULE" => {
+                        name = rest.trim().to_string();
+                        "@RULE"
+                    }
+                    "TABLE" => "@TABLE",
+                    "COLORS" => "@COLORS",
+                    "TREE" => {
+                        has_tree = true;
+                        ""
+                    }
+                    _ => "",
+                };
+            } else if !line.is_empty() {
+                match section {
+                    "@TABLE" => table.push(line),
+                    "@COLORS" => colours.push(line),
+                    _ => (),
+                }
+            }
+        }
+        ensure!(
+            !table.is_empty() || !has_tree,
+            "rule {name:?} is a tree, only @TABLE rules are supported"
+        );
+        ensure!(!table.is_empty(), "rule {name:?} has no @TABLE");
+        ensure!(!name.is_empty(), "rule has no @RULE name");
+
+        let mut rule = compile(&name, &table)?;
+        rule.colours = parse_colours(&colours, rule.states)?;
+        Ok(rule)
+    }
+
+    /// Parses a rule file and remembers it, so later rules of the same name
+    /// find it
+    pub fn load(text: &str, name: &str) -> anyhow::Result<Self> {
+        let rule = Self::parse(text, name)?;
+        if let Ok(mut loaded) = LOADED.lock() {
+            loaded.retain(|other| other.name != rule.name);
+            loaded.push(rule.clone());
+        }
+        Ok(rule)
+    }
+
+    /// Reads a `.rule` or `.table` file
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_file(path: &std::path::Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("unable to read rule file {}", path.display()))?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        Self::load(&text, &name)
+    }
+
+    /// The rule called `name`: one loaded already, a built-in one, or on
+    /// desktop one in `RULES_DIR`
+    pub fn find(name: &str) -> anyhow::Result<Self> {
+        if let Ok(loaded) = LOADED.lock()
+            && let Some(rule) = loaded.iter().find(|rule| rule.name == name)
+        {
+            return Ok(rule.clone());
+        }
+        // compiling is slow, so only compile the one with the right name
+        if let Some(text) = BUILT_IN.iter().find(|text| is_named(text, name)) {
+            return Self::parse(text, name);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = rules_dir_file(name) {
+            return Self::load_file(&path);
+        }
+        bail!("unknown rule {name:?}, open its .rule file first")
+    }
+
+    /// Whether `find` gets this rule back by its name in a session that
+    /// hasn't opened its file
+    pub fn can_be_found(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        if rules_dir_file(&self.name).is_some() {
+            return true;
+        }
+        BUILT_IN.iter().any(|text| is_named(text, &self.name))
+    }
+
+    /// The compiled table with four states to each word, for the GPU
+    pub fn packed(&self) -> Vec<u32> {
+        self.next
+            .chunks(4)
+            .map(|chunk| {
+                let mut bytes = [0; 4];
+                bytes[..chunk.len()].copy_from_slice(chunk);
+                u32::from_le_bytes(bytes)
+            })
+            .collect()
+    }
+}
+
+/// Whether the rule file `text` starts `@RULE name`
+fn is_named(text: &str, name: &str) -> bool {
+    text.lines()
+        .any(|line| line.strip_prefix("@RULE").map(str::trim) == Some(name))
+}
+
+/// The `.rule` or `.table` file for `name` in `RULES_DIR`, if there is one
+#[cfg(not(target_arch = "wasm32"))]
+fn rules_dir_file(name: &str) -> Option<std::path::PathBuf> {
+    ["rule", "table"]
+        .iter()
+        .map(|extension| std::path::Path::new(RULES_DIR).join(format!("{name}.{extension}")))
+        .find(|path| path.exists())
+}
+
+fn compile(name: &str, lines: &[&str]) -> anyhow::Result<RuleTable> {
+    let mut states = None;
+    let mut neighbourhood = TableNeighbourhood::Moore;
+    let mut symmetries = "none".to_string();
+    let mut variables: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut transitions = Vec::new();
+
+    for line in lines {
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            match key.trim() {
+                "n_states" => {
+                    let n: u32 = value
+                        .parse()
+                        .with_context(|| format!("{value:?} is not a number of states"))?;
+                    ensure!(
+                        (2..=MAX_STATES).contains(&n),
+                        "rules must have between 2 and {MAX_STATES} states"
+                    );
+                    states = Some(n);
+                }
+                "neighborhood" => {
+                    neighbourhood = match value {
+                        "Moore" => TableNeighbourhood::Moore,
+                        "vonNeumann" => TableNeighbourhood::VonNeumann,
+                        _ => bail!("{value} neighbourhoods aren't supported"),
+                    }
+                }
+                "symmetries" => symmetries = value.to_string(),
+                _ => bail!("unexpected {line:?} in rule {name:?}"),
+            }
+            continue;
+        }
+        let states = states.context("n_states must come before the transitions")?;
+        if let Some(variable) = line.strip_prefix("var ") {
+            let (variable, values) = variable
+                .split_once('=')
+                .with_context(|| format!("{line:?} should look like var a={{0,1}}"))?;
+            let values = values.trim().trim_start_matches('{').trim_end_matches('}');
+            let mut set = Vec::new();
+            for value in values.split(',').map(str::trim) {
+                match variables.get(value) {
+                    Some(other) => set.extend_from_slice(other),
+                    None => set.push(parse_state(value, states)?),
+                }
+            }
+            variables.insert(variable.trim().to_string(), set);
+        } else {
+            transitions.push(parse_transition(line, states, neighbourhood, &variables)?);
+        }
+    }
+
+    let states = states.context("the table needs n_states")?;
+    let arrangements = arrangements(neighbourhood, &symmetries)?;
+    let n = neighbourhood.neighbours();
+    let entries = (states as usize)
+        .checked_pow(n as u32 + 1)
+        .filter(|&entries| entries <= MAX_TABLE_ENTRIES)
+        .with_context(|| {
+            format!("{states} states are too many for a compiled {neighbourhood:?} table")
+        })?;
+
+    // cells no transition matches stay as they are
+    let mut next: Vec<u8> = (0..entries).map(|i| (i % states as usize) as u8).collect();
+    let mut matched = vec![false; entries];
+    let mut cells = vec![0; n + 1];
+    for transition in &transitions {
+        let mut choice = vec![0; transition.variables.len()];
+        loop {
+            let value = |term: Term| match term {
+                Term::State(state) => state,
+                Term::Variable(i) => transition.variables[i][choice[i]],
+            };
+            let output = value(transition.output);
+            for arrangement in &arrangements {
+                cells[0] = value(transition.inputs[0]);
+                for (i, &from) in arrangement.iter().enumerate() {
+                    cells[i + 1] = value(transition.inputs[from + 1]);
+                }
+                let index = cells
+                    .iter()
+                    .rev()
+                    .fold(0, |index, &cell| index * states as usize + cell as usize);
+                if !matched[index] {
+                    matched[index] = true;
+                    next[index] = output;
+                }
+            }
+            // count through every combination of the variables' states
+            let Some(i) =
+                (0..choice.len()).find(|&i| choice[i] + 1 < transition.variables[i].len())
+            else {
+                break;
+            };
+            choice[i] += 1;
+            choice[..i].iter_mut().for_each(|c| *c = 0);
+        }
+    }
+
+    Ok(RuleTable {
+        name: name.to_string(),
+        neighbourhood,
+        states,
+        next,
+        colours: Vec::new(),
+    })
+}
+
+fn parse_state(state: &str, states: u32) -> anyhow::Result<u8> {
+    match state.parse::<u32>() {
+        Ok(n) if n < states => Ok(n as u8),
+        _ => bail!("{state:?} is not a state or a variable"),
+    }
+}
+
+fn parse_transition(
+    line: &str,
+    states: u32,
+    neighbourhood: TableNeighbourhood,
+    variables: &HashMap<String, Vec<u8>>,
+) -> anyhow::Result<Transition> {
+    // tables with 10 states or fewer can leave out the commas
+    let terms: Vec<String> = if line.contains(',') {
+        line.split(',')
+            .map(|term| term.trim().to_string())
+            .collect()
+    } else if line.contains(char::is_whitespace) {
+        line.split_whitespace().map(str::to_string).collect()
+    } else {
+        line.chars().map(String::from).collect()
+    };
+    let expected = neighbourhood.neighbours() + 2;
+    ensure!(
+        terms.len() == expected,
+        "transition {line:?} should have {expected} states"
+    );
+
+    let mut names: Vec<&str> = Vec::new();
+    let mut transition = Transition {
+        inputs: Vec::new(),
+        output: Term::State(0),
+        variables: Vec::new(),
+    };
+    for term in &terms[..expected - 1] {
+        let input = match variables.get(term.as_str()) {
+            Some(values) => match names.iter().position(|name| name == term) {
+                Some(i) => Term::Variable(i),
+                None => {
+                    names.push(term);
+                    transition.variables.push(values.clone());
+                    Term::Variable(names.len() - 1)
+                }
+            },
+            None => Term::State(parse_state(term, states)?),
+        };
+        transition.inputs.push(input);
+    }
+    let output = &terms[expected - 1];
+    transition.output = match names.iter().position(|name| name == output) {
+        Some(i) => Term::Variable(i),
+        None if variables.contains_key(output.as_str()) => {
+            bail!("the result of {line:?} uses a variable that isn't in its inputs")
+        }
+        None => Term::State(parse_state(output, states)?),
+    };
+    Ok(transition)
+}
+
+/// For each way the symmetries allow a transition's neighbours to be
+/// arranged, which of the transition's neighbours goes in each position
+fn arrangements(
+    neighbourhood: TableNeighbourhood,
+    symmetries: &str,
+) -> anyhow::Result<Vec<Vec<usize>>> {
+    let n = neighbourhood.neighbours();
+    if symmetries == "permute" {
+        return Ok(permutations(n));
+    }
+    let (rotations, reflect) = match (symmetries, neighbourhood) {
+        ("none", _) => (1, false),
+        ("rotate4", _) => (4, false),
+        ("rotate8", TableNeighbourhood::Moore) => (8, false),
+        ("reflect", _) => (1, true),
+        ("rotate4reflect", _) => (4, true),
+        ("rotate8reflect", TableNeighbourhood::Moore) => (8, true),
+        _ => bail!("{symmetries} symmetry isn't supported for {neighbourhood:?} neighbourhoods"),
+    };
+    let step = n / rotations;
+    let mut arrangements = Vec::new();
+    for rotation in 0..rotations {
+        for mirrored in [false, true].into_iter().take(reflect as usize + 1) {
+            arrangements.push(
+                (0..n)
+                    .map(|i| {
+                        // neighbours go clockwise from N, so mirroring left to
+                        // right swaps i and n - i
+                        let i = if mirrored { (n - i) % n } else { i };
+                        (i + rotation * step) % n
+                    })
+                    .collect(),
+            );
+        }
+    }
+    Ok(arrangements)
+}
+
+/// Every ordering of 0..n
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![Vec::new()];
+    }
+    permutations(n - 1)
+        .into_iter()
+        .flat_map(|shorter| {
+            (0..n).map(move |i| {
+                let mut longer = shorter.clone();
+                longer.insert(i, n - 1);
+                longer
+            })
+        })
+        .collect()
+}
+
+/// `state r g b` lines, or `r1 g1 b1 r2 g2 b2` for a gradient across the
+/// live states
+fn parse_colours(lines: &[&str], states: u32) -> anyhow::Result<Vec<Option<[u8; 3]>>> {
+    let mut colours = vec![None; states as usize];
+    for line in lines {
+        let numbers = line
+            .split_whitespace()
+            .map(str::parse::<u32>)
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("{line:?} is not a colour"))?;
+        let channel = |n: u32| n.min(255) as u8;
+        match numbers[..] {
+            [state, r, g, b] => {
+                if let Some(colour) = colours.get_mut(state as usize) {
+                    *colour = Some([r, g, b].map(channel));
+                }
+            }
+            [r1, g1, b1, r2, g2, b2] => {
+                let live_states = states as usize - 1;
+                for (i, colour) in colours.iter_mut().enumerate().skip(1) {
+                    let t = if live_states > 1 {
+                        (i - 1) as f32 / (live_states - 1) as f32
+                    } else {
+                        0.0
+                    };
+                    let mix =
+                        |from: u32, to: u32| (from as f32 + (to as f32 - from as f32) * t) as u32;
+                    *colour = Some([mix(r1, r2), mix(g1, g2), mix(b1, b2)].map(channel));
+                }
+            }
+            _ => bail!("{line:?} is not a colour"),
+        }
+    }
+    Ok(colours)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Neighbours in the order transitions list them, clockwise from N
+    const N: usize = 0;
+    const NE: usize = 1;
+    const E: usize = 2;
+    const SE: usize = 3;
+    const S: usize = 4;
+    const SW: usize = 5;
+    const W: usize = 6;
+    const NW: usize = 7;
+
+    /// The next state of `cell` with the neighbours at `live` in state
+    /// `state` and the rest in 0, looked up the way the shader does
+    fn next(table: &RuleTable, cell: u8, live: &[usize], state: u8) -> u8 {
+        let mut neighbours = vec![0; table.neighbourhood.neighbours()];
+        for &i in live {
+            neighbours[i] = state;
+        }
+        next_of(table, cell, &neighbours)
+    }
+
+    fn next_of(table: &RuleTable, cell: u8, neighbours: &[u8]) -> u8 {
+        // the cell is the least significant digit, then each neighbour
+        let index = std::iter::once(cell)
+            .chain(neighbours.iter().copied())
+            .rev()
+            .fold(0, |index, state| {
+                index * table.states as usize + state as usize
+            });
+        table.next[index]
+    }
+
+    /// A two state Moore table with `symmetries` and `transitions`
+    fn table(symmetries: &str, transitions: &[&str]) -> RuleTable {
+        let mut lines = vec!["n_states:2", "neighborhood:Moore"];
+        let symmetries = format!("symmetries:{symmetries}");
+        lines.push(&symmetries);
+        lines.extend_from_slice(transitions);
+        compile("test", &lines).unwrap()
+    }
+
+    #[test]
+    fn wireworld_steps_by_hand() {
+        let wireworld = RuleTable::find("WireWorld").unwrap();
+        let (empty, head, tail, wire) = (0, 1, 2, 3);
+        assert_eq!(wireworld.states, 4);
+        assert_eq!(next(&wireworld, head, &[N, E], wire), tail);
+        assert_eq!(next(&wireworld, tail, &[N, E], head), wire);
+        for heads in [&[N][..], &[SE], &[W, NE], &[S, SW]] {
+            assert_eq!(next(&wireworld, wire, heads, head), head, "{heads:?}");
+        }
+        assert_eq!(next(&wireworld, wire, &[], head), wire);
+        assert_eq!(next(&wireworld, wire, &[N, E, S], head), wire);
+        // tails and wire don't count as heads
+        assert_eq!(next(&wireworld, wire, &[N, E], tail), wire);
+        assert_eq!(next(&wireworld, empty, &[N, E], head), empty);
+    }
+
+    #[test]
+    fn the_cell_is_the_lowest_digit_of_the_index() {
+        // only a dead cell with a live N comes alive
+        let table = table("none", &["0,1,0,0,0,0,0,0,0,1"]);
+        assert_eq!(table.next.len(), 1 << 9);
+        assert_eq!(table.next[0b10], 1);
+        // the shader finds the first four from the lowest byte up, with the
+        // live cells at 1 and 3 staying alive
+        assert_eq!(table.packed()[0], 0x01_01_01_00);
+        // everything else stays as it is
+        for (index, &next) in table.next.iter().enumerate() {
+            if index != 0b10 {
+                assert_eq!(next as usize, index & 1, "{index:#b}");
+            }
+        }
+
+        let text = "n_states:3\nneighborhood:vonNeumann\nsymmetries:none\n0,0,2,0,0,1";
+        let lines: Vec<&str> = text.lines().collect();
+        let table = compile("test", &lines).unwrap();
+        // the cell then N, E, S and W, in base 3
+        assert_eq!(table.next.len(), 3usize.pow(5));
+        assert_eq!(table.next[2 * 3usize.pow(2)], 1);
+    }
+
+    #[test]
+    fn rotate4_turns_the_neighbours_a_quarter_at_a_time() {
+        let table = table("rotate4", &["0,1,0,0,0,0,0,0,0,1"]);
+        for born in [N, E, S, W] {
+            assert_eq!(next(&table, 0, &[born], 1), 1, "{born}");
+        }
+        for not_born in [NE, SE, NW] {
+            assert_eq!(next(&table, 0, &[not_born], 1), 0, "{not_born}");
+        }
+    }
+
+    #[test]
+    fn rotate8_turns_the_neighbours_an_eighth_at_a_time() {
+        let table = table("rotate8", &["0,1,0,0,0,0,0,0,0,1"]);
+        for born in 0..8 {
+            assert_eq!(next(&table, 0, &[born], 1), 1, "{born}");
+        }
+    }
+
+    #[test]
+    fn reflect_mirrors_left_to_right() {
+        let table = table("reflect", &["0,0,1,0,0,0,0,0,0,1"]);
+        assert_eq!(next(&table, 0, &[NE], 1), 1);
+        assert_eq!(next(&table, 0, &[NW], 1), 1);
+        assert_eq!(next(&table, 0, &[SE], 1), 0);
+        assert_eq!(next(&table, 0, &[N], 1), 0);
+    }
+
+    #[test]
+    fn rotate4reflect_matches_all_8_orientations() {
+        // N, NE and S alive isn't the same as itself in any other orientation
+        let table = table("rotate4reflect", &["0,1,1,0,0,1,0,0,0,1"]);
+        let orientations: Vec<[usize; 3]> = (0..4)
+            .flat_map(|rotation| {
+                [false, true].map(|mirrored| {
+                    [N, NE, S].map(|i| {
+                        let i = if mirrored { (8 - i) % 8 } else { i };
+                        (i + rotation * 2) % 8
+                    })
+                })
+            })
+            .collect();
+        for live in &orientations {
+            assert_eq!(next(&table, 0, live, 1), 1, "{live:?}");
+        }
+        // and nothing else with three live neighbours is born
+        let mut born = 0;
+        for a in 0..8 {
+            for b in a + 1..8 {
+                for c in b + 1..8 {
+                    born += next(&table, 0, &[a, b, c], 1) as usize;
+                }
+            }
+        }
+        assert_eq!(born, 8);
+    }
+
+    #[test]
+    fn permute_matches_any_order() {
+        let table = table("permute", &["0,1,1,0,0,0,0,0,0,1"]);
+        for a in 0..8 {
+            for b in 0..8 {
+                let expected = (a != b) as u8;
+                assert_eq!(next(&table, 0, &[a, b], 1), expected, "{a} {b}");
+            }
+        }
+        assert_eq!(next(&table, 0, &[N, E, S], 1), 0);
+    }
+
+    #[test]
+    fn variables_used_twice_take_the_same_state() {
+        let text = "n_states:3\nneighborhood:Moore\nsymmetries:none\n\
+                    var a={1,2}\n0,a,0,0,0,a,0,0,0,a";
+        let lines: Vec<&str> = text.lines().collect();
+        let table = compile("test", &lines).unwrap();
+        let mut neighbours = [0; 8];
+        for (north, south, expected) in [(1, 1, 1), (2, 2, 2), (1, 2, 0), (2, 0, 0)] {
+            neighbours[N] = north;
+            neighbours[S] = south;
+            assert_eq!(next_of(&table, 0, &neighbours), expected, "{north} {south}");
+        }
+    }
+
+    #[test]
+    fn the_first_matching_transition_wins() {
+        let table = table(
+            "none",
+            &[
+                "var a={0,1}",
+                "0,1,a,0,0,0,0,0,0,1",
+                "0,1,1,0,0,0,0,0,0,0",
+                "1,a,a,a,a,a,a,a,a,0",
+            ],
+        );
+        assert_eq!(next(&table, 0, &[N], 1), 1);
+        assert_eq!(next(&table, 0, &[N, NE], 1), 1);
+        assert_eq!(next(&table, 1, &[], 1), 0);
+        assert_eq!(next(&table, 1, &[N, E], 1), 1);
+    }
+
+    #[test]
+    fn arrangements_and_permutations_count_every_orientation() {
+        let count = |symmetries: &str| {
+            arrangements(TableNeighbourhood::Moore, symmetries)
+                .unwrap()
+                .len()
+        };
+        assert_eq!(count("none"), 1);
+        assert_eq!(count("rotate4"), 4);
+        assert_eq!(count("rotate8reflect"), 16);
+        assert_eq!(count("permute"), 40320);
+        assert!(arrangements(TableNeighbourhood::VonNeumann, "rotate8").is_err());
+
+        let mut permutations = permutations(3);
+        permutations.sort();
+        permutations.dedup();
+        assert_eq!(permutations.len(), 6);
+        assert!(permutations.iter().all(|p| {
+            let mut sorted = p.clone();
+            sorted.sort();
+            sorted == [0, 1, 2]
+        }));
+    }
+
+    #[test]
+    fn colours_can_be_given_one_by_one_or_as_a_gradient() {
+        let colours = parse_colours(&["0 0 0 255 255 255", "3 1 2 3"], 4).unwrap();
+        assert_eq!(
+            colours,
+            [
+                None,
+                Some([0, 0, 0]),
+                Some([127, 127, 127]),
+                Some([1, 2, 3]),
+            ]
+        );
+        let wireworld = RuleTable::find("WireWorld").unwrap();
+        assert_eq!(wireworld.colours[1], Some([0, 128, 255]));
+        assert!(parse_colours(&["1 2"], 4).is_err());
+    }
+
+    #[test]
+    fn bad_tables_are_rejected() {
+        let compile_lines = |text: &str| compile("test", &text.lines().collect::<Vec<_>>());
+        for text in [
+            // too few states in the transition
+            "n_states:2\nneighborhood:Moore\n0,1,0,0,0,0,0,0,1",
+            // a result variable that isn't an input
+            "n_states:2\nneighborhood:Moore\nvar a={0,1}\n0,0,0,0,0,0,0,0,0,a",
+            // a state past n_states
+            "n_states:2\nneighborhood:Moore\n0,2,0,0,0,0,0,0,0,1",
+            "neighborhood:Moore\n0,1,0,0,0,0,0,0,0,1",
+            "n_states:2\nneighborhood:hex\n0,1,0,0,0,0,0,1",
+            // 256^9 neighbourhoods won't fit
+            "n_states:256\nneighborhood:Moore\n0,1,0,0,0,0,0,0,0,1",
+        ] {
+            assert!(compile_lines(text).is_err(), "{text}");
+        }
+    }
+}
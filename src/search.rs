@@ -0,0 +1,300 @@
+// Soup search: an apgsearch-like batch mode.
+//
+// Seeded random soups are run on a small torus until they settle into a
+// repeating state. Each settled soup gets a census, and any soup that produced
+// a rare object, took unusually long to settle or never settled is written to
+// a results file together with its seed, so it can be reproduced later.
+
+use crate::{
+    analysis::{Census, Object},
+    config::{AppConfig, Topology},
+    headless::Headless,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+/// Objects turning up in nearly every soup. Anything else counts as rare.
+const COMMON_OBJECTS: &[&str] = &[
+    "block",
+    "blinker",
+    "beehive",
+    "glider",
+    "loaf",
+    "boat",
+    "ship",
+    "tub",
+    "pond",
+    "long boat",
+    "toad",
+    "beacon",
+];
+
+// generations run per GPU submission and readback
+const BATCH_GENERATIONS: usize = 64;
+
+pub struct SearchOptions {
+    pub soups: u64,
+    pub first_seed: u64,
+    /// side length of the torus
+    pub size: usize,
+    /// soups still changing after this many generations are given up on
+    pub max_generations: usize,
+    /// soups settling later than this are reported
+    pub long_lifespan: usize,
+    pub results_path: PathBuf,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            soups: 1000,
+            first_seed: 0,
+            size: 64,
+            max_generations: 20_000,
+            long_lifespan: 5_000,
+            results_path: PathBuf::from("search_results.tsv"),
+        }
+    }
+}
+
+pub struct SoupResult {
+    pub seed: u64,
+    /// generation at which the soup started repeating, if it did
+    pub lifespan: Option<usize>,
+    pub period: usize,
+    pub census: Census,
+}
+
+impl SoupResult {
+    pub fn rare_objects(&self) -> Vec<String> {
+        self.census
+            .entries
+            .iter()
+            .filter(|(object, _)| match object {
+                Object::Known { name, .. } => !COMMON_OBJECTS.contains(name),
+                Object::Unidentified { .. } => true,
+            })
+            .map(|(object, _)| object.name())
+            .collect()
+    }
+
+    /// Why this soup is worth keeping, if it is
+    pub fn hit_reason(&self, options: &SearchOptions) -> Option<String> {
+        let rare_objects = self.rare_objects();
+        match self.lifespan {
+            None => Some(format!(
+                "did not settle within {} generations",
+                options.max_generations
+            )),
+            Some(lifespan) if lifespan > options.long_lifespan => {
+                Some(format!("settled after {lifespan} generations"))
+            }
+            _ if !rare_objects.is_empty() => Some(format!("rare: {}", rare_objects.join(", "))),
+            _ => None,
+        }
+    }
+}
+
+pub fn run_search(mut config: AppConfig, options: &SearchOptions) -> anyhow::Result<()> {
+    Census::check_rule(&config.rule)?;
+    config.topology = Topology::Torus;
+    config.resize_grid(options.size, options.size);
+    let mut headless = Headless::new(config)?;
+
+    let mut results = BufWriter::new(File::create(&options.results_path)?);
+    writeln!(
+        results,
+        "# soups on a {0}x{0} torus, fill {1}, symmetry {2:?}",
+        options.size, headless.config.init_rand_threshold, headless.config.soup_symmetry
+    )?;
+    writeln!(results, "seed\tlifespan\tperiod\treason\tcensus")?;
+
+    let mut totals: BTreeMap<String, usize> = BTreeMap::new();
+    let mut hits = 0;
+    for seed in options.first_seed..options.first_seed + options.soups {
+        let soup = run_soup(&mut headless, seed, options.max_generations)?;
+        for (object, count) in &soup.census.entries {
+            *totals.entry(object.name()).or_default() += count;
+        }
+
+        if let Some(reason) = soup.hit_reason(options) {
+            hits += 1;
+            let census: Vec<String> = soup
+                .census
+                .entries
+                .iter()
+                .map(|(object, count)| format!("{}x{count}", object.name()))
+                .collect();
+            let lifespan = soup
+                .lifespan
+                .map_or_else(|| "-".to_string(), |l| l.to_string());
+            log::info!("seed {seed}: {reason}");
+            writeln!(
+                results,
+                "{seed}\t{lifespan}\t{}\t{reason}\t{}",
+                soup.period,
+                census.join(", ")
+            )?;
+            results.flush()?;
+        }
+
+        let searched = seed - options.first_seed + 1;
+        if searched.is_multiple_of(100) {
+            println!("{searched} soups searched, {hits} hits");
+        }
+    }
+
+    let mut totals: Vec<(String, usize)> = totals.into_iter().collect();
+    totals.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    println!(
+        "Searched {} soups, {hits} hits written to {}",
+        options.soups,
+        options.results_path.display()
+    );
+    for (name, count) in totals {
+        println!("{name:<30} {count:>8}");
+    }
+    Ok(())
+}
+
+/// Runs one seeded soup until a state repeats or `max_generations` is reached.
+pub fn run_soup(
+    headless: &mut Headless,
+    seed: u64,
+    max_generations: usize,
+) -> anyhow::Result<SoupResult> {
+    headless
+        .game_data
+        .randomise_grid_state_from_seed(seed, &headless.config, &headless.queue);
+    headless.game_data.is_a_current = true;
+
+    // hash of each state seen so far and the generation it was first seen in
+    let mut seen: HashMap<u64, usize> = HashMap::new();
+    seen.insert(state_hash(&headless.read_grid_state()?), 0);
+
+    let mut generation = 0;
+    let mut last_state = Vec::new();
+    while generation < max_generations {
+        for state in headless.step_recording(BATCH_GENERATIONS)? {
+            generation += 1;
+            let hash = state_hash(&state);
+            if let Some(&first_seen) = seen.get(&hash) {
+                return Ok(SoupResult {
+                    seed,
+                    lifespan: Some(first_seen),
+                    period: generation - first_seen,
+                    census: census(headless, &state)?,
+                });
+            }
+            seen.insert(hash, generation);
+            last_state = state;
+        }
+    }
+
+    Ok(SoupResult {
+        seed,
+        lifespan: None,
+        period: 0,
+        census: census(headless, &last_state)?,
+    })
+}
+
+fn census(headless: &Headless, state: &[u32]) -> anyhow::Result<Census> {
+    Census::of_grid(
+        state,
+        headless.config.rows,
+        headless.config.cols,
+        headless.config.topology,
+        &headless.config.rule,
+    )
+}
+
+fn state_hash(state: &[u32]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::ObjectKind;
+
+    fn soup(lifespan: Option<usize>, objects: &[Object]) -> SoupResult {
+        SoupResult {
+            seed: 7,
+            lifespan,
+            period: 2,
+            census: Census {
+                entries: objects.iter().map(|object| (object.clone(), 1)).collect(),
+            },
+        }
+    }
+
+    fn known(name: &'static str) -> Object {
+        Object::Known {
+            name,
+            kind: ObjectKind::StillLife,
+        }
+    }
+
+    #[test]
+    fn common_objects_arent_rare() {
+        let result = soup(
+            Some(100),
+            &[
+                known("block"),
+                known("pentadecathlon"),
+                known("glider"),
+                Object::Unidentified { population: 12 },
+            ],
+        );
+        assert_eq!(
+            result.rare_objects(),
+            ["pentadecathlon", "unidentified (12 cells)"]
+        );
+        assert!(
+            soup(Some(100), &[known("block"), known("blinker")])
+                .rare_objects()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn hits_are_reported_for_the_most_unusual_reason() {
+        let options = SearchOptions::default();
+        let ordinary = [known("block"), known("blinker")];
+        let rare = [known("block"), known("pentadecathlon")];
+
+        assert_eq!(soup(Some(100), &ordinary).hit_reason(&options), None);
+        assert_eq!(
+            soup(Some(options.long_lifespan), &ordinary).hit_reason(&options),
+            None
+        );
+        assert_eq!(
+            soup(Some(100), &rare).hit_reason(&options).unwrap(),
+            "rare: pentadecathlon"
+        );
+        assert_eq!(
+            soup(Some(options.long_lifespan + 1), &rare)
+                .hit_reason(&options)
+                .unwrap(),
+            "settled after 5001 generations"
+        );
+        assert_eq!(
+            soup(None, &rare).hit_reason(&options).unwrap(),
+            "did not settle within 20000 generations"
+        );
+    }
+
+    #[test]
+    fn equal_states_hash_equally() {
+        assert_eq!(state_hash(&[0, 1, 1, 0]), state_hash(&[0, 1, 1, 0]));
+        assert_ne!(state_hash(&[0, 1, 1, 0]), state_hash(&[1, 0, 0, 1]));
+    }
+}
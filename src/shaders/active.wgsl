@@ -0,0 +1,81 @@
+struct GridInfo {
+    rows: u32,
+    cols: u32,
+    // 1 if the edges wrap around
+    wrap: u32,
+    // 2 for Life-like rules, more for Generations rules
+    states: u32,
+    // Larger than Life rules, see ltl.wgsl. range is also how far one
+    // dimensional rules reach along their row
+    range: u32,
+    shape: u32,
+    include_middle: u32,
+    birth_min: u32,
+    birth_max: u32,
+    survival_min: u32,
+    survival_max: u32,
+    // 1 in continuous mode, where states are f32 bits
+    continuous: u32,
+};
+@group(0)@binding(0)
+var<uniform> grid: GridInfo;
+
+// the workgroups of the indirect dispatch that steps the squares
+struct DispatchArgs {
+    x: atomic<u32>,
+    y: u32,
+    z: u32,
+};
+
+// 1 for each 16x16 square with a cell that changed last generation
+@group(1)@binding(0)
+var<storage, read> changed: array<u32>;
+// cleared here, then marked as this generation steps the squares
+@group(1)@binding(1)
+var<storage, read_write> next_changed: array<u32>;
+// the squares to step, see main_active in compute.wgsl
+@group(1)@binding(2)
+var<storage, read_write> active_squares: array<u32>;
+@group(1)@binding(3)
+var<storage, read_write> dispatch: DispatchArgs;
+
+const SQUARE_SIZE: u32 = 16u;
+
+// lists a square to be stepped if it or any square around it changed
+@compute @workgroup_size(64,1,1)
+fn select_squares(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let squares = vec2<i32>(square_count());
+    let index = i32(global_id.x);
+    if index >= squares.x * squares.y {
+        return;
+    }
+    next_changed[index] = 0u;
+
+    let square = vec2<i32>(index % squares.x, index / squares.x);
+    for (var dy: i32 = -1; dy <= 1; dy = dy + 1) {
+        for (var dx: i32 = -1; dx <= 1; dx = dx + 1) {
+            let neighbour = square_index(square + vec2<i32>(dx, dy), squares);
+            if neighbour >= 0 && changed[neighbour] != 0u {
+                active_squares[atomicAdd(&dispatch.x, 1u)] = u32(index);
+                return;
+            }
+        }
+    }
+}
+
+fn square_count() -> vec2<u32> {
+    return (vec2<u32>(grid.cols, grid.rows) + SQUARE_SIZE - 1u) / SQUARE_SIZE;
+}
+
+// index of the square at `square`, wrapping around a torus, or -1 if it's
+// off the edge
+fn square_index(square: vec2<i32>, squares: vec2<i32>) -> i32 {
+    var wrapped = square;
+    if grid.wrap == 1u {
+        wrapped = (square + squares) % squares;
+    }
+    if any(wrapped < vec2<i32>(0)) || any(wrapped >= squares) {
+        return -1;
+    }
+    return wrapped.x + wrapped.y * squares.x;
+}
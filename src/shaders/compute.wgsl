@@ -1,26 +1,56 @@
 struct GridInfo {
     rows: u32,
     cols: u32,
-    _pad: vec2<u32>
+    // 1 if the edges wrap around
+    wrap: u32,
+    // 2 for Life-like rules, more for Generations rules
+    states: u32,
+    // Larger than Life rules, see ltl.wgsl. range is also how far one
+    // dimensional rules reach along their row
+    range: u32,
+    shape: u32,
+    include_middle: u32,
+    birth_min: u32,
+    birth_max: u32,
+    survival_min: u32,
+    survival_max: u32,
+    // 1 in continuous mode, where states are f32 bits
+    continuous: u32,
 };
 @group(0)@binding(0)
 var<uniform> grid: GridInfo;
 
+// 1 if a cell in state 0 or 1 is alive next generation, indexed by its
+// neighbourhood, see neighbourhood_index and hex_neighbourhood_index
+@group(0)@binding(1)
+var<storage, read> rule_table: array<u32, 512>;
+
 @group(1)@binding(0)
 var<storage, read_write> current_state: array<u32>;
 @group(1)@binding(1)
 var<storage, read_write> next_state: array<u32>;
 
+// the 16x16 squares the active entry points step, see active.wgsl
+@group(2)@binding(0)
+var<storage, read> active_squares: array<u32>;
+// marked for each square with a cell that changes
+@group(2)@binding(1)
+var<storage, read_write> changed: array<u32>;
+
+const SQUARE_SIZE: u32 = 16u;
 
-const OFFSETS: array<vec2<i32>, 8> = array<vec2<i32>, 8>(
-    vec2<i32>( 0, -1), // top
-    vec2<i32>( -1, -1), // top left 
+
+// row 0 is drawn at the bottom, so up is +y
+const OFFSETS: array<vec2<i32>, 9> = array<vec2<i32>, 9>(
+    vec2<i32>(-1,  1), // top left
+    vec2<i32>( 0,  1), // top
+    vec2<i32>( 1,  1), // top right
     vec2<i32>(-1,  0), // left
-    vec2<i32>(-1, 1), // bottom left
-    vec2<i32>( 0,  1), // bottom
-    vec2<i32>( 1,  1), // bottom right
-    vec2<i32>( 1,  0),  // Right
-    vec2<i32>( 1,  -1)  // top right
+    vec2<i32>( 0,  0), // middle
+    vec2<i32>( 1,  0), // right
+    vec2<i32>(-1, -1), // bottom left
+    vec2<i32>( 0, -1), // bottom
+    vec2<i32>( 1, -1)  // bottom right
 );
 
 @compute @workgroup_size(16,16,1)
@@ -31,16 +61,108 @@ fn  main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         return;
     }
 
+    step(pos, neighbourhood_index(pos));
+}
+
+// hexagonal grids, where every odd row is shifted half a cell right
+@compute @workgroup_size(16,16,1)
+fn main_hex(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let pos = vec2<i32>(i32(global_id.x), i32(global_id.y));
+
+    if !pos_in_grid(pos) {
+        return;
+    }
+
+    step(pos, hex_neighbourhood_index(pos));
+}
+
+// one dimensional rules, where row 0 is the newest generation and every
+// other row takes the one below it, so the history scrolls up the screen
+@compute @workgroup_size(16,16,1)
+fn main_1d(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let pos = vec2<i32>(i32(global_id.x), i32(global_id.y));
+
+    if !pos_in_grid(pos) {
+        return;
+    }
+
+    let state_index = pos_to_index(pos);
+    if pos.y > 0 {
+        next_state[state_index] = current_state[pos_to_index(pos - vec2<i32>(0, 1))];
+        return;
+    }
+
+    // the 2 range + 1 cells of the row as bits, leftmost first
+    let range = i32(grid.range);
+    var index: u32 = 0u;
+    for (var dx: i32 = -range; dx <= range; dx = dx + 1) {
+        index = (index << 1u) | is_alive(pos + vec2<i32>(dx, 0));
+    }
+    next_state[state_index] = rule_table[index];
+}
+
+// only the squares in active_squares, one workgroup each
+@compute @workgroup_size(16,16,1)
+fn main_active(
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+) {
+    let pos = active_pos(workgroup_id.x, local_id.xy);
+
+    if !pos_in_grid(pos) {
+        return;
+    }
+
+    step(pos, neighbourhood_index(pos));
+    mark_if_changed(pos, workgroup_id.x);
+}
+
+@compute @workgroup_size(16,16,1)
+fn main_hex_active(
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+) {
+    let pos = active_pos(workgroup_id.x, local_id.xy);
+
+    if !pos_in_grid(pos) {
+        return;
+    }
+
+    step(pos, hex_neighbourhood_index(pos));
+    mark_if_changed(pos, workgroup_id.x);
+}
+
+// the cell at `local` in the `workgroup`th active square
+fn active_pos(workgroup: u32, local: vec2<u32>) -> vec2<i32> {
+    let squares_x = (grid.cols + SQUARE_SIZE - 1u) / SQUARE_SIZE;
+    let square = active_squares[workgroup];
+    let corner = vec2<u32>(square % squares_x, square / squares_x) * SQUARE_SIZE;
+    return vec2<i32>(corner + local);
+}
+
+fn mark_if_changed(pos: vec2<i32>, workgroup: u32) {
+    let state_index = pos_to_index(pos);
+    if next_state[state_index] != current_state[state_index] {
+        changed[active_squares[workgroup]] = 1u;
+    }
+}
+
+fn step(pos: vec2<i32>, neighbourhood: u32) {
     let state_index = pos_to_index(pos);
 
     let state = current_state[state_index];
 
-    let n_neighbors = get_n_neighbors(pos);
     var next: u32 = 0u;
-    if n_neighbors == 3u {
-        next = 1u;
-    } else if state == 1u && n_neighbors == 2u {
-        next = 1u;
+    if state <= 1u {
+        if rule_table[neighbourhood] == 1u {
+            next = 1u;
+        } else if state == 1u {
+            // start decaying, or die straight away in a 2 state rule
+            next = 2u % grid.states;
+        }
+    } else {
+        // decaying cells count down to dead whatever their neighbours are
+        next = (state + 1u) % grid.states;
     }
 
     next_state[state_index] = next;
@@ -64,20 +186,46 @@ fn pos_in_grid(pos:vec2<i32>) -> bool {
         pos.y < i32(grid.rows);
 }
 
-fn get_n_neighbors(pos: vec2<i32>) -> u32 {
-    var n: u32 = 0u;
-    for (var i: i32 = 0; i < 8; i=i+1) {
-        let offset = OFFSETS[i];
-        let neighbor = pos + offset;
-        if pos_in_grid(neighbor) {
-            let idx = pos_to_index(neighbor);
-            if current_state[idx] == 1u {
-                n= n+1u;
-            }
-            
-        }
-        
+// on a torus bring positions that fall off one edge back in on the other
+fn wrap_pos(pos: vec2<i32>) -> vec2<i32> {
+    if grid.wrap == 0u {
+        return pos;
+    }
+    let dims = vec2<i32>(i32(grid.cols), i32(grid.rows));
+    return (pos + dims) % dims;
+}
+
+// the live cells of the 3x3 block around `pos` as bits, top left first
+fn neighbourhood_index(pos: vec2<i32>) -> u32 {
+    var index: u32 = 0u;
+    for (var i: i32 = 0; i < 9; i=i+1) {
+        index = (index << 1u) | is_alive(pos + OFFSETS[i]);
     }
 
-    return n;
-}
\ No newline at end of file
+    return index;
+}
+
+// the live cells of the 6 hexagons around `pos`, each in the bit of the
+// 3x3 block it's closest to, so the top and bottom bits are always 0
+fn hex_neighbourhood_index(pos: vec2<i32>) -> u32 {
+    // the rows above and below are shifted half a cell the other way
+    let shift = pos.y & 1;
+    var index: u32 = 0u;
+    index |= is_alive(pos + vec2<i32>(shift - 1, 1)) << 8u; // top left
+    index |= is_alive(pos + vec2<i32>(shift, 1)) << 6u; // top right
+    index |= is_alive(pos + vec2<i32>(-1, 0)) << 5u; // left
+    index |= is_alive(pos) << 4u; // middle
+    index |= is_alive(pos + vec2<i32>(1, 0)) << 3u; // right
+    index |= is_alive(pos + vec2<i32>(shift - 1, -1)) << 2u; // bottom left
+    index |= is_alive(pos + vec2<i32>(shift, -1)); // bottom right
+
+    return index;
+}
+
+fn is_alive(pos: vec2<i32>) -> u32 {
+    let neighbor = wrap_pos(pos);
+    if pos_in_grid(neighbor) && current_state[pos_to_index(neighbor)] == 1u {
+        return 1u;
+    }
+    return 0u;
+}
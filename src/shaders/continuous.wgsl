@@ -0,0 +1,115 @@
+// Continuous mode: cells hold values from 0 to 1 as f32 bits. Each workgroup
+// copies its block of the grid plus a border `radius` cells wide into
+// workgroup memory, then every cell weights its surroundings by the kernel
+// and grows or decays by how close the total is to `mu`.
+struct Params {
+    rows: u32,
+    cols: u32,
+    // 1 if the edges wrap around
+    wrap: u32,
+    radius: u32,
+    mu: f32,
+    sigma: f32,
+    dt: f32,
+    // 0 gaussian, 1 polynomial, 2 step
+    growth: u32,
+};
+@group(0)@binding(0)
+var<uniform> params: Params;
+// (2 radius + 1)^2 weights, row by row
+@group(0)@binding(1)
+var<storage, read> kernel: array<f32>;
+
+@group(1)@binding(0)
+var<storage, read_write> current_state: array<u32>;
+@group(1)@binding(1)
+var<storage, read_write> next_state: array<u32>;
+
+const WORKGROUP_SIZE: u32 = 16u;
+// keep in step with continuous::MAX_RADIUS
+const MAX_RADIUS: u32 = 16u;
+const TILE: u32 = WORKGROUP_SIZE + 2u * MAX_RADIUS;
+
+const POLYNOMIAL: u32 = 1u;
+const STEP: u32 = 2u;
+
+var<workgroup> tile: array<f32, TILE * TILE>;
+
+@compute @workgroup_size(16,16,1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let radius = min(params.radius, MAX_RADIUS);
+    let width = WORKGROUP_SIZE + 2u * radius;
+    let origin = vec2<i32>(workgroup_id.xy * WORKGROUP_SIZE) - i32(radius);
+
+    for (var i = local_index; i < width * width; i += WORKGROUP_SIZE * WORKGROUP_SIZE) {
+        let offset = vec2<i32>(i32(i % width), i32(i / width));
+        tile[(i / width) * TILE + i % width] = cell_value(origin + offset);
+    }
+    workgroupBarrier();
+
+    let pos = vec2<i32>(global_id.xy);
+    if !pos_in_grid(pos) {
+        return;
+    }
+
+    let kernel_width = 2u * radius + 1u;
+    var total = 0.0;
+    for (var y = 0u; y < kernel_width; y++) {
+        let row = (local_id.y + y) * TILE + local_id.x;
+        for (var x = 0u; x < kernel_width; x++) {
+            total += tile[row + x] * kernel[y * kernel_width + x];
+        }
+    }
+
+    let state_index = pos_to_index(pos);
+    let value = bitcast<f32>(current_state[state_index]);
+    let next = clamp(value + params.dt * growth(total), 0.0, 1.0);
+    next_state[state_index] = bitcast<u32>(next);
+}
+
+// between -1 and 1, highest when `total` is `mu`
+fn growth(total: f32) -> f32 {
+    let distance = total - params.mu;
+    let sigma = params.sigma;
+    switch params.growth {
+        case POLYNOMIAL: {
+            let bell = max(0.0, 1.0 - distance * distance / (9.0 * sigma * sigma));
+            return 2.0 * pow(bell, 4.0) - 1.0;
+        }
+        case STEP: {
+            return select(-1.0, 1.0, abs(distance) <= sigma);
+        }
+        default: {
+            return 2.0 * exp(-distance * distance / (2.0 * sigma * sigma)) - 1.0;
+        }
+    }
+}
+
+fn cell_value(pos: vec2<i32>) -> f32 {
+    var p = pos;
+    if params.wrap == 1u {
+        // the kernel can be wider than small grids, so wrap more than once
+        let dims = vec2<i32>(i32(params.cols), i32(params.rows));
+        p = ((p % dims) + dims) % dims;
+    }
+    if !pos_in_grid(p) {
+        return 0.0;
+    }
+    return bitcast<f32>(current_state[pos_to_index(p)]);
+}
+
+fn pos_to_index(pos: vec2<i32>) -> u32 {
+    return u32(pos.x + pos.y * i32(params.cols));
+}
+
+fn pos_in_grid(pos:vec2<i32>) -> bool {
+    return pos.x >= 0 &&
+        pos.x < i32(params.cols) &&
+        pos.y >= 0 &&
+        pos.y < i32(params.rows);
+}
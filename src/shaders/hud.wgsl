@@ -0,0 +1,66 @@
+// Text drawn over the grid, one quad per character, see hud.rs
+
+struct HudUniform {
+    // of the surface, in pixels
+    screen_size: vec2<f32>,
+    // screen pixels per font pixel
+    scale: f32,
+    // font pixels across and down a glyph, as floats
+    glyph_width: f32,
+    glyph_height: f32,
+    text_colour: vec4f,
+    background_colour: vec4f,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: HudUniform;
+// each row of every glyph, leftmost pixel in bit 7
+@group(0) @binding(1)
+var<storage,read> glyphs: array<u32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // in font pixels from the glyph's top left
+    @location(0) font_position: vec2<f32>,
+    @location(1) @interpolate(flat) glyph: u32,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_idx: u32,
+    // top left of the character, in pixels from the top left of the screen
+    @location(0) position: vec2f,
+    @location(1) glyph: u32,
+) -> VertexOutput {
+    // two triangles, counter-clockwise on screen
+    var corners = array<vec2f, 6>(
+        vec2f(0.0, 0.0),
+        vec2f(0.0, 1.0),
+        vec2f(1.0, 1.0),
+        vec2f(0.0, 0.0),
+        vec2f(1.0, 1.0),
+        vec2f(1.0, 0.0),
+    );
+    let glyph_size = vec2f(uniforms.glyph_width, uniforms.glyph_height);
+    let corner = corners[vertex_idx] * glyph_size;
+    let pixel = position + corner * uniforms.scale;
+    // y goes down the screen but up in clip space
+    let clip = pixel / uniforms.screen_size * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(clip, 0.0, 1.0);
+    out.font_position = corner;
+    out.glyph = glyph;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let x = min(u32(in.font_position.x), u32(uniforms.glyph_width) - 1u);
+    let y = min(u32(in.font_position.y), u32(uniforms.glyph_height) - 1u);
+    let row = glyphs[in.glyph * u32(uniforms.glyph_height) + y];
+    if ((row >> (7u - x)) & 1u) == 1u {
+        return uniforms.text_colour;
+    }
+    return uniforms.background_colour;
+}
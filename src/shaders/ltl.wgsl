@@ -0,0 +1,168 @@
+// Larger than Life: like compute.wgsl, but neighbours are counted over a range
+// of up to MAX_RANGE cells in one of several shapes. Each workgroup copies
+// its block of the grid plus a border `range` cells wide into workgroup
+// memory, turns each row into running totals, and then every cell adds up
+// one span per row of its neighbourhood.
+struct GridInfo {
+    rows: u32,
+    cols: u32,
+    // 1 if the edges wrap around
+    wrap: u32,
+    // 2 for two state rules, more for Generations rules
+    states: u32,
+    // how far the neighbourhood reaches
+    range: u32,
+    // 0 Moore, 1 von Neumann, 2 circular, 3 hexagonal
+    shape: u32,
+    // 1 if a live cell counts itself
+    include_middle: u32,
+    birth_min: u32,
+    birth_max: u32,
+    survival_min: u32,
+    survival_max: u32,
+    // 1 in continuous mode, where states are f32 bits
+    continuous: u32,
+};
+@group(0)@binding(0)
+var<uniform> grid: GridInfo;
+
+@group(1)@binding(0)
+var<storage, read_write> current_state: array<u32>;
+@group(1)@binding(1)
+var<storage, read_write> next_state: array<u32>;
+
+const WORKGROUP_SIZE: u32 = 16u;
+// keep in step with rule::MAX_RANGE
+const MAX_RANGE: u32 = 16u;
+const TILE: u32 = WORKGROUP_SIZE + 2u * MAX_RANGE;
+
+const MOORE: u32 = 0u;
+const VON_NEUMANN: u32 = 1u;
+const CIRCULAR: u32 = 2u;
+const HEXAGONAL: u32 = 3u;
+
+// live cells, then the running total of each row
+var<workgroup> tile: array<u32, TILE * TILE>;
+
+@compute @workgroup_size(16,16,1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let range = min(grid.range, MAX_RANGE);
+    let width = WORKGROUP_SIZE + 2u * range;
+    let origin = vec2<i32>(workgroup_id.xy * WORKGROUP_SIZE) - i32(range);
+
+    for (var i = local_index; i < width * width; i += WORKGROUP_SIZE * WORKGROUP_SIZE) {
+        let offset = vec2<i32>(i32(i % width), i32(i / width));
+        tile[(i / width) * TILE + i % width] = is_alive(origin + offset);
+    }
+    workgroupBarrier();
+
+    if local_index < width {
+        let row = local_index * TILE;
+        for (var x = 1u; x < width; x++) {
+            tile[row + x] += tile[row + x - 1u];
+        }
+    }
+    workgroupBarrier();
+
+    let pos = vec2<i32>(global_id.xy);
+    if !pos_in_grid(pos) {
+        return;
+    }
+
+    let centre = vec2<i32>(local_id.xy + range);
+    let r = i32(range);
+    var n_neighbors = 0u;
+    for (var dy = -r; dy <= r; dy++) {
+        let span = row_span(dy, r);
+        if span.x > span.y {
+            continue;
+        }
+        let row = u32(centre.y + dy) * TILE;
+        let last = u32(centre.x + span.y);
+        let first = centre.x + span.x;
+        n_neighbors += tile[row + last];
+        if first > 0 {
+            n_neighbors -= tile[row + u32(first - 1)];
+        }
+    }
+
+    let state_index = pos_to_index(pos);
+    let state = current_state[state_index];
+    if state == 1u && grid.include_middle == 0u {
+        n_neighbors -= 1u;
+    }
+
+    var next: u32 = 0u;
+    if state == 0u {
+        if n_neighbors >= grid.birth_min && n_neighbors <= grid.birth_max {
+            next = 1u;
+        }
+    } else if state == 1u {
+        if n_neighbors >= grid.survival_min && n_neighbors <= grid.survival_max {
+            next = 1u;
+        } else {
+            // start decaying, or die straight away in a 2 state rule
+            next = 2u % grid.states;
+        }
+    } else {
+        // decaying cells count down to dead whatever their neighbours are
+        next = (state + 1u) % grid.states;
+    }
+
+    next_state[state_index] = next;
+}
+
+// first and last column offset of the neighbourhood in the row `dy` away
+fn row_span(dy: i32, r: i32) -> vec2<i32> {
+    switch grid.shape {
+        case VON_NEUMANN: {
+            let w = r - abs(dy);
+            return vec2<i32>(-w, w);
+        }
+        case CIRCULAR: {
+            // cells within r + 0.5 of the middle
+            let limit = r * r + r - dy * dy;
+            var w = 0;
+            while (w + 1) * (w + 1) <= limit {
+                w++;
+            }
+            return vec2<i32>(-w, w);
+        }
+        case HEXAGONAL: {
+            // rows are drawn bottom up, so this leans the hexagon's top to the left
+            return vec2<i32>(max(-r, -r - dy), min(r, r - dy));
+        }
+        default: {
+            return vec2<i32>(-r, r);
+        }
+    }
+}
+
+fn is_alive(pos: vec2<i32>) -> u32 {
+    var p = pos;
+    if grid.wrap == 1u {
+        // ranges can be wider than small grids, so wrap more than once
+        let dims = vec2<i32>(i32(grid.cols), i32(grid.rows));
+        p = ((p % dims) + dims) % dims;
+    }
+    if !pos_in_grid(p) {
+        return 0u;
+    }
+    return u32(current_state[pos_to_index(p)] == 1u);
+}
+
+fn pos_to_index(pos: vec2<i32>) -> u32 {
+    return u32(pos.x + pos.y * i32(grid.cols));
+}
+
+fn pos_in_grid(pos:vec2<i32>) -> bool {
+    return pos.x >= 0 &&
+        pos.x < i32(grid.cols) &&
+        pos.y >= 0 &&
+        pos.y < i32(grid.rows);
+}
@@ -1,7 +1,21 @@
 struct GridInfo {
     rows: u32,
     cols: u32,
-    _pad: vec2<u32>
+    // 1 if the edges wrap around
+    wrap: u32,
+    // 2 for Life-like rules, more for Generations rules
+    states: u32,
+    // Larger than Life rules, see ltl.wgsl. range is also how far one
+    // dimensional rules reach along their row
+    range: u32,
+    shape: u32,
+    include_middle: u32,
+    birth_min: u32,
+    birth_max: u32,
+    survival_min: u32,
+    survival_max: u32,
+    // 1 in continuous mode, where states are f32 bits
+    continuous: u32,
 };
 
 @group(0)@binding(0)
@@ -24,7 +38,15 @@ fn  main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
     let idx = pos_to_index(pos);
 
-    current_state[idx] = current_state[idx] | paint_buffer[idx];
+    // painted cells hold the paint state plus one, 0 leaves the cell alone.
+    // in continuous mode state 0 erases and any other fills the cell
+    if paint_buffer[idx] != 0u {
+        if grid.continuous == 1u {
+            current_state[idx] = bitcast<u32>(min(f32(paint_buffer[idx] - 1u), 1.0));
+        } else {
+            current_state[idx] = paint_buffer[idx] - 1u;
+        }
+    }
     return;
 }
 
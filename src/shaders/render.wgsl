@@ -6,14 +6,27 @@ struct VertexOutput {
 
 struct RenderUniform {
     cell_size: vec2<f32>,
-    _pad: vec2<f32>,
+    gap_size: vec2<f32>,
+    turmite_colour: vec4f,
 };
 
 @group(0) @binding(0)
 var<uniform> uniforms: RenderUniform;
+// colour of every cell state, or the colour ramp in continuous mode
+@group(0) @binding(1)
+var<storage,read> palette: array<vec4f>;
 @group(1) @binding(0)
 var<storage,read> current_state: array<u32>;
 
+struct Turmite {
+    x: i32,
+    y: i32,
+    direction: u32,
+    state: u32,
+};
+@group(2) @binding(0)
+var<storage,read> turmites: array<Turmite>;
+
 
 @vertex
 fn vs_main(
@@ -36,6 +49,47 @@ fn fs_main(
     in: VertexOutput,
 
 ) -> @location(0) vec4f {
-    let alive = f32(current_state[in.instance_idx] & 1u);
-    return vec4f(alive);
+    let state = min(current_state[in.instance_idx], arrayLength(&palette) - 1u);
+    return palette[state];
+}
+
+// continuous mode, where states are f32 bits from 0 to 1 spread along the ramp
+@fragment
+fn fs_continuous(
+    in: VertexOutput,
+
+) -> @location(0) vec4f {
+    let value = clamp(bitcast<f32>(current_state[in.instance_idx]), 0.0, 1.0);
+    let last = arrayLength(&palette) - 1u;
+    let position = value * f32(last);
+    let below = min(u32(position), last);
+    let above = min(below + 1u, last);
+    return mix(palette[below], palette[above], fract(position));
+}
+
+// a turmite that's walked off a bounded grid
+const HALTED: u32 = 0xffffffffu;
+// turmites are drawn smaller than cells so the cell shows around them
+const TURMITE_SCALE: f32 = 0.6;
+
+// one instance per turmite, placed the way vertex::get_instances places cells
+@vertex
+fn vs_turmite(
+    @location(0) position: vec2f,
+    @builtin(instance_index) instance_idx: u32
+) -> @builtin(position) vec4f {
+    let turmite = turmites[instance_idx];
+    if turmite.state == HALTED {
+        // every vertex in one spot, so nothing is drawn
+        return vec4f(2.0, 2.0, 0.0, 1.0);
+    }
+    let pitch = uniforms.cell_size + uniforms.gap_size;
+    let centre = -1.0 + uniforms.gap_size + uniforms.cell_size / 2.0
+        + vec2f(f32(turmite.x), f32(turmite.y)) * pitch;
+    return vec4f(centre + position * uniforms.cell_size * TURMITE_SCALE, 0.0, 1.0);
+}
+
+@fragment
+fn fs_turmite() -> @location(0) vec4f {
+    return uniforms.turmite_colour;
 }
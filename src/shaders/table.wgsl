@@ -0,0 +1,117 @@
+// Rule tables: the next state of every neighbourhood is looked up in a table
+// compiled from the rule file, see rule_table.rs.
+struct GridInfo {
+    rows: u32,
+    cols: u32,
+    // 1 if the edges wrap around
+    wrap: u32,
+    // how many states the table has
+    states: u32,
+    // Larger than Life rules, see ltl.wgsl. range is also how far one
+    // dimensional rules reach along their row
+    range: u32,
+    shape: u32,
+    include_middle: u32,
+    birth_min: u32,
+    birth_max: u32,
+    survival_min: u32,
+    survival_max: u32,
+    // 1 in continuous mode, where states are f32 bits
+    continuous: u32,
+};
+@group(0)@binding(0)
+var<uniform> grid: GridInfo;
+
+// next state of every neighbourhood, four to a word. The cell is the least
+// significant digit of the index in base `states`, then each neighbour in
+// the order of MOORE or VON_NEUMANN.
+@group(0)@binding(1)
+var<storage, read> rule_table: array<u32>;
+
+@group(1)@binding(0)
+var<storage, read_write> current_state: array<u32>;
+@group(1)@binding(1)
+var<storage, read_write> next_state: array<u32>;
+
+// the cell, then its neighbours clockwise from the top, as Golly lists them.
+// row 0 is drawn at the bottom, so up is +y
+const MOORE: array<vec2<i32>, 9> = array<vec2<i32>, 9>(
+    vec2<i32>( 0,  0), // middle
+    vec2<i32>( 0,  1), // top
+    vec2<i32>( 1,  1), // top right
+    vec2<i32>( 1,  0), // right
+    vec2<i32>( 1, -1), // bottom right
+    vec2<i32>( 0, -1), // bottom
+    vec2<i32>(-1, -1), // bottom left
+    vec2<i32>(-1,  0), // left
+    vec2<i32>(-1,  1)  // top left
+);
+const VON_NEUMANN: array<vec2<i32>, 5> = array<vec2<i32>, 5>(
+    vec2<i32>( 0,  0), // middle
+    vec2<i32>( 0,  1), // top
+    vec2<i32>( 1,  0), // right
+    vec2<i32>( 0, -1), // bottom
+    vec2<i32>(-1,  0)  // left
+);
+
+@compute @workgroup_size(16,16,1)
+fn main_moore(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let pos = vec2<i32>(i32(global_id.x), i32(global_id.y));
+
+    if !pos_in_grid(pos) {
+        return;
+    }
+
+    var index = 0u;
+    var place = 1u;
+    for (var i = 0; i < 9; i++) {
+        index += state_at(pos + MOORE[i]) * place;
+        place *= grid.states;
+    }
+    next_state[pos_to_index(pos)] = lookup(index);
+}
+
+@compute @workgroup_size(16,16,1)
+fn main_von_neumann(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let pos = vec2<i32>(i32(global_id.x), i32(global_id.y));
+
+    if !pos_in_grid(pos) {
+        return;
+    }
+
+    var index = 0u;
+    var place = 1u;
+    for (var i = 0; i < 5; i++) {
+        index += state_at(pos + VON_NEUMANN[i]) * place;
+        place *= grid.states;
+    }
+    next_state[pos_to_index(pos)] = lookup(index);
+}
+
+fn lookup(index: u32) -> u32 {
+    return (rule_table[index / 4u] >> ((index % 4u) * 8u)) & 0xffu;
+}
+
+fn pos_to_index(pos: vec2<i32>) -> u32 {
+    return u32(pos.x + pos.y * i32(grid.cols));
+}
+
+fn pos_in_grid(pos:vec2<i32>) -> bool {
+    return pos.x >= 0 &&
+        pos.x < i32(grid.cols) &&
+        pos.y >= 0 &&
+        pos.y < i32(grid.rows);
+}
+
+// cells off the edge of a bounded grid are in state 0
+fn state_at(pos: vec2<i32>) -> u32 {
+    var p = pos;
+    if grid.wrap == 1u {
+        let dims = vec2<i32>(i32(grid.cols), i32(grid.rows));
+        p = (p + dims) % dims;
+    }
+    if !pos_in_grid(p) {
+        return 0u;
+    }
+    return current_state[pos_to_index(p)];
+}
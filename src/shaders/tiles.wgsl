@@ -0,0 +1,211 @@
+struct GridInfo {
+    rows: u32,
+    cols: u32,
+    // 1 if the edges wrap around
+    wrap: u32,
+    // 2 for Life-like rules, more for Generations rules
+    states: u32,
+    // Larger than Life rules, see ltl.wgsl. range is also how far one
+    // dimensional rules reach along their row
+    range: u32,
+    shape: u32,
+    include_middle: u32,
+    birth_min: u32,
+    birth_max: u32,
+    survival_min: u32,
+    survival_max: u32,
+    // 1 in continuous mode, where states are f32 bits
+    continuous: u32,
+};
+@group(0)@binding(0)
+var<uniform> grid: GridInfo;
+
+// 1 if a cell in state 0 or 1 is alive next generation, indexed by its
+// neighbourhood, see compute.wgsl
+@group(0)@binding(1)
+var<storage, read> rule_table: array<u32, 512>;
+
+const TILE_SIZE: i32 = 32;
+const TILE_CELLS: u32 = 1024u;
+// tiles with live cells this close to an edge get a neighbour on that side
+const MARGIN: i32 = 8;
+const EMPTY: u32 = 0xffffffffu;
+// flag bits, the edges and corners follow on in the order of `neighbours`
+const ALIVE: u32 = 1u;
+
+struct TileInfo {
+    // the grid is covered by the first tiles, a row of this many at a time
+    grid_tiles_x: u32,
+    // length of tile_list
+    count: u32,
+    _pad0: u32,
+    _pad1: u32,
+};
+
+// slots of the tiles to the N, NE, E, SE, S, SW, W and NW, or EMPTY
+struct Tile {
+    neighbours: array<u32, 8>,
+};
+
+@group(1)@binding(0)
+var<uniform> info: TileInfo;
+@group(1)@binding(1)
+var<storage, read> tiles: array<Tile>;
+// slots of the tiles being stepped
+@group(1)@binding(2)
+var<storage, read> tile_list: array<u32>;
+@group(1)@binding(3)
+var<storage, read_write> current_cells: array<u32>;
+@group(1)@binding(4)
+var<storage, read_write> next_cells: array<u32>;
+@group(1)@binding(5)
+var<storage, read_write> flags: array<atomic<u32>>;
+
+// the grid on screen
+@group(2)@binding(0)
+var<storage, read_write> current_state: array<u32>;
+@group(2)@binding(1)
+var<storage, read_write> next_state: array<u32>;
+
+
+// row 0 is drawn at the bottom, so up is +y
+const OFFSETS: array<vec2<i32>, 9> = array<vec2<i32>, 9>(
+    vec2<i32>(-1,  1), // top left
+    vec2<i32>( 0,  1), // top
+    vec2<i32>( 1,  1), // top right
+    vec2<i32>(-1,  0), // left
+    vec2<i32>( 0,  0), // middle
+    vec2<i32>( 1,  0), // right
+    vec2<i32>(-1, -1), // bottom left
+    vec2<i32>( 0, -1), // bottom
+    vec2<i32>( 1, -1)  // bottom right
+);
+
+// copies the grid into the tiles under it, so painting and loading patterns
+// reach the tiles
+@compute @workgroup_size(16,16,1)
+fn load_grid(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let pos = vec2<i32>(i32(global_id.x), i32(global_id.y));
+    if !pos_in_grid(pos) {
+        return;
+    }
+    current_cells[grid_cell_index(pos)] = current_state[pos_to_index(pos)];
+}
+
+// one workgroup for each 16x16 quarter of each tile in the list
+@compute @workgroup_size(16,16,1)
+fn step_tiles(
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+) {
+    if workgroup_id.x >= info.count {
+        return;
+    }
+    let slot = tile_list[workgroup_id.x];
+    let quarter = vec2<i32>(i32(workgroup_id.y & 1u), i32(workgroup_id.y >> 1u)) * 16;
+    let pos = quarter + vec2<i32>(local_id.xy);
+
+    var neighbourhood: u32 = 0u;
+    for (var i: i32 = 0; i < 9; i=i+1) {
+        neighbourhood = (neighbourhood << 1u) | is_alive(slot, pos + OFFSETS[i]);
+    }
+
+    let index = slot * TILE_CELLS + u32(pos.x + pos.y * TILE_SIZE);
+    let state = current_cells[index];
+    var next: u32 = 0u;
+    if state <= 1u {
+        if rule_table[neighbourhood] == 1u {
+            next = 1u;
+        } else if state == 1u {
+            // start decaying, or die straight away in a 2 state rule
+            next = 2u % grid.states;
+        }
+    } else {
+        // decaying cells count down to dead whatever their neighbours are
+        next = (state + 1u) % grid.states;
+    }
+    next_cells[index] = next;
+
+    if next != 0u {
+        atomicOr(&flags[slot], ALIVE | edge_flags(pos));
+    }
+}
+
+// copies the tiles under the grid back into it to be drawn
+@compute @workgroup_size(16,16,1)
+fn store_grid(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let pos = vec2<i32>(i32(global_id.x), i32(global_id.y));
+    if !pos_in_grid(pos) {
+        return;
+    }
+    next_state[pos_to_index(pos)] = next_cells[grid_cell_index(pos)];
+}
+
+// the flags of the edges and corners `pos` is within MARGIN of
+fn edge_flags(pos: vec2<i32>) -> u32 {
+    let north = pos.y >= TILE_SIZE - MARGIN;
+    let east = pos.x >= TILE_SIZE - MARGIN;
+    let south = pos.y < MARGIN;
+    let west = pos.x < MARGIN;
+    var bits: u32 = 0u;
+    bits |= select(0u, 1u << 1u, north);
+    bits |= select(0u, 1u << 2u, north && east);
+    bits |= select(0u, 1u << 3u, east);
+    bits |= select(0u, 1u << 4u, south && east);
+    bits |= select(0u, 1u << 5u, south);
+    bits |= select(0u, 1u << 6u, south && west);
+    bits |= select(0u, 1u << 7u, west);
+    bits |= select(0u, 1u << 8u, north && west);
+    return bits;
+}
+
+// whether the cell at `pos` relative to the tile in `slot` is alive, looking
+// in the neighbouring tile if it's off the edge. Missing tiles are all dead
+fn is_alive(slot: u32, pos: vec2<i32>) -> u32 {
+    let step = vec2<i32>(
+        select(0, -1, pos.x < 0) + select(0, 1, pos.x >= TILE_SIZE),
+        select(0, -1, pos.y < 0) + select(0, 1, pos.y >= TILE_SIZE),
+    );
+    var tile = slot;
+    if step.x != 0 || step.y != 0 {
+        tile = tiles[slot].neighbours[direction(step)];
+        if tile == EMPTY {
+            return 0u;
+        }
+    }
+    let local = (pos + TILE_SIZE) % TILE_SIZE;
+    if current_cells[tile * TILE_CELLS + u32(local.x + local.y * TILE_SIZE)] == 1u {
+        return 1u;
+    }
+    return 0u;
+}
+
+// index into `neighbours` of the tile one step away
+fn direction(step: vec2<i32>) -> u32 {
+    if step.x == 0 {
+        return select(4u, 0u, step.y > 0);
+    }
+    if step.x > 0 {
+        return u32(2 - step.y);
+    }
+    return u32(6 + step.y);
+}
+
+// the grid's bottom left cell is the bottom left of slot 0
+fn grid_cell_index(pos: vec2<i32>) -> u32 {
+    let tile = vec2<u32>(pos / TILE_SIZE);
+    let local = pos % TILE_SIZE;
+    let slot = tile.x + tile.y * info.grid_tiles_x;
+    return slot * TILE_CELLS + u32(local.x + local.y * TILE_SIZE);
+}
+
+fn pos_to_index(pos: vec2<i32>) -> u32 {
+    return u32(pos.x + pos.y * i32(grid.cols));
+}
+
+fn pos_in_grid(pos:vec2<i32>) -> bool {
+    return pos.x >= 0 &&
+        pos.x < i32(grid.cols) &&
+        pos.y >= 0 &&
+        pos.y < i32(grid.rows);
+}
@@ -0,0 +1,76 @@
+// Turmites, see turmite.rs. They move one after another in a single
+// invocation, so two on the same cell take turns instead of racing.
+struct TurmiteInfo {
+    rows: u32,
+    cols: u32,
+    // 1 if the edges wrap around
+    wrap: u32,
+    // how many turmites there are, halted or not
+    count: u32,
+    // how many cell states the turmites read and write
+    colours: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+struct Turmite {
+    x: i32,
+    y: i32,
+    // 0 up, 1 right, 2 down, 3 left
+    direction: u32,
+    state: u32,
+};
+
+@group(0)@binding(0)
+var<uniform> info: TurmiteInfo;
+// write | quarter turns clockwise << 8 | next state << 16, for each state
+// then each colour
+@group(0)@binding(1)
+var<storage, read> transitions: array<u32>;
+@group(0)@binding(2)
+var<storage, read_write> turmites: array<Turmite>;
+
+// the grid being written this step, after the rule if it ran
+@group(1)@binding(1)
+var<storage, read_write> next_state: array<u32>;
+
+// a turmite that's walked off a bounded grid
+const HALTED: u32 = 0xffffffffu;
+
+// row 0 is drawn at the bottom, so up is +y
+const STEPS: array<vec2<i32>, 4> = array<vec2<i32>, 4>(
+    vec2<i32>( 0,  1), // up
+    vec2<i32>( 1,  0), // right
+    vec2<i32>( 0, -1), // down
+    vec2<i32>(-1,  0)  // left
+);
+
+@compute @workgroup_size(1,1,1)
+fn main() {
+    let dims = vec2<i32>(i32(info.cols), i32(info.rows));
+    for (var i = 0u; i < info.count; i++) {
+        var turmite = turmites[i];
+        if turmite.state == HALTED {
+            continue;
+        }
+
+        let index = u32(turmite.x + turmite.y * dims.x);
+        let colour = next_state[index] % info.colours;
+        let transition = transitions[turmite.state * info.colours + colour];
+        next_state[index] = transition & 0xffu;
+        turmite.direction = (turmite.direction + ((transition >> 8u) & 0xffu)) % 4u;
+        turmite.state = transition >> 16u;
+
+        var pos = vec2<i32>(turmite.x, turmite.y) + STEPS[turmite.direction];
+        if info.wrap == 1u {
+            pos = (pos + dims) % dims;
+        }
+        if pos.x < 0 || pos.x >= dims.x || pos.y < 0 || pos.y >= dims.y {
+            turmite.state = HALTED;
+        }
+        turmite.x = pos.x;
+        turmite.y = pos.y;
+        turmites[i] = turmite;
+    }
+}
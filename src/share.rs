@@ -0,0 +1,332 @@
+// Links that reproduce the grid on the web build. Everything lives in the
+// URL's hash, so none of it is sent to the server:
+//
+//   #rule=B3/S23&grid=120x200&topology=torus&fps=20&cells=eJxLyk9R...
+//
+// | key        | contents                                        |
+// |------------|-------------------------------------------------|
+// | `rule`     | the rule as it's written in the config          |
+// | `grid`     | rows x cols                                     |
+// | `topology` | `bounded`, `torus` or `unbounded`               |
+// | `fps`      | generations a second                            |
+// | `cells`    | the whole grid as RLE, zlib compressed and then |
+// |            | written in unpadded base64url (optional)        |
+//
+// The RLE covers the whole grid rather than just the live cells so the
+// pattern comes back where it was. Links to big or busy grids can be too
+// long to pass around, so those are shared without `cells`.
+
+use crate::{
+    config::{AppConfig, Topology},
+    rle::Pattern,
+    rule::Rule,
+};
+use anyhow::{Context, bail, ensure};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use flate2::{Compression, read::ZlibDecoder, write::ZlibEncoder};
+use std::{
+    io::{Read, Write},
+    time::Duration,
+};
+
+/// Longest link that's shared with its cells. Browsers cope with far
+/// longer ones, but chat apps and servers tend to cut them off.
+pub const MAX_URL_LEN: usize = 8000;
+/// most cells a link can ask for, so a bad link can't use up the memory
+const MAX_CELLS: usize = 1 << 24;
+/// longest RLE a link's cells are decompressed into
+const MAX_RLE_LEN: u64 = 1 << 26;
+
+#[derive(Debug, Clone)]
+pub struct SharedState {
+    pub rule: Rule,
+    pub rows: usize,
+    pub cols: usize,
+    pub topology: Topology,
+    pub fps: usize,
+    /// every cell, `x + cols * y` from the bottom left, if they're shared
+    pub cells: Option<Vec<u32>>,
+}
+
+impl SharedState {
+    pub fn new(config: &AppConfig, cells: Option<Vec<u32>>) -> Self {
+        Self {
+            rule: config.rule.clone(),
+            rows: config.rows,
+            cols: config.cols,
+            topology: config.topology,
+            fps: config.fps,
+            cells,
+        }
+    }
+
+    /// The hash of a link to this state, without the `#`
+    pub fn to_hash(&self) -> anyhow::Result<String> {
+        let rule = self.rule.to_string();
+        // rule tables opened from a file only have their name
+        ensure!(
+            self.rule.can_be_found()
+                && rule.parse::<Rule>().is_ok_and(|parsed| parsed == self.rule),
+            "the rule {rule} can't be written in a link"
+        );
+        ensure!(
+            self.topology != Topology::Unbounded || self.cells.is_none(),
+            "links only keep the cells on the grid, so an unbounded universe's cells can't be shared"
+        );
+        let topology = match self.topology {
+            Topology::Bounded => "bounded",
+            Topology::Torus => "torus",
+            Topology::Unbounded => "unbounded",
+        };
+        let mut hash = format!(
+            "rule={}&grid={}x{}&topology={topology}&fps={}",
+            escape(&rule),
+            self.rows,
+            self.cols,
+            self.fps
+        );
+        if let Some(cells) = &self.cells {
+            hash.push_str("&cells=");
+            hash.push_str(&encode_cells(cells, self.rows, self.cols)?);
+        }
+        Ok(hash)
+    }
+
+    /// Reads the hash of a link, with or without its `#`
+    pub fn from_hash(hash: &str) -> anyhow::Result<Self> {
+        let hash = hash.strip_prefix('#').unwrap_or(hash);
+        let value = |key: &str| {
+            hash.split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value)
+        };
+
+        let rule_text = unescape(value("rule").context("the link has no rule")?)?;
+        let rule: Rule = rule_text
+            .parse()
+            .with_context(|| format!("the link's rule {rule_text:?} doesn't parse"))?;
+        let grid = value("grid").context("the link has no grid size")?;
+        let (rows, cols): (usize, usize) = grid
+            .split_once('x')
+            .and_then(|(rows, cols)| Some((rows.parse().ok()?, cols.parse().ok()?)))
+            .with_context(|| format!("the link's grid should look like 120x200, not {grid:?}"))?;
+        ensure!(
+            rows > 0 && cols > 0 && rows.checked_mul(cols).is_some_and(|n| n <= MAX_CELLS),
+            "a {rows}x{cols} grid is too big to open from a link"
+        );
+        let topology = match value("topology") {
+            None | Some("bounded") => Topology::Bounded,
+            Some("torus") => Topology::Torus,
+            Some("unbounded") => Topology::Unbounded,
+            Some(other) => bail!("the link's topology {other:?} isn't bounded, torus or unbounded"),
+        };
+        let fps = match value("fps") {
+            Some(fps) => fps
+                .parse()
+                .with_context(|| format!("the link's fps {fps:?} isn't a number"))?,
+            None => 20,
+        };
+        let cells = value("cells")
+            .map(|cells| decode_cells(cells, rows, cols))
+            .transpose()?;
+        Ok(Self {
+            rule,
+            rows,
+            cols,
+            topology,
+            fps,
+            cells,
+        })
+    }
+
+    /// Makes `config` match the link's grid, rule and speed
+    pub fn apply_to_config(&self, config: &mut AppConfig) {
+        config.topology = self.topology;
+        config.set_rule(self.rule.clone());
+        config.resize_grid(self.rows, self.cols);
+        config.fps = self.fps.clamp(1, 60);
+        config.frame_duration = Duration::from_nanos(1_000_000_000 / config.fps as u64);
+    }
+}
+
+fn encode_cells(cells: &[u32], rows: usize, cols: usize) -> anyhow::Result<String> {
+    // the grid's bottom row is the pattern's last
+    let pattern = Pattern {
+        width: cols,
+        height: rows,
+        rule: None,
+        cells: cells.chunks(cols).rev().flatten().copied().collect(),
+    };
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(pattern.to_rle().as_bytes())?;
+    Ok(BASE64_URL_SAFE_NO_PAD.encode(encoder.finish()?))
+}
+
+fn decode_cells(encoded: &str, rows: usize, cols: usize) -> anyhow::Result<Vec<u32>> {
+    let compressed = BASE64_URL_SAFE_NO_PAD
+        .decode(encoded)
+        .context("the link's cells aren't base64url")?;
+    let mut rle = String::new();
+    // one more byte than allowed is enough to tell there are too many
+    ZlibDecoder::new(compressed.as_slice())
+        .take(MAX_RLE_LEN + 1)
+        .read_to_string(&mut rle)
+        .context("the link's cells aren't compressed RLE")?;
+    ensure!(
+        rle.len() as u64 <= MAX_RLE_LEN,
+        "the link's cells are too big to open"
+    );
+    Pattern::parse(&rle)?.to_grid(rows, cols)
+}
+
+/// Percent encodes anything in `text` that could be mistaken for part of
+/// the hash
+fn escape(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~'
+            | b'/'
+            | b','
+            | b':'
+            | b'+' => char::from(byte).to_string(),
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn unescape(text: &str) -> anyhow::Result<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .with_context(|| format!("{text:?} has a bad % escape"))?;
+            bytes.push(hex);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_table::RuleTable;
+
+    fn shared(rule: &str, cells: Option<Vec<u32>>) -> SharedState {
+        SharedState {
+            rule: rule.parse().unwrap(),
+            rows: 3,
+            cols: 4,
+            topology: Topology::Torus,
+            fps: 30,
+            cells,
+        }
+    }
+
+    #[test]
+    fn links_round_trip() {
+        let cells = vec![0, 1, 1, 0, 0, 0, 0, 1, 1, 1, 0, 0];
+        for rule in [
+            "B3/S23",
+            "B2-a/S12/C4",
+            "R5,C0,M1,S34..58,B34..45,NM",
+            "WireWorld",
+        ] {
+            let state = shared(rule, Some(cells.clone()));
+            let hash = state.to_hash().unwrap();
+            assert!(hash.starts_with("rule="), "{hash}");
+            let loaded = SharedState::from_hash(&format!("#{hash}")).unwrap();
+            assert_eq!(loaded.rule, state.rule);
+            assert_eq!((loaded.rows, loaded.cols), (3, 4));
+            assert_eq!(loaded.topology, Topology::Torus);
+            assert_eq!(loaded.fps, 30);
+            assert_eq!(loaded.cells, Some(cells.clone()));
+        }
+    }
+
+    #[test]
+    fn links_can_leave_things_out() {
+        let loaded = SharedState::from_hash("rule=B36%2FS23&grid=10x20").unwrap();
+        assert_eq!(loaded.rule, "B36/S23".parse().unwrap());
+        assert_eq!(loaded.topology, Topology::Bounded);
+        assert_eq!(loaded.fps, 20);
+        assert_eq!(loaded.cells, None);
+        let hash = shared("B3/S23", None).to_hash().unwrap();
+        assert!(!hash.contains("cells"), "{hash}");
+    }
+
+    #[test]
+    fn rule_tables_that_cant_be_found_arent_shared() {
+        let text = include_str!("rules/WireWorld.rule").replace("WireWorld", "NotWireWorld");
+        let mut state = shared("B3/S23", None);
+        state.rule = RuleTable::parse(&text, "NotWireWorld").unwrap().into();
+        assert!(state.to_hash().is_err());
+    }
+
+    #[test]
+    fn unbounded_universes_are_shared_without_their_cells() {
+        let mut state = shared("B3/S23", Some(vec![1; 12]));
+        state.topology = Topology::Unbounded;
+        assert!(state.to_hash().is_err());
+        state.cells = None;
+        let loaded = SharedState::from_hash(&state.to_hash().unwrap()).unwrap();
+        assert_eq!(loaded.topology, Topology::Unbounded);
+    }
+
+    #[test]
+    fn escapes_round_trip() {
+        let text = "B3/S23 & more=#%";
+        assert_eq!(unescape(&escape(text)).unwrap(), text);
+        assert!(!escape(text).contains(['&', '=', '#', ' ']));
+        assert!(unescape("%zz").is_err());
+        assert!(unescape("%4").is_err());
+    }
+
+    #[test]
+    fn oversized_and_corrupt_links_are_rejected() {
+        for hash in [
+            "grid=10x10",
+            "rule=B3/S23",
+            "rule=B3/S23&grid=10",
+            "rule=B3/S23&grid=0x10",
+            "rule=B3/S23&grid=100000x100000",
+            "rule=B3/S23&grid=18446744073709551615x2",
+            "rule=B9/S23&grid=10x10",
+            "rule=B3/S23&grid=10x10&topology=sphere",
+            "rule=B3/S23&grid=10x10&fps=fast",
+            "rule=B3/S23&grid=10x10&cells=not*base64",
+            "rule=B3/S23&grid=10x10&cells=AAAA",
+        ] {
+            assert!(SharedState::from_hash(hash).is_err(), "{hash}");
+        }
+        // cells that don't fit the grid
+        let mut state = shared("B3/S23", Some(vec![1; 12]));
+        let hash = state.to_hash().unwrap().replace("grid=3x4", "grid=2x2");
+        assert!(SharedState::from_hash(&hash).is_err(), "{hash}");
+        state.cells = None;
+        assert!(SharedState::from_hash(&state.to_hash().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn cells_that_decompress_too_far_are_rejected() {
+        let rle = format!("x = 1, y = 1\n{}o!", "0".repeat(MAX_RLE_LEN as usize));
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(rle.as_bytes()).unwrap();
+        let cells = BASE64_URL_SAFE_NO_PAD.encode(encoder.finish().unwrap());
+        assert!(decode_cells(&cells, 1, 1).is_err());
+    }
+}
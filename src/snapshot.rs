@@ -0,0 +1,421 @@
+// Session snapshots: everything needed to pick a run back up where it was
+// left off.
+//
+// File layout (all integers little endian):
+//
+// | bytes | contents                                    |
+// |-------|---------------------------------------------|
+// | 8     | magic `GOLSNAP\0`                           |
+// | 4     | format version                              |
+// | 4     | rows                                        |
+// | 4     | cols                                        |
+// | 1     | topology (0 bounded, 1 torus, 2 unbounded)  |
+// | 8     | generation                                  |
+// | 2     | length of the rule string (version 3 on)    |
+// | n     | rule string, e.g. `B3/S23` (version 2 on)   |
+// | 1     | mode (0 discrete, 1 continuous, version 4)  |
+// | rest  | zlib compressed cell states                 |
+//
+// Discrete states take a byte each, and continuous ones the four bytes of
+// their f32. Version 1 snapshots have no rule and are loaded as Conway's
+// Life, version 2 gives the rule's length in a single byte, and snapshots
+// before version 4 are all discrete. Unbounded universes
+// aren't saved, as their cells off the grid would be lost, but older
+// snapshots of them still load.
+
+use crate::{
+    config::{AppConfig, SimulationMode, Topology},
+    rule::Rule,
+    rule_table::RULES_DIR,
+};
+use anyhow::{Context, bail, ensure};
+use flate2::{Compression, read::ZlibDecoder, write::ZlibEncoder};
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 8] = b"GOLSNAP\0";
+const VERSION: u32 = 4;
+/// length of the fixed part of the header, before the rule
+const HEADER_LEN: usize = 8 + 4 + 4 + 4 + 1 + 8;
+/// most cells a snapshot can hold, so a corrupt file can't use up the memory
+const MAX_CELLS: usize = 1 << 26;
+
+/// Where snapshots go on desktop when no other path is given
+pub const DEFAULT_SNAPSHOT_PATH: &str = "snapshot.gols";
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub rows: usize,
+    pub cols: usize,
+    pub topology: Topology,
+    pub rule: Rule,
+    pub mode: SimulationMode,
+    pub generation: u64,
+    /// whole number states, or f32 bits in continuous mode
+    pub cells: Vec<u32>,
+}
+
+impl Snapshot {
+    pub fn new(config: &AppConfig, generation: u64, cells: Vec<u32>) -> Self {
+        Self {
+            rows: config.rows,
+            cols: config.cols,
+            topology: config.topology,
+            rule: config.rule.clone(),
+            mode: config.mode,
+            generation,
+            cells,
+        }
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        ensure!(
+            self.topology != Topology::Unbounded,
+            "snapshots only keep the cells on the grid, so unbounded universes can't be saved"
+        );
+        ensure!(
+            self.rule.can_be_found(),
+            "snapshots only keep the rule's name, so {} can't be saved unless it's \
+             built in or in the {RULES_DIR} folder",
+            self.rule
+        );
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.rows as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.cols as u32).to_le_bytes());
+        bytes.push(match self.topology {
+            Topology::Bounded => 0,
+            Topology::Torus => 1,
+            Topology::Unbounded => 2,
+        });
+        bytes.extend_from_slice(&self.generation.to_le_bytes());
+        let rule = self.rule.to_string();
+        let rule_len = u16::try_from(rule.len()).context("the rule is too long to save")?;
+        bytes.extend_from_slice(&rule_len.to_le_bytes());
+        bytes.extend_from_slice(rule.as_bytes());
+        bytes.push(match self.mode {
+            SimulationMode::Discrete => 0,
+            SimulationMode::Continuous => 1,
+        });
+
+        let cells: Vec<u8> = match self.mode {
+            SimulationMode::Discrete => {
+                ensure!(
+                    self.cells.iter().all(|&state| state <= u8::MAX as u32),
+                    "snapshots only keep states up to {}",
+                    u8::MAX
+                );
+                self.cells.iter().map(|&state| state as u8).collect()
+            }
+            SimulationMode::Continuous => self
+                .cells
+                .iter()
+                .flat_map(|&bits| bits.to_le_bytes())
+                .collect(),
+        };
+        let mut encoder = ZlibEncoder::new(bytes, Compression::default());
+        encoder.write_all(&cells)?;
+        Ok(encoder.finish()?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        ensure!(
+            bytes.len() >= HEADER_LEN && &bytes[..8] == MAGIC,
+            "not a snapshot file"
+        );
+        let read_u32 = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+
+        let version = read_u32(8);
+        ensure!(
+            (1..=VERSION).contains(&version),
+            "unsupported snapshot version {version}"
+        );
+        let rows = read_u32(12) as usize;
+        let cols = read_u32(16) as usize;
+        let num_cells = rows
+            .checked_mul(cols)
+            .filter(|&num_cells| num_cells <= MAX_CELLS)
+            .with_context(|| format!("a {rows}x{cols} snapshot is too big to load"))?;
+        let topology = match bytes[20] {
+            0 => Topology::Bounded,
+            1 => Topology::Torus,
+            2 => Topology::Unbounded,
+            other => bail!("unknown topology {other}"),
+        };
+        let generation = u64::from_le_bytes(bytes[21..29].try_into().unwrap());
+
+        let (rule, cells_start) = if version >= 2 {
+            let (rule_len, rule_start) = if version >= 3 {
+                let len = bytes
+                    .get(HEADER_LEN..HEADER_LEN + 2)
+                    .context("snapshot is truncated")?;
+                (
+                    u16::from_le_bytes([len[0], len[1]]) as usize,
+                    HEADER_LEN + 2,
+                )
+            } else {
+                let len = *bytes.get(HEADER_LEN).context("snapshot is truncated")?;
+                (len as usize, HEADER_LEN + 1)
+            };
+            let rule = bytes
+                .get(rule_start..rule_start + rule_len)
+                .context("snapshot is truncated")?;
+            (std::str::from_utf8(rule)?.parse()?, rule_start + rule_len)
+        } else {
+            (Rule::default(), HEADER_LEN)
+        };
+        let (mode, cells_start) = if version >= 4 {
+            let mode = match bytes.get(cells_start).context("snapshot is truncated")? {
+                0 => SimulationMode::Discrete,
+                1 => SimulationMode::Continuous,
+                other => bail!("unknown mode {other}"),
+            };
+            (mode, cells_start + 1)
+        } else {
+            (SimulationMode::Discrete, cells_start)
+        };
+        let cell_len = match mode {
+            SimulationMode::Discrete => 1,
+            SimulationMode::Continuous => 4,
+        };
+
+        let expected = num_cells * cell_len;
+        let mut cell_bytes = Vec::with_capacity(expected);
+        // one more than expected is enough to tell there are too many
+        ZlibDecoder::new(&bytes[cells_start..])
+            .take(expected as u64 + 1)
+            .read_to_end(&mut cell_bytes)
+            .context("corrupt cell data")?;
+        ensure!(
+            cell_bytes.len() == expected,
+            "expected {num_cells} cells but found {}",
+            match cell_bytes.len() {
+                found if found > expected => "more".to_string(),
+                found => (found / cell_len).to_string(),
+            }
+        );
+        let cells = match mode {
+            SimulationMode::Discrete => cell_bytes.into_iter().map(u32::from).collect(),
+            SimulationMode::Continuous => cell_bytes
+                .chunks_exact(4)
+                .map(|bits| u32::from_le_bytes(bits.try_into().unwrap()))
+                .collect(),
+        };
+
+        Ok(Self {
+            rows,
+            cols,
+            topology,
+            rule,
+            mode,
+            generation,
+            cells,
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes =
+            std::fs::read(path).with_context(|| format!("unable to read {}", path.display()))?;
+        Snapshot::from_bytes(&bytes)
+    }
+
+    /// Makes `config` match the snapshot's grid, rule and mode
+    pub fn apply_to_config(&self, config: &mut AppConfig) {
+        config.topology = self.topology;
+        config.mode = self.mode;
+        config.set_rule(self.rule.clone());
+        config.resize_grid(self.rows, self.cols);
+    }
+}
+
+/// Where the web build keeps a snapshot in localStorage
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY: &str = "game-of-life-snapshot";
+
+#[cfg(target_arch = "wasm32")]
+impl Snapshot {
+    pub fn save_to_local_storage(&self) -> anyhow::Result<()> {
+        use base64::{Engine, prelude::BASE64_STANDARD};
+
+        // localStorage only holds strings
+        let encoded = BASE64_STANDARD.encode(self.to_bytes()?);
+        crate::web_files::local_storage_set(STORAGE_KEY, &encoded)
+            .map_err(|e| anyhow::anyhow!("unable to write to localStorage: {e:?}"))
+    }
+
+    pub fn load_from_local_storage() -> anyhow::Result<Self> {
+        use base64::{Engine, prelude::BASE64_STANDARD};
+
+        let encoded = crate::web_files::local_storage_get(STORAGE_KEY)
+            .map_err(|e| anyhow::anyhow!("unable to read from localStorage: {e:?}"))?
+            .context("no snapshot has been saved in this browser")?;
+        Snapshot::from_bytes(&BASE64_STANDARD.decode(encoded)?)
+    }
+
+    pub fn download(&self) -> anyhow::Result<()> {
+        crate::web_files::download_bytes(
+            DEFAULT_SNAPSHOT_PATH,
+            &self.to_bytes()?,
+            "application/octet-stream",
+        )
+        .map_err(|e| anyhow::anyhow!("unable to download snapshot: {e:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_table::RuleTable;
+
+    fn snapshot(rule: &str) -> Snapshot {
+        Snapshot {
+            rows: 3,
+            cols: 4,
+            topology: Topology::Torus,
+            rule: rule.parse().unwrap(),
+            mode: SimulationMode::Discrete,
+            generation: 1234,
+            cells: vec![0, 1, 2, 0, 0, 0, 1, 1, 2, 0, 0, 1],
+        }
+    }
+
+    /// A header with `version` and no rule or cells
+    fn header(version: u32, rows: u32, cols: u32, topology: u8) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(&rows.to_le_bytes());
+        bytes.extend_from_slice(&cols.to_le_bytes());
+        bytes.push(topology);
+        bytes.extend_from_slice(&7u64.to_le_bytes());
+        bytes
+    }
+
+    fn compressed(cells: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(cells).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn snapshots_round_trip() {
+        for rule in ["B2/S/C3", "WireWorld"] {
+            let saved = snapshot(rule);
+            let loaded = Snapshot::from_bytes(&saved.to_bytes().unwrap()).unwrap();
+            assert_eq!((loaded.rows, loaded.cols), (3, 4));
+            assert_eq!(loaded.topology, Topology::Torus);
+            assert_eq!(loaded.rule, saved.rule);
+            assert_eq!(loaded.mode, SimulationMode::Discrete);
+            assert_eq!(loaded.generation, 1234);
+            assert_eq!(loaded.cells, saved.cells);
+        }
+    }
+
+    #[test]
+    fn continuous_snapshots_keep_their_values() {
+        let mut saved = snapshot("B3/S23");
+        saved.mode = SimulationMode::Continuous;
+        saved.cells = [0.0, 0.25, 1.0 / 3.0, 1.0]
+            .iter()
+            .cycle()
+            .take(12)
+            .map(|value: &f32| value.to_bits())
+            .collect();
+        let loaded = Snapshot::from_bytes(&saved.to_bytes().unwrap()).unwrap();
+        assert_eq!(loaded.mode, SimulationMode::Continuous);
+        assert_eq!(loaded.cells, saved.cells);
+
+        // discrete snapshots can't hold f32 bits
+        saved.mode = SimulationMode::Discrete;
+        assert!(saved.to_bytes().is_err());
+    }
+
+    #[test]
+    fn older_versions_still_load() {
+        let mut version_1 = header(1, 1, 2, 0);
+        version_1.extend(compressed(&[1, 0]));
+        let loaded = Snapshot::from_bytes(&version_1).unwrap();
+        assert_eq!(loaded.rule, Rule::default());
+        assert_eq!(loaded.mode, SimulationMode::Discrete);
+        assert_eq!(loaded.cells, [1, 0]);
+
+        let mut version_2 = header(2, 1, 2, 1);
+        version_2.push(7);
+        version_2.extend_from_slice(b"B36/S23");
+        version_2.extend(compressed(&[0, 1]));
+        let loaded = Snapshot::from_bytes(&version_2).unwrap();
+        assert_eq!(loaded.rule, "B36/S23".parse().unwrap());
+        assert_eq!(loaded.topology, Topology::Torus);
+    }
+
+    #[test]
+    fn rule_tables_that_cant_be_found_arent_saved() {
+        let text = include_str!("rules/WireWorld.rule").replace("WireWorld", "NotWireWorld");
+        let mut saved = snapshot("B3/S23");
+        saved.rule = RuleTable::parse(&text, "NotWireWorld").unwrap().into();
+        assert!(saved.to_bytes().is_err());
+    }
+
+    #[test]
+    fn unbounded_universes_arent_saved() {
+        let mut saved = snapshot("B3/S23");
+        saved.topology = Topology::Unbounded;
+        assert!(saved.to_bytes().is_err());
+        // but older snapshots of them still load
+        let mut bytes = header(2, 1, 2, 2);
+        bytes.push(6);
+        bytes.extend_from_slice(b"B3/S23");
+        bytes.extend(compressed(&[0, 1]));
+        assert_eq!(
+            Snapshot::from_bytes(&bytes).unwrap().topology,
+            Topology::Unbounded
+        );
+    }
+
+    #[test]
+    fn oversized_headers_are_rejected() {
+        for (rows, cols) in [(1 << 14, 1 << 14), (u32::MAX, u32::MAX)] {
+            let mut bytes = header(1, rows, cols, 0);
+            bytes.extend(compressed(&[]));
+            assert!(Snapshot::from_bytes(&bytes).is_err(), "{rows}x{cols}");
+        }
+    }
+
+    #[test]
+    fn corrupt_snapshots_are_rejected() {
+        let good = snapshot("B3/S23").to_bytes().unwrap();
+        assert!(Snapshot::from_bytes(&good[..HEADER_LEN - 1]).is_err());
+        assert!(Snapshot::from_bytes(&good[..HEADER_LEN + 4]).is_err());
+        assert!(Snapshot::from_bytes(&good[..good.len() - 4]).is_err());
+
+        let mut bad_magic = good.clone();
+        bad_magic[0] = b'X';
+        assert!(Snapshot::from_bytes(&bad_magic).is_err());
+
+        let mut future = header(VERSION + 1, 1, 1, 0);
+        future.extend(compressed(&[0]));
+        assert!(Snapshot::from_bytes(&future).is_err());
+
+        let mut bad_topology = header(1, 1, 1, 3);
+        bad_topology.extend(compressed(&[0]));
+        assert!(Snapshot::from_bytes(&bad_topology).is_err());
+
+        let mut bad_mode = header(4, 1, 1, 0);
+        bad_mode.extend_from_slice(&6u16.to_le_bytes());
+        bad_mode.extend_from_slice(b"B3/S23");
+        bad_mode.push(2);
+        bad_mode.extend(compressed(&[0]));
+        assert!(Snapshot::from_bytes(&bad_mode).is_err());
+
+        let mut too_many_cells = header(1, 1, 2, 0);
+        too_many_cells.extend(compressed(&[0, 1, 0]));
+        assert!(Snapshot::from_bytes(&too_many_cells).is_err());
+    }
+}
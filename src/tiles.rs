@@ -0,0 +1,531 @@
+// Unbounded universes, stored as 32x32 tiles.
+//
+// The tiles live in a pool of slots on the GPU, and only the ones in use are
+// stepped. The grid on screen is covered by the first tiles, which are always
+// there, and every step copies the grid into them, steps the tiles and copies
+// them back, so painting and loading patterns work as they do on a bounded
+// grid.
+//
+// Stepping a tile flags it if it has live cells, and which of its edges and
+// corners have live cells near them. Every so often the flags are read back,
+// tiles are added next to flagged edges and empty tiles are freed. Patterns
+// move at most a cell a generation, so as long as the flags in use are never
+// more than `MARGIN` generations old nothing reaches a missing tile. Reading
+// them back takes a while, so stepping waits for them when they fall behind.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    config::{AppConfig, SimulationMode, Topology},
+    readback::{self, Readback},
+    rule::RuleKind,
+};
+
+pub const TILE_SIZE: usize = 32;
+const TILE_CELLS: usize = TILE_SIZE * TILE_SIZE;
+/// tiles with live cells this close to an edge get a neighbour on that side
+const MARGIN: usize = 8;
+/// the most generations that can run before the tiles have to be updated
+pub const STEPS_BETWEEN_UPDATES: usize = MARGIN;
+/// how many tiles patterns can spread over beyond the ones under the grid
+const MAX_EXTRA_TILES: usize = 4096;
+/// marks a missing neighbour
+const EMPTY: u32 = u32::MAX;
+/// a flag bit set if the tile has live cells. The next 8 bits are set if
+/// there are live cells near each of `DIRECTIONS`
+const ALIVE: u32 = 1;
+/// offsets of the tiles to the N, NE, E, SE, S, SW, W and NW, in tiles
+const DIRECTIONS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+/// Slots of a tile's neighbours, in the order of `DIRECTIONS`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuTile {
+    neighbours: [u32; 8],
+}
+
+impl GpuTile {
+    const UNUSED: Self = Self {
+        neighbours: [EMPTY; 8],
+    };
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TileUniform {
+    /// how many tiles there are in a row of the ones covering the grid
+    grid_tiles_x: u32,
+    /// how many tiles are stepped
+    count: u32,
+    _pad: [u32; 2],
+}
+
+pub struct Tiles {
+    load_pipeline: wgpu::ComputePipeline,
+    step_pipeline: wgpu::ComputePipeline,
+    store_pipeline: wgpu::ComputePipeline,
+    uniform: TileUniform,
+    uniform_buffer: wgpu::Buffer,
+    tile_buffer: wgpu::Buffer,
+    list_buffer: wgpu::Buffer,
+    cell_buffers: [wgpu::Buffer; 2],
+    flag_buffer: wgpu::Buffer,
+    /// the first reads from cell buffer 0, to go with game state buffer A
+    bind_groups: [wgpu::BindGroup; 2],
+    map: TileMap,
+    /// the tiles under the grid, which are never freed
+    grid_tiles: u32,
+    /// the last flags read back, waiting to be used
+    flags: Arc<Mutex<Option<Readback<Vec<u32>>>>>,
+    /// the generation the flags being read back were taken at
+    reading: Option<u64>,
+    /// the generation the flags in use were taken at
+    flag_generation: u64,
+    is_full: bool,
+}
+
+impl Tiles {
+    /// Unbounded universes step Life-like rules on square cells. B0 rules
+    /// would need every tile of the plane, as empty ones don't stay empty.
+    pub fn is_active(config: &AppConfig) -> bool {
+        config.topology == Topology::Unbounded
+            && config.mode == SimulationMode::Discrete
+            && matches!(
+                config.rule.kind,
+                RuleKind::Totalistic { .. } | RuleKind::Isotropic { .. } | RuleKind::Map { .. }
+            )
+            && !config.rule.births_from_nothing()
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        config: &AppConfig,
+        compute_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+        game_state_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let grid_tiles_x = config.cols.div_ceil(TILE_SIZE);
+        let grid_tiles = grid_tiles_x * config.rows.div_ceil(TILE_SIZE);
+        let capacity = grid_tiles + MAX_EXTRA_TILES;
+
+        let uniform = TileUniform {
+            grid_tiles_x: grid_tiles_x as u32,
+            count: grid_tiles as u32,
+            _pad: [0; 2],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tile Uniform Buffer"),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let map = TileMap::new(capacity, grid_tiles_x, grid_tiles);
+        let tile_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tile Buffer"),
+            contents: bytemuck::cast_slice(&map.tiles),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let mut list = map.list();
+        list.resize(capacity, 0);
+        let list_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tile List Buffer"),
+            contents: bytemuck::cast_slice(&list),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let cell_buffer = |label| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: (capacity * TILE_CELLS * size_of::<u32>()) as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        let cell_buffers = [cell_buffer("Tile Cells A"), cell_buffer("Tile Cells B")];
+        let flag_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tile Flag Buffer"),
+            size: (capacity * size_of::<u32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let read_only = wgpu::BufferBindingType::Storage { read_only: true };
+        let read_write = wgpu::BufferBindingType::Storage { read_only: false };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Tile Bind Group Layout"),
+            entries: &[
+                buffer_entry(0, wgpu::BufferBindingType::Uniform),
+                buffer_entry(1, read_only),
+                buffer_entry(2, read_only),
+                buffer_entry(3, read_write),
+                buffer_entry(4, read_write),
+                buffer_entry(5, read_write),
+            ],
+        });
+        let bind_group = |label, current: &wgpu::Buffer, next: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: tile_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: list_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: current.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: next.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: flag_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        let bind_groups = [
+            bind_group("Tile Bind Group A", &cell_buffers[0], &cell_buffers[1]),
+            bind_group("Tile Bind Group B", &cell_buffers[1], &cell_buffers[0]),
+        ];
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Tile Pipeline Layout"),
+            bind_group_layouts: &[
+                compute_uniform_bind_group_layout,
+                &bind_group_layout,
+                game_state_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Tile shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/tiles.wgsl").into()),
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Tile Pipeline"),
+                layout: Some(&pipeline_layout),
+                cache: None,
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            })
+        };
+
+        Self {
+            load_pipeline: pipeline("load_grid"),
+            step_pipeline: pipeline("step_tiles"),
+            store_pipeline: pipeline("store_grid"),
+            uniform,
+            uniform_buffer,
+            tile_buffer,
+            list_buffer,
+            cell_buffers,
+            flag_buffer,
+            bind_groups,
+            map,
+            grid_tiles: grid_tiles as u32,
+            flags: Arc::default(),
+            reading: None,
+            flag_generation: 0,
+            is_full: false,
+        }
+    }
+
+    /// how many tiles are in use
+    pub fn count(&self) -> usize {
+        self.map.slots.len()
+    }
+
+    /// Frees every tile beyond the grid and clears the ones under it, ready
+    /// for the grid to be copied in on the next step as generation
+    /// `generation`
+    pub fn reset(&mut self, queue: &wgpu::Queue, generation: u64) {
+        let extra: Vec<u32> = self
+            .map
+            .slots
+            .values()
+            .copied()
+            .filter(|&slot| slot >= self.grid_tiles)
+            .collect();
+        for slot in extra {
+            self.map.unlink(slot);
+        }
+        self.is_full = false;
+        // flags still being read back are for tiles that are gone
+        self.flags = Arc::default();
+        self.reading = None;
+        self.flag_generation = generation;
+        let zeroes = vec![0_u32; self.grid_tiles as usize * TILE_CELLS];
+        for buffer in &self.cell_buffers {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&zeroes));
+        }
+        self.upload(queue);
+    }
+
+    /// Steps every tile, taking the grid from and giving it back to
+    /// `game_state_bind_group`. `is_a_current` picks which tile cells are
+    /// current the same way it does for the grid.
+    pub fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        config: &AppConfig,
+        compute_uniform_bind_group: &wgpu::BindGroup,
+        game_state_bind_group: &wgpu::BindGroup,
+        is_a_current: bool,
+    ) {
+        encoder.clear_buffer(&self.flag_buffer, 0, None);
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Tile Compute Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(0, compute_uniform_bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.bind_groups[!is_a_current as usize], &[]);
+        compute_pass.set_bind_group(2, game_state_bind_group, &[]);
+
+        let (grid_x, grid_y) = (
+            config.compute_dispatches[0] as u32,
+            config.compute_dispatches[1] as u32,
+        );
+        compute_pass.set_pipeline(&self.load_pipeline);
+        compute_pass.dispatch_workgroups(grid_x, grid_y, 1);
+        // a workgroup for each quarter of each tile
+        compute_pass.set_pipeline(&self.step_pipeline);
+        compute_pass.dispatch_workgroups(self.uniform.count, 4, 1);
+        compute_pass.set_pipeline(&self.store_pipeline);
+        compute_pass.dispatch_workgroups(grid_x, grid_y, 1);
+    }
+
+    /// Whether generation `generation` can be stepped without the flags in
+    /// use getting too old
+    pub fn can_step(&self, generation: u64) -> bool {
+        generation.saturating_sub(self.flag_generation) < STEPS_BETWEEN_UPDATES as u64
+    }
+
+    /// Adds and frees tiles using the last flags read back, and starts
+    /// reading the flags of generation `generation`. Has to be called after
+    /// every step so new tiles have been flagged before they're looked at.
+    /// On desktop this blocks on the flags rather than let them fall behind,
+    /// elsewhere [`Tiles::can_step`] says when to wait for them.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, generation: u64) {
+        // desktop map callbacks only run when the device is polled
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = device.poll(wgpu::PollType::Poll) {
+            log::error!("Unable to poll the device: {e}");
+        }
+        let flags = self.flags.lock().unwrap().take();
+        if let (Some(flags), Some(read_at)) = (flags, self.reading) {
+            self.reading = None;
+            match flags {
+                Ok(flags) => {
+                    self.apply_flags(&flags, queue);
+                    self.flag_generation = read_at;
+                }
+                Err(e) => log::error!("Unable to read the tile flags: {e}"),
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if !self.can_step(generation) {
+            if let Err(e) = self.update_blocking(device, queue, generation) {
+                log::error!("Unable to update the tiles: {e}");
+            }
+            return;
+        }
+        if self.reading.is_none() {
+            self.reading = Some(generation);
+            let slot = self.flags.clone();
+            readback::read_buffer(device, queue, &self.flag_buffer, move |bytes| {
+                let flags = bytes.map(|bytes| bytemuck::cast_slice(&bytes).to_vec());
+                *slot.lock().unwrap() = Some(flags);
+            });
+        }
+    }
+
+    /// Blocking version of [`Tiles::update`] that always reads the flags of
+    /// generation `generation`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn update_blocking(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        generation: u64,
+    ) -> anyhow::Result<()> {
+        let bytes = readback::read_buffer_blocking(device, queue, &self.flag_buffer)?;
+        // anything read before is out of date
+        self.flags = Arc::default();
+        self.reading = None;
+        self.apply_flags(bytemuck::cast_slice(&bytes), queue);
+        self.flag_generation = generation;
+        Ok(())
+    }
+
+    fn apply_flags(&mut self, flags: &[u32], queue: &wgpu::Queue) {
+        let in_use: Vec<(u32, (i32, i32))> = self
+            .map
+            .slots
+            .iter()
+            .map(|(&position, &slot)| (slot, position))
+            .collect();
+
+        // a tile is still needed if it has live cells or a neighbour's live
+        // cells are heading for it
+        let is_needed = |slot: u32| {
+            flags[slot as usize] & ALIVE != 0
+                || self.map.tiles[slot as usize]
+                    .neighbours
+                    .iter()
+                    .enumerate()
+                    .any(|(direction, &neighbour)| {
+                        let towards = (direction + 4) % 8;
+                        neighbour != EMPTY && flags[neighbour as usize] & (2 << towards) != 0
+                    })
+        };
+        let unneeded: Vec<u32> = in_use
+            .iter()
+            .map(|&(slot, _)| slot)
+            .filter(|&slot| slot >= self.grid_tiles && !is_needed(slot))
+            .collect();
+        let wanted: Vec<(i32, i32)> = in_use
+            .iter()
+            .flat_map(|&(slot, (x, y))| {
+                DIRECTIONS
+                    .iter()
+                    .enumerate()
+                    .filter(move |&(direction, _)| flags[slot as usize] & (2 << direction) != 0)
+                    .map(move |(_, (dx, dy))| (x + dx, y + dy))
+            })
+            .collect();
+
+        if !unneeded.is_empty() {
+            self.is_full = false;
+        }
+        for slot in unneeded {
+            self.map.unlink(slot);
+        }
+        for position in wanted {
+            if self.map.slots.contains_key(&position) {
+                continue;
+            }
+            let Some(slot) = self.map.free.pop() else {
+                if !self.is_full {
+                    log::warn!("Out of tiles, cells beyond the last ones die");
+                    self.is_full = true;
+                }
+                break;
+            };
+            let zeroes = [0_u32; TILE_CELLS];
+            let offset = (slot as usize * TILE_CELLS * size_of::<u32>()) as u64;
+            for buffer in &self.cell_buffers {
+                queue.write_buffer(buffer, offset, bytemuck::cast_slice(&zeroes));
+            }
+            self.map.link(slot, position);
+        }
+        self.upload(queue);
+    }
+
+    /// Sends the tiles and the list of ones to step to the GPU
+    fn upload(&mut self, queue: &wgpu::Queue) {
+        let list = self.map.list();
+        self.uniform.count = list.len() as u32;
+        queue.write_buffer(&self.tile_buffer, 0, bytemuck::cast_slice(&self.map.tiles));
+        queue.write_buffer(&self.list_buffer, 0, bytemuck::cast_slice(&list));
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&self.uniform));
+    }
+}
+
+/// Which tile is in which slot, and their neighbours
+struct TileMap {
+    /// the slot of each tile by its position in tiles, with the grid's
+    /// bottom left tile at (0, 0)
+    slots: HashMap<(i32, i32), u32>,
+    /// the position of the tile in each slot, if it's in use
+    positions: Vec<Option<(i32, i32)>>,
+    tiles: Vec<GpuTile>,
+    free: Vec<u32>,
+}
+
+impl TileMap {
+    /// A map with the tiles under the grid in the first slots, a row of
+    /// `grid_tiles_x` at a time
+    fn new(capacity: usize, grid_tiles_x: usize, grid_tiles: usize) -> Self {
+        let mut map = Self {
+            slots: HashMap::new(),
+            positions: vec![None; capacity],
+            tiles: vec![GpuTile::UNUSED; capacity],
+            free: (grid_tiles as u32..capacity as u32).rev().collect(),
+        };
+        for slot in 0..grid_tiles {
+            let position = ((slot % grid_tiles_x) as i32, (slot / grid_tiles_x) as i32);
+            map.link(slot as u32, position);
+        }
+        map
+    }
+
+    /// the slots in use, in order
+    fn list(&self) -> Vec<u32> {
+        let mut list: Vec<u32> = self.slots.values().copied().collect();
+        list.sort_unstable();
+        list
+    }
+
+    /// Puts a tile in `slot` and connects it to its neighbours
+    fn link(&mut self, slot: u32, (x, y): (i32, i32)) {
+        self.slots.insert((x, y), slot);
+        self.positions[slot as usize] = Some((x, y));
+        for (direction, (dx, dy)) in DIRECTIONS.iter().enumerate() {
+            let neighbour = self.slots.get(&(x + dx, y + dy)).copied();
+            self.tiles[slot as usize].neighbours[direction] = neighbour.unwrap_or(EMPTY);
+            if let Some(neighbour) = neighbour {
+                self.tiles[neighbour as usize].neighbours[(direction + 4) % 8] = slot;
+            }
+        }
+    }
+
+    /// Frees the tile in `slot` and disconnects it from its neighbours
+    fn unlink(&mut self, slot: u32) {
+        let Some(position) = self.positions[slot as usize].take() else {
+            return;
+        };
+        self.slots.remove(&position);
+        for (direction, neighbour) in self.tiles[slot as usize].neighbours.into_iter().enumerate() {
+            if neighbour != EMPTY {
+                self.tiles[neighbour as usize].neighbours[(direction + 4) % 8] = EMPTY;
+            }
+        }
+        self.tiles[slot as usize] = GpuTile::UNUSED;
+        self.free.push(slot);
+    }
+}
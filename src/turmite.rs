@@ -0,0 +1,533 @@
+// Turmites: agents that walk over the grid, like Langton's ant.
+//
+// Each generation every turmite reads the colour (state) of its cell, then
+// its rule says what colour to write, which way to turn and which state of
+// its own to go into, and it steps forward one cell. Rules are written as a
+// turn for each colour, e.g. `RL` for Langton's ant or `LLRR`, where each
+// colour is replaced by the next. Full turmites use Golly's notation:
+//
+//   {{{1, 2, 0}, {0, 8, 0}}}
+//
+// with a list for each turmite state, holding {colour to write, turn, next
+// state} for each colour. Turns are 1 for none, 2 right, 4 U-turn and 8 left.
+//
+// Turmites walk off bounded grids and halt, and wrap around on a torus. They
+// move one after another, so ones on the same cell take turns.
+
+use anyhow::{bail, ensure};
+use std::str::FromStr;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    config::{AppConfig, SimulationMode, Topology, TurmiteMode},
+    rule::MAX_STATES,
+};
+
+/// the most turmites the grid can hold at once
+pub const MAX_TURMITES: u32 = 1024;
+
+/// What a turmite does on a cell of each colour in each of its states
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TurmiteRule {
+    /// how many cell states the turmite reads and writes
+    pub colours: u32,
+    /// for each state, what to do on each colour
+    pub transitions: Vec<Vec<Transition>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub write: u32,
+    /// quarter turns clockwise
+    pub turn: u32,
+    pub next_state: u32,
+}
+
+impl Default for TurmiteRule {
+    fn default() -> Self {
+        // Langton's ant, RL
+        "RL".parse().unwrap()
+    }
+}
+
+impl TurmiteRule {
+    /// Transitions as `write | turn << 8 | next state << 16`, state by state
+    fn packed(&self) -> Vec<u32> {
+        self.transitions
+            .iter()
+            .flatten()
+            .map(|t| t.write | (t.turn << 8) | (t.next_state << 16))
+            .collect()
+    }
+}
+
+/// Parses `RL` style rules, where colour c turns by the cth letter and
+/// becomes colour c + 1
+fn parse_turns(rule: &str) -> anyhow::Result<TurmiteRule> {
+    let colours = rule.len() as u32;
+    let transitions = rule
+        .chars()
+        .enumerate()
+        .map(|(colour, letter)| {
+            let turn = match letter.to_ascii_uppercase() {
+                'N' => 0,
+                'R' => 1,
+                'U' => 2,
+                'L' => 3,
+                _ => bail!("{letter:?} is not a turn, use L, R, N or U"),
+            };
+            Ok(Transition {
+                write: (colour as u32 + 1) % colours,
+                turn,
+                next_state: 0,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok(TurmiteRule {
+        colours,
+        transitions: vec![transitions],
+    })
+}
+
+/// Parses Golly's `{{{write, turn, next}, ...}, ...}` notation
+fn parse_golly(rule: &str) -> anyhow::Result<TurmiteRule> {
+    // the numbers of each transition of each state
+    let mut states: Vec<Vec<Vec<u32>>> = Vec::new();
+    let mut depth = 0;
+    let mut number = String::new();
+    for c in rule.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        if !number.is_empty() {
+            let transition = states.last_mut().and_then(|state| state.last_mut());
+            match transition {
+                Some(transition) if depth == 3 => transition.push(number.parse()?),
+                _ => bail!("{number} is outside of a {{write, turn, next state}} list"),
+            }
+            number.clear();
+        }
+        match c {
+            '{' => {
+                depth += 1;
+                match depth {
+                    1 => (),
+                    2 => states.push(Vec::new()),
+                    3 => states
+                        .last_mut()
+                        .into_iter()
+                        .for_each(|s| s.push(Vec::new())),
+                    _ => bail!("turmite {rule:?} has too many braces"),
+                }
+            }
+            '}' => {
+                ensure!(depth > 0, "turmite {rule:?} has unmatched braces");
+                depth -= 1;
+            }
+            ',' => (),
+            c if c.is_whitespace() => (),
+            c => bail!("unexpected {c:?} in turmite {rule:?}"),
+        }
+    }
+    ensure!(depth == 0, "turmite {rule:?} has unmatched braces");
+
+    let colours = states.first().map_or(0, Vec::len);
+    ensure!(
+        (2..=MAX_STATES as usize).contains(&colours),
+        "turmites must have between 2 and {MAX_STATES} colours"
+    );
+    let transitions = states
+        .iter()
+        .map(|state| {
+            ensure!(
+                state.len() == colours,
+                "every turmite state needs a transition for each of its {colours} colours"
+            );
+            state
+                .iter()
+                .map(|transition| {
+                    let &[write, turn, next_state] = transition.as_slice() else {
+                        bail!("{transition:?} should be {{write, turn, next state}}");
+                    };
+                    let turn = match turn {
+                        1 => 0,
+                        2 => 1,
+                        4 => 2,
+                        8 => 3,
+                        turn => bail!("{turn} is not a turn, use 1, 2, 4 or 8"),
+                    };
+                    ensure!((write as usize) < colours, "colour {write} is out of range");
+                    ensure!(
+                        (next_state as usize) < states.len(),
+                        "state {next_state} is out of range"
+                    );
+                    Ok(Transition {
+                        write,
+                        turn,
+                        next_state,
+                    })
+                })
+                .collect()
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok(TurmiteRule {
+        colours: colours as u32,
+        transitions,
+    })
+}
+
+impl FromStr for TurmiteRule {
+    type Err = anyhow::Error;
+
+    fn from_str(rule: &str) -> anyhow::Result<Self> {
+        let rule = rule.trim();
+        if rule.starts_with('{') {
+            parse_golly(rule)
+        } else {
+            ensure!(
+                (2..=MAX_STATES as usize).contains(&rule.len()),
+                "turmites must have between 2 and {MAX_STATES} colours"
+            );
+            parse_turns(rule)
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuTurmite {
+    x: i32,
+    y: i32,
+    /// 0 up, 1 right, 2 down, 3 left
+    direction: u32,
+    state: u32,
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TurmiteUniform {
+    rows: u32,
+    cols: u32,
+    wrap: u32,
+    count: u32,
+    colours: u32,
+    _pad: [u32; 3],
+}
+
+/// The turmites on the grid and the pipeline that moves them
+pub struct Turmites {
+    pipeline: wgpu::ComputePipeline,
+    uniform: TurmiteUniform,
+    uniform_buffer: wgpu::Buffer,
+    turmite_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// where the config starts them
+    start: Vec<GpuTurmite>,
+    /// the turmites for the render pipeline to draw
+    pub render_bind_group: wgpu::BindGroup,
+}
+
+impl Turmites {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &AppConfig,
+        game_state_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let rule = &config.turmite_rule;
+        let start: Vec<GpuTurmite> = config
+            .turmites
+            .start
+            .iter()
+            .map(|&[x, y]| GpuTurmite {
+                x: config.cols as i32 / 2 + x,
+                y: config.rows as i32 / 2 + y,
+                direction: 0,
+                state: 0,
+            })
+            .filter(|turmite| {
+                let on_grid = (0..config.cols as i32).contains(&turmite.x)
+                    && (0..config.rows as i32).contains(&turmite.y);
+                if !on_grid {
+                    log::warn!(
+                        "Turmite start ({}, {}) is off the grid",
+                        turmite.x,
+                        turmite.y
+                    );
+                }
+                on_grid
+            })
+            .take(MAX_TURMITES as usize)
+            .collect();
+        let uniform = TurmiteUniform {
+            rows: config.rows as u32,
+            cols: config.cols as u32,
+            wrap: (config.topology == Topology::Torus) as u32,
+            count: start.len() as u32,
+            colours: rule.colours,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Turmite Uniform Buffer"),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let transition_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Turmite Transition Buffer"),
+            contents: bytemuck::cast_slice(&rule.packed()),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let mut turmites = start.clone();
+        turmites.resize(MAX_TURMITES as usize, bytemuck::Zeroable::zeroed());
+        let turmite_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Turmite Buffer"),
+            contents: bytemuck::cast_slice(&turmites),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let buffer_entry = |binding, ty, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Turmite Bind Group Layout"),
+            entries: &[
+                buffer_entry(
+                    0,
+                    wgpu::BufferBindingType::Uniform,
+                    wgpu::ShaderStages::COMPUTE,
+                ),
+                buffer_entry(
+                    1,
+                    wgpu::BufferBindingType::Storage { read_only: true },
+                    wgpu::ShaderStages::COMPUTE,
+                ),
+                buffer_entry(
+                    2,
+                    wgpu::BufferBindingType::Storage { read_only: false },
+                    wgpu::ShaderStages::COMPUTE,
+                ),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Turmite Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: transition_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: turmite_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Turmite Render Bind Group"),
+            layout: &Turmites::get_render_bind_group_layout(device),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: turmite_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Turmite Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, game_state_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Turmite shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/turmite.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Turmite Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache: None,
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        });
+
+        Self {
+            pipeline,
+            uniform,
+            uniform_buffer,
+            turmite_buffer,
+            bind_group,
+            start,
+            render_bind_group,
+        }
+    }
+
+    pub fn get_render_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Turmite Render Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        })
+    }
+
+    /// Turmites only walk square grids of whole number states
+    pub fn is_active(config: &AppConfig) -> bool {
+        config.mode == SimulationMode::Discrete
+            && !config.rule.is_hexagonal()
+            && !config.rule.is_one_dimensional()
+    }
+
+    /// Whether the turmites move instead of the rule stepping the grid
+    pub fn replace_rule(config: &AppConfig) -> bool {
+        Self::is_active(config) && config.turmites.mode == TurmiteMode::Replace
+    }
+
+    /// how many turmites there are, halted or not
+    pub fn count(&self) -> u32 {
+        self.uniform.count
+    }
+
+    /// Puts the turmites back where the config starts them
+    pub fn reset(&mut self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.turmite_buffer, 0, bytemuck::cast_slice(&self.start));
+        self.uniform.count = self.start.len() as u32;
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&self.uniform));
+    }
+
+    /// Adds a turmite facing up on the cell at column `x`, row `y`
+    pub fn place(&mut self, x: u32, y: u32, queue: &wgpu::Queue) {
+        if self.uniform.count >= MAX_TURMITES {
+            log::warn!("There's no room for more than {MAX_TURMITES} turmites");
+            return;
+        }
+        let turmite = GpuTurmite {
+            x: x as i32,
+            y: y as i32,
+            direction: 0,
+            state: 0,
+        };
+        let offset = self.uniform.count as usize * size_of::<GpuTurmite>();
+        queue.write_buffer(
+            &self.turmite_buffer,
+            offset as u64,
+            bytemuck::bytes_of(&turmite),
+        );
+        self.uniform.count += 1;
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&self.uniform));
+    }
+
+    /// Moves every turmite over the grid being written this step, which is
+    /// group 1 binding 1 of `game_state_bind_group`
+    pub fn encode(
+        &self,
+        compute_pass: &mut wgpu::ComputePass,
+        game_state_bind_group: &wgpu::BindGroup,
+    ) {
+        if self.uniform.count == 0 {
+            return;
+        }
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.set_bind_group(1, game_state_bind_group, &[]);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transition(write: u32, turn: u32, next_state: u32) -> Transition {
+        Transition {
+            write,
+            turn,
+            next_state,
+        }
+    }
+
+    #[test]
+    fn turns_give_each_colour_the_next_one() {
+        let ant: TurmiteRule = "RL".parse().unwrap();
+        assert_eq!(ant.colours, 2);
+        assert_eq!(
+            ant.transitions,
+            [[transition(1, 1, 0), transition(0, 3, 0)]]
+        );
+
+        let rule: TurmiteRule = " nrul ".parse().unwrap();
+        assert_eq!(rule.colours, 4);
+        let turns: Vec<u32> = rule.transitions[0].iter().map(|t| t.turn).collect();
+        assert_eq!(turns, [0, 1, 2, 3]);
+        let writes: Vec<u32> = rule.transitions[0].iter().map(|t| t.write).collect();
+        assert_eq!(writes, [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn bad_turns_are_rejected() {
+        for rule in ["", "R", "RX", "R L", &"RL".repeat(129)] {
+            assert!(rule.parse::<TurmiteRule>().is_err(), "{rule:?}");
+        }
+    }
+
+    #[test]
+    fn golly_turmites_parse() {
+        let ant: TurmiteRule = "{{{1, 2, 0}, {0, 8, 0}}}".parse().unwrap();
+        assert_eq!(ant, "RL".parse().unwrap());
+
+        // a two state turmite
+        let rule: TurmiteRule = "{{{1,2,1},{0,1,0}},{{1,8,0},{1,4,1}}}".parse().unwrap();
+        assert_eq!(rule.colours, 2);
+        assert_eq!(
+            rule.transitions,
+            [
+                [transition(1, 1, 1), transition(0, 0, 0)],
+                [transition(1, 3, 0), transition(1, 2, 1)],
+            ]
+        );
+        assert_eq!(
+            rule.packed(),
+            [1 | 1 << 8 | 1 << 16, 0, 1 | 3 << 8, 1 | 2 << 8 | 1 << 16]
+        );
+    }
+
+    #[test]
+    fn bad_golly_turmites_are_rejected() {
+        for rule in [
+            // unmatched braces
+            "{{{1, 2, 0}, {0, 8, 0}}",
+            "{{{1, 2, 0}, {0, 8, 0}}}}",
+            "{{{{1, 2, 0}}}}",
+            // a number outside a transition
+            "{{1, {1, 2, 0}, {0, 8, 0}}}",
+            // 3 isn't a turn
+            "{{{1, 3, 0}, {0, 8, 0}}}",
+            // colour 2 and state 1 don't exist
+            "{{{2, 2, 0}, {0, 8, 0}}}",
+            "{{{1, 2, 1}, {0, 8, 0}}}",
+            // the second state is missing a colour
+            "{{{1, 2, 1}, {0, 8, 0}}, {{1, 2, 0}}}",
+            "{{{1, 2}, {0, 8, 0}}}",
+            "{{{1, 2, 0}}}",
+            "{{{1; 2, 0}, {0, 8, 0}}}",
+        ] {
+            assert!(rule.parse::<TurmiteRule>().is_err(), "{rule:?}");
+        }
+    }
+}
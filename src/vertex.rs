@@ -25,16 +25,48 @@ pub const CELL_VERTICES: &[Vertex] = &[
     },
 ];
 
+/// A pointy topped hexagon around the middle with its corners at the edges of
+/// the cell, so neighbouring rows shifted by half a cell slot together
+pub const HEX_CELL_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [0.0, 0.0],
+    },
+    Vertex {
+        position: [0.0, -2.0 / 3.0],
+    },
+    Vertex {
+        position: [0.5, -1.0 / 3.0],
+    },
+    Vertex {
+        position: [0.5, 1.0 / 3.0],
+    },
+    Vertex {
+        position: [0.0, 2.0 / 3.0],
+    },
+    Vertex {
+        position: [-0.5, 1.0 / 3.0],
+    },
+    Vertex {
+        position: [-0.5, -1.0 / 3.0],
+    },
+];
+
 pub fn get_instances(
     rows: usize,
     cols: usize,
     gap_size: (f32, f32),
     cell_size: (f32, f32),
+    hexagonal: bool,
 ) -> Vec<Instance> {
     let mut result = Vec::with_capacity(rows * cols);
     for row in 0..rows {
+        // odd rows of hexagons are shifted half a cell right
+        let shift = if hexagonal && row % 2 == 1 { 0.5 } else { 0.0 };
         for col in 0..cols {
-            let x = -1.0 + gap_size.0 + cell_size.0 / 2.0 + col as f32 * (cell_size.0 + gap_size.0);
+            let x = -1.0
+                + gap_size.0
+                + cell_size.0 / 2.0
+                + (col as f32 + shift) * (cell_size.0 + gap_size.0);
             let y = -1.0 + gap_size.1 + cell_size.1 / 2.0 + row as f32 * (cell_size.1 + gap_size.1);
 
             result.push(Instance { position: [x, y] });
@@ -45,6 +77,18 @@ pub fn get_instances(
 
 pub const INDICES: &[u16] = &[0, 1, 2, 2, 3, 0];
 
+/// a fan of triangles around the middle of the hexagon
+pub const HEX_INDICES: &[u16] = &[0, 1, 2, 0, 2, 3, 0, 3, 4, 0, 4, 5, 0, 5, 6, 0, 6, 1];
+
+/// The vertices and indices of one cell
+pub fn cell_shape(hexagonal: bool) -> (&'static [Vertex], &'static [u16]) {
+    if hexagonal {
+        (HEX_CELL_VERTICES, HEX_INDICES)
+    } else {
+        (CELL_VERTICES, INDICES)
+    }
+}
+
 impl Vertex {
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
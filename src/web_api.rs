@@ -0,0 +1,209 @@
+// What's behind the JavaScript API in web_controls.rs: Promises answered
+// from inside the event loop, the callbacks pages subscribe with, and
+// watching for the grid to settle.
+//
+// The grid only lives on the GPU, so anything that looks at it waits for a
+// readback and answers later. Settling is spotted by hashing the grid after
+// each generation, see `analysis::Settling`. While a page is waiting for
+// that, the next generation isn't stepped until the last one has been read
+// back, so none are skipped and the period comes out right.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use js_sys::{Function, Promise};
+use wasm_bindgen::JsValue;
+
+use crate::{
+    analysis::Settling,
+    app::{AppEvents, send_app_event},
+    config::{SimulationMode, Topology},
+};
+
+/// The two ends of a Promise handed to JavaScript
+pub struct Reply {
+    resolve: Function,
+    reject: Function,
+}
+
+impl Reply {
+    /// A Promise answered by whatever handles the event `make` builds
+    pub fn promise(make: impl FnOnce(Reply) -> AppEvents) -> Promise {
+        let mut make = Some(make);
+        Promise::new(&mut |resolve, reject| {
+            if let Some(make) = make.take() {
+                send_app_event(make(Reply { resolve, reject }));
+            }
+        })
+    }
+
+    pub fn resolve(&self, value: &JsValue) {
+        if let Err(e) = self.resolve.call1(&JsValue::NULL, value) {
+            log::error!("Unable to resolve a promise: {e:?}");
+        }
+    }
+
+    pub fn reject(&self, message: &str) {
+        if let Err(e) = self
+            .reject
+            .call1(&JsValue::NULL, &js_sys::Error::new(message))
+        {
+            log::error!("Unable to reject a promise: {e:?}");
+        }
+    }
+}
+
+/// Callbacks a page has subscribed with
+#[derive(Default)]
+pub struct Listeners {
+    generation: Vec<Function>,
+    stable: Vec<Function>,
+    settling: Settling,
+    is_checking: bool,
+}
+
+impl Listeners {
+    pub fn on_generation(&mut self, callback: Function) {
+        self.generation.push(callback);
+    }
+
+    pub fn on_stable(&mut self, callback: Function) {
+        self.stable.push(callback);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self {
+            is_checking: self.is_checking,
+            ..Default::default()
+        };
+    }
+
+    /// Tells the generation callbacks about `generation`
+    pub fn generation(&self, generation: u64) {
+        for callback in &self.generation {
+            if let Err(e) = callback.call1(&JsValue::NULL, &JsValue::from(generation as f64)) {
+                log::error!("Generation callback failed: {e:?}");
+            }
+        }
+    }
+
+    /// Whether the grid should be read back for `check`, marking it as
+    /// being read if so
+    pub fn start_check(&mut self) -> bool {
+        let start = !self.stable.is_empty() && !self.is_checking;
+        self.is_checking |= start;
+        start
+    }
+
+    /// Whether a generation is still being read back, which stops the next
+    /// one being stepped
+    pub fn is_checking(&self) -> bool {
+        self.is_checking
+    }
+
+    /// Lets the next generation be read back after this one couldn't be
+    pub fn check_failed(&mut self) {
+        self.is_checking = false;
+    }
+
+    /// Looks for the grid's hash at `generation` among the earlier ones,
+    /// calling the stable callbacks with the generation and period the
+    /// first time it's found
+    pub fn check(&mut self, generation: u64, hash: u64) {
+        self.is_checking = false;
+        let Some(period) = self.settling.check(generation, hash) else {
+            return;
+        };
+        for callback in &self.stable {
+            let result = callback.call2(
+                &JsValue::NULL,
+                &JsValue::from(generation as f64),
+                &JsValue::from(period as f64),
+            );
+            if let Err(e) = result {
+                log::error!("Stable callback failed: {e:?}");
+            }
+        }
+    }
+}
+
+pub fn grid_hash(state: &[u32]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A byte for each cell: its state, or its value from 0 to 1 scaled up to
+/// 255 in continuous mode
+pub fn cells_to_bytes(state: &[u32], mode: SimulationMode) -> Vec<u8> {
+    state
+        .iter()
+        .map(|&cell| match mode {
+            SimulationMode::Discrete => cell.min(u8::MAX as u32) as u8,
+            SimulationMode::Continuous => {
+                (f32::from_bits(cell).clamp(0.0, 1.0) * 255.0).round() as u8
+            }
+        })
+        .collect()
+}
+
+/// The other way round from `cells_to_bytes`
+pub fn bytes_to_cells(bytes: &[u8], mode: SimulationMode) -> Vec<u32> {
+    bytes
+        .iter()
+        .map(|&byte| match mode {
+            SimulationMode::Discrete => byte as u32,
+            SimulationMode::Continuous => (byte as f32 / 255.0).to_bits(),
+        })
+        .collect()
+}
+
+/// Topologies by the names the config uses
+pub fn parse_topology(name: &str) -> Option<Topology> {
+    match name {
+        "bounded" => Some(Topology::Bounded),
+        "torus" => Some(Topology::Torus),
+        "unbounded" => Some(Topology::Unbounded),
+        _ => None,
+    }
+}
+
+pub fn topology_name(topology: Topology) -> &'static str {
+    match topology {
+        Topology::Bounded => "bounded",
+        Topology::Torus => "torus",
+        Topology::Unbounded => "unbounded",
+    }
+}
+
+/// What `getStatus` resolves with
+pub struct Status {
+    pub generation: u64,
+    pub population: u64,
+    pub rows: usize,
+    pub cols: usize,
+    pub rule: String,
+    pub topology: Topology,
+    pub paused: bool,
+    pub fps: f64,
+}
+
+impl Status {
+    pub fn to_js(&self) -> JsValue {
+        let object = js_sys::Object::new();
+        let fields = [
+            ("generation", JsValue::from(self.generation as f64)),
+            ("population", JsValue::from(self.population as f64)),
+            ("rows", JsValue::from(self.rows as f64)),
+            ("cols", JsValue::from(self.cols as f64)),
+            ("rule", JsValue::from_str(&self.rule)),
+            ("topology", JsValue::from_str(topology_name(self.topology))),
+            ("paused", JsValue::from_bool(self.paused)),
+            ("fps", JsValue::from(self.fps)),
+        ];
+        for (name, value) in fields {
+            // setting a property on a plain object can't fail
+            let _ = js_sys::Reflect::set(&object, &JsValue::from_str(name), &value);
+        }
+        object.into()
+    }
+}
@@ -3,6 +3,9 @@
 // these will be exported using wasm-bindgen
 
 use crate::app::{AppEvents, EVENT_LOOP_PROXY};
+use crate::rule::Rule;
+use crate::web_api::{Reply, parse_topology};
+use js_sys::{Function, Promise};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen(js_name = "playPause")]
@@ -71,3 +74,262 @@ pub fn reset_state() {
         }
     })
 }
+
+#[wasm_bindgen(js_name = "census")]
+pub fn census() {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::Census);
+            }
+        }
+    })
+}
+
+#[wasm_bindgen(js_name = "saveSnapshot")]
+pub fn save_snapshot() {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::SaveSnapshot);
+            }
+        }
+    })
+}
+
+#[wasm_bindgen(js_name = "loadSnapshot")]
+pub fn load_snapshot() {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::LoadSnapshot);
+            }
+        }
+    })
+}
+
+#[wasm_bindgen(js_name = "downloadSnapshot")]
+pub fn download_snapshot() {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::DownloadSnapshot);
+            }
+        }
+    })
+}
+
+/// Restores a snapshot file the user picked, passed in as a Uint8Array
+#[wasm_bindgen(js_name = "loadSnapshotFile")]
+pub fn load_snapshot_file(bytes: Vec<u8>) {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::LoadSnapshotBytes(bytes));
+            }
+        }
+    })
+}
+
+#[wasm_bindgen(js_name = "exportPng")]
+pub fn export_png() {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::ExportPng);
+            }
+        }
+    })
+}
+
+#[wasm_bindgen(js_name = "toggleRecording")]
+pub fn toggle_recording() {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::ToggleRecording);
+            }
+        }
+    })
+}
+
+/// Draws a PNG or JPEG, passed in as a Uint8Array, onto the grid
+#[wasm_bindgen(js_name = "importImage")]
+pub fn import_image(bytes: Vec<u8>) {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::ImportImage(bytes));
+            }
+        }
+    })
+}
+
+/// Places an RLE pattern in the middle of the grid
+#[wasm_bindgen(js_name = "loadPattern")]
+pub fn load_pattern(rle: String) {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::LoadPattern(rle));
+            }
+        }
+    })
+}
+
+#[wasm_bindgen(js_name = "savePattern")]
+pub fn save_pattern() {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::SavePattern);
+            }
+        }
+    })
+}
+
+/// Switches to the rule in a Golly `.rule` or `.table` file
+#[wasm_bindgen(js_name = "loadRule")]
+pub fn load_rule(text: String, file_name: String) {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let name = file_name
+                    .trim_end_matches(".rule")
+                    .trim_end_matches(".table")
+                    .to_string();
+                let _ = proxy.send_event(AppEvents::LoadRule(text, name));
+            }
+        }
+    })
+}
+
+/// Puts the grid in the page's URL hash so the link can be shared
+#[wasm_bindgen(js_name = "shareLink")]
+pub fn share_link() {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::ShareLink);
+            }
+        }
+    })
+}
+
+/// Picks which state the mouse paints cells in
+#[wasm_bindgen(js_name = "setPaintState")]
+pub fn set_paint_state(state: u32) {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::SetPaintState(state));
+            }
+        }
+    })
+}
+
+// The rest are for pages embedding the simulator, see web_api.rs. Anything
+// that looks at the grid answers with a Promise, since the grid has to be
+// read back from the GPU first.
+
+/// Resolves with a Uint8Array of every cell, `x + cols * y` from the bottom
+/// left. Continuous mode scales each value from 0 to 1 up to 255.
+#[wasm_bindgen(js_name = "getCells")]
+pub fn get_cells() -> Promise {
+    Reply::promise(AppEvents::GetCells)
+}
+
+/// Replaces every cell, laid out the way `getCells` gives them
+#[wasm_bindgen(js_name = "setCells")]
+pub fn set_cells(cells: Vec<u8>) {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::SetCells(cells));
+            }
+        }
+    })
+}
+
+/// Resolves with the generation, population, grid size, rule, topology,
+/// whether it's paused and the frame rate
+#[wasm_bindgen(js_name = "getStatus")]
+pub fn get_status() -> Promise {
+    Reply::promise(AppEvents::GetStatus)
+}
+
+/// Resolves with the grid as RLE text
+#[wasm_bindgen(js_name = "exportPattern")]
+pub fn export_pattern() -> Promise {
+    Reply::promise(AppEvents::ExportPattern)
+}
+
+/// Switches to a rule like "B3/S23", starting again on an empty grid
+#[wasm_bindgen(js_name = "setRule")]
+pub fn set_rule(rule: &str) -> Result<(), JsError> {
+    let rule: Rule = rule.parse().map_err(|e| JsError::new(&format!("{e}")))?;
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::SetRule(rule));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Switches to "bounded", "torus" or "unbounded", starting again on an
+/// empty grid
+#[wasm_bindgen(js_name = "setTopology")]
+pub fn set_topology(topology: &str) -> Result<(), JsError> {
+    let topology = parse_topology(topology).ok_or_else(|| {
+        JsError::new(&format!(
+            "{topology:?} isn't \"bounded\", \"torus\" or \"unbounded\""
+        ))
+    })?;
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::SetTopology(topology));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Calls `callback(generation)` after each generation
+#[wasm_bindgen(js_name = "onGeneration")]
+pub fn on_generation(callback: Function) {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::OnGeneration(callback));
+            }
+        }
+    })
+}
+
+/// Calls `callback(generation, period)` once the grid starts repeating,
+/// with a period of 1 for a still life
+#[wasm_bindgen(js_name = "onStable")]
+pub fn on_stable(callback: Function) {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::OnStable(callback));
+            }
+        }
+    })
+}
+
+/// Drops every callback passed to `onGeneration` and `onStable`
+#[wasm_bindgen(js_name = "clearCallbacks")]
+pub fn clear_callbacks() {
+    EVENT_LOOP_PROXY.with(|proxy| {
+        if let Ok(guard) = proxy.lock() {
+            if let Some(proxy) = &*guard {
+                let _ = proxy.send_event(AppEvents::ClearListeners);
+            }
+        }
+    })
+}
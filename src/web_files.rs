@@ -0,0 +1,57 @@
+// Browser helpers for getting data out of the page: file downloads,
+// localStorage and the URL hash.
+
+use wasm_bindgen::{JsCast, JsValue, UnwrapThrowExt};
+
+/// Offers `bytes` to the user as a file download called `file_name`
+pub fn download_bytes(file_name: &str, bytes: &[u8], mime_type: &str) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    // clicking a temporary link is the only way to start a download from script
+    let document = web_sys::window().unwrap_throw().document().unwrap_throw();
+    let anchor: web_sys::HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    web_sys::Url::revoke_object_url(&url)
+}
+
+fn local_storage() -> Result<web_sys::Storage, JsValue> {
+    web_sys::window()
+        .unwrap_throw()
+        .local_storage()?
+        .ok_or_else(|| JsValue::from_str("localStorage is not available"))
+}
+
+pub fn local_storage_set(key: &str, value: &str) -> Result<(), JsValue> {
+    local_storage()?.set_item(key, value)
+}
+
+pub fn local_storage_get(key: &str) -> Result<Option<String>, JsValue> {
+    local_storage()?.get_item(key)
+}
+
+/// The page's URL hash, without the `#`
+pub fn location_hash() -> Result<String, JsValue> {
+    let hash = web_sys::window().unwrap_throw().location().hash()?;
+    Ok(hash.trim_start_matches('#').to_string())
+}
+
+/// The page's URL up to its hash
+pub fn location_without_hash() -> Result<String, JsValue> {
+    let href = web_sys::window().unwrap_throw().location().href()?;
+    Ok(href.split('#').next().unwrap_or_default().to_string())
+}
+
+/// Swaps the page's URL hash for `hash` without adding to the history
+pub fn replace_location_hash(hash: &str) -> Result<(), JsValue> {
+    web_sys::window()
+        .unwrap_throw()
+        .history()?
+        .replace_state_with_url(&JsValue::NULL, "", Some(&format!("#{hash}")))
+}